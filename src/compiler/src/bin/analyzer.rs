@@ -1,28 +1,37 @@
 use std::env;
 use std::error::Error;
-use std::fs;
 
-use compiler::{config::Config, input, tokenizer};
+use compiler::{
+    config::{Config, Verbosity, USAGE},
+    input, tokenizer,
+};
 
 fn run(config: &Config) -> Result<(), Box<dyn Error>> {
-    let files = input::get_files(config.source_path.clone());
-    // dbg!(&config.source_path);
-    // println!("Files: {:?}", files);
-    for file in files {
-        let tokens_result = tokenizer::tokens_to_xml(tokenizer::tokenize(
-            fs::read_to_string(file.as_path())?.as_str(),
-        )?);
-        let mut target_path = file.clone();
-        target_path.set_extension("out.xml");
-        fs::write(target_path.as_path(), tokens_result)?;
-        println!("Wrote to: {}", target_path.as_path().display());
+    for (file, source) in input::read_sources(&config.source_path)? {
+        let tokens = tokenizer::tokenize_with(source.as_str(), config.options.extensions)?;
+        let tokens_result = tokenizer::tokens_to_xml(tokens);
+        if config.check {
+            continue;
+        }
+        let target_path = config.target_path(&file, "out.xml");
+        input::write_output(&target_path, &tokens_result)?;
+        if config.verbosity >= Verbosity::Normal && !config.writes_stdout() {
+            eprintln!("Wrote to: {}", target_path.as_path().display());
+        }
     }
     Ok(())
 }
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
     let args: Vec<String> = env::args().collect();
-    let config = Config::new(&args).map_err(|err| format!("Problem parsing arguments: {}", err))?;
+    let config = Config::new(&args).unwrap_or_else(|err| {
+        println!("Problem parsing arguments: {}\n\n{}", err, USAGE);
+        std::process::exit(1);
+    });
+    if config.help {
+        print!("{}", USAGE);
+        return Ok(());
+    }
 
     run(&config).map_err(|err| format!("Application error: {}", err).into())
 }
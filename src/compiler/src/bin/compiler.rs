@@ -1,10 +1,20 @@
 use std::{env, error::Error};
 
-use ::compiler::{compiler_cli, config::Config};
+use ::compiler::{
+    compiler_cli,
+    config::{Config, USAGE},
+};
 
 fn main() -> Result<(), Box<dyn Error>> {
     let args: Vec<String> = env::args().collect();
-    let config = Config::new(&args).map_err(|err| format!("Problem parsing arguments: {}", err))?;
+    let config = Config::new(&args).unwrap_or_else(|err| {
+        println!("Problem parsing arguments: {}\n\n{}", err, USAGE);
+        std::process::exit(1);
+    });
+    if config.help {
+        print!("{}", USAGE);
+        return Ok(());
+    }
 
     compiler_cli::run_for_config(&config).map_err(|err| {
         // Print error manually because `main` func error reporter preseves escapes
@@ -0,0 +1,334 @@
+use std::collections::HashMap;
+
+use crate::{diagnostic::Diagnostic, node::*, os, parser, span::Span};
+
+type Res<T = ()> = Result<T, Box<dyn std::error::Error>>;
+
+#[derive(Debug, Clone)]
+pub struct Signature {
+    pub variant: GrammarSubroutineVariant,
+    pub return_type: GrammarSubroutineReturnType,
+    pub params: Vec<GrammarItemType>,
+}
+
+/// Subroutine signatures of every class in the program, by class and subroutine name.
+#[derive(Debug, Default, Clone)]
+pub struct Signatures {
+    classes: HashMap<String, HashMap<String, Signature>>,
+}
+
+impl Signatures {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Signatures of the OS classes in `src/os`.
+    pub fn with_os() -> Res<Self> {
+        let mut signatures = Self::new();
+        for (_, source) in os::SOURCES.iter() {
+            signatures.add_class(&parser::parse(source)?.root);
+        }
+        Ok(signatures)
+    }
+
+    /// Adds the class, replacing a class with the same name, e.g. an OS class
+    /// which is part of the compiled program.
+    pub fn add_class(&mut self, class: &Class) {
+        let Class(name, _, sub_decs, _) = class;
+        let subroutines = sub_decs
+            .iter()
+            .map(|SubroutineDec(variant, return_type, name, params, ..)| {
+                let signature = Signature {
+                    variant: variant.clone(),
+                    return_type: return_type.clone(),
+                    params: params.iter().map(|p| p.type_.clone()).collect(),
+                };
+                (name.clone(), signature)
+            })
+            .collect();
+        self.classes.insert(name.clone(), subroutines);
+    }
+
+    pub fn has_class(&self, class_name: &str) -> bool {
+        self.classes.contains_key(class_name)
+    }
+
+    pub fn lookup(&self, class_name: &str, subroutine: &str) -> Option<&Signature> {
+        self.classes.get(class_name)?.get(subroutine)
+    }
+}
+
+/// Types of the variables visible in a subroutine.
+#[derive(Debug, Default, Clone)]
+pub struct Scope {
+    class_vars: HashMap<String, GrammarItemType>,
+    vars: HashMap<String, GrammarItemType>,
+}
+
+impl Scope {
+    pub fn for_class(var_decs: &[ClassVarDec]) -> Self {
+        let mut scope = Self::default();
+        for ClassVarDec(_, typ, names, _) in var_decs {
+            for name in names {
+                scope.class_vars.insert(name.clone(), typ.clone());
+            }
+        }
+        scope
+    }
+
+    pub fn enter_subroutine(&mut self, params: &[GrammarParamDec], var_decs: &[VarDec]) {
+        self.vars = params
+            .iter()
+            .map(|p| (p.ident.clone(), p.type_.clone()))
+            .collect();
+        for VarDec(typ, names, _) in var_decs {
+            for name in names {
+                self.vars.insert(name.clone(), typ.clone());
+            }
+        }
+    }
+
+    pub fn lookup(&self, name: &str) -> Option<&GrammarItemType> {
+        self.vars.get(name).or_else(|| self.class_vars.get(name))
+    }
+}
+
+pub fn type_name(typ: &GrammarItemType) -> &str {
+    match typ {
+        GrammarItemType::Int => "int",
+        GrammarItemType::Char => "char",
+        GrammarItemType::Boolean => "boolean",
+        GrammarItemType::Class(name) => name,
+    }
+}
+
+pub fn variant_name(variant: &GrammarSubroutineVariant) -> &'static str {
+    match variant {
+        GrammarSubroutineVariant::Constructor => "constructor",
+        GrammarSubroutineVariant::Function => "function",
+        GrammarSubroutineVariant::Method => "method",
+    }
+}
+
+/// Validates every subroutine call in the class against the program's signatures.
+pub fn check_class(class: &Class, signatures: &Signatures) -> Vec<Diagnostic> {
+    let Class(class_name, var_decs, sub_decs, _) = class;
+    let mut checker = Checker {
+        signatures,
+        class_name,
+        scope: Scope::for_class(var_decs),
+        variant: GrammarSubroutineVariant::Function,
+        errors: vec![],
+    };
+    for SubroutineDec(variant, _, _, params, Subroutine(var_decs, statements), _) in sub_decs {
+        checker.variant = variant.clone();
+        checker.scope.enter_subroutine(params, var_decs);
+        checker.check_statements(statements);
+    }
+    checker.errors
+}
+
+struct Checker<'a> {
+    signatures: &'a Signatures,
+    class_name: &'a str,
+    scope: Scope,
+    variant: GrammarSubroutineVariant,
+    errors: Vec<Diagnostic>,
+}
+
+impl<'a> Checker<'a> {
+    fn error<S: Into<String>>(&mut self, message: S, span: Span) {
+        self.errors.push(Diagnostic::error(message, span));
+    }
+
+    fn warning<S: Into<String>>(&mut self, message: S, span: Span) {
+        self.errors.push(Diagnostic::warning(message, span));
+    }
+
+    fn check_statements(&mut self, statements: &[Statement]) {
+        for statement in statements {
+            self.check_statement(statement);
+        }
+    }
+
+    fn check_statement(&mut self, statement: &Statement) {
+        match statement {
+            Statement::LetStatement(stmt) => self.check_let(stmt),
+            Statement::IfStatement(stmt) => {
+                self.check_expression(&stmt.if_expr);
+                self.check_statements(&stmt.if_statements);
+                if let Some(statements) = &stmt.else_statements {
+                    self.check_statements(statements);
+                }
+            }
+            Statement::WhileStatement(stmt) => {
+                self.check_expression(&stmt.cond_expr);
+                self.check_statements(&stmt.statements);
+            }
+            Statement::DoStatement(stmt) => self.check_call(&stmt.call),
+            Statement::ReturnStatement(stmt) => {
+                if let Some(expr) = &stmt.result {
+                    self.check_expression(expr);
+                }
+            }
+            Statement::ForStatement(stmt) => {
+                if let Some(init) = &stmt.init {
+                    self.check_let(init);
+                }
+                if let Some(expr) = &stmt.cond_expr {
+                    self.check_expression(expr);
+                }
+                if let Some(step) = &stmt.step {
+                    self.check_let(step);
+                }
+                self.check_statements(&stmt.statements);
+            }
+            Statement::BreakStatement(..) | Statement::ContinueStatement(..) => {}
+        }
+    }
+
+    fn check_let(&mut self, stmt: &LetStatement) {
+        if let Some(expr) = &stmt.index_expr {
+            self.check_expression(expr);
+        }
+        self.check_expression(&stmt.value_expr);
+    }
+
+    fn check_expression(&mut self, Expr(term, terms): &Expr) {
+        self.check_term(term);
+        for (_, term) in terms {
+            self.check_term(term);
+        }
+    }
+
+    fn check_term(&mut self, term: &Term) {
+        match term {
+            Term::UnaryOp(_, term) => self.check_term(term),
+            Term::ParenExpr(expr) | Term::IndexExpr(_, expr, _) => self.check_expression(expr),
+            Term::SubroutineCall(call) => self.check_call(call),
+            Term::VarName(..)
+            | Term::KeywordConstant(..)
+            | Term::IntegerConstant(..)
+            | Term::StringConst(..) => {}
+        }
+    }
+
+    fn check_call(&mut self, call: &SubroutineCall) {
+        match call {
+            SubroutineCall::SimpleCall(name, args, span) => {
+                let class_name = self.class_name;
+                if let Some(signature) = self.lookup(class_name, name, *span) {
+                    match signature.variant {
+                        GrammarSubroutineVariant::Method => {
+                            if self.variant == GrammarSubroutineVariant::Function {
+                                self.error(
+                                    format!(
+                                        "Can't call method {}.{} from a function",
+                                        class_name, name
+                                    ),
+                                    *span,
+                                );
+                            }
+                        }
+                        ref variant => self.error(
+                            format!(
+                                "Can't call {} {}.{} without a class name",
+                                variant_name(variant),
+                                class_name,
+                                name
+                            ),
+                            *span,
+                        ),
+                    }
+                    self.check_args(class_name, name, &signature, args.len(), *span);
+                }
+                self.check_args_exprs(args);
+            }
+            SubroutineCall::MethodCall(target, name, args, span) => {
+                match self.scope.lookup(target).cloned() {
+                    Some(GrammarItemType::Class(class_name)) => {
+                        if let Some(signature) = self.lookup(&class_name, name, *span) {
+                            if signature.variant != GrammarSubroutineVariant::Method {
+                                self.error(
+                                    format!(
+                                        "Can't call {} {}.{} on an object",
+                                        variant_name(&signature.variant),
+                                        class_name,
+                                        name
+                                    ),
+                                    *span,
+                                );
+                            }
+                            self.check_args(&class_name, name, &signature, args.len(), *span);
+                        }
+                    }
+                    Some(typ) => self.error(
+                        format!(
+                            "Can't call {} on {} of type {}",
+                            name,
+                            target,
+                            type_name(&typ)
+                        ),
+                        *span,
+                    ),
+                    None => {
+                        if let Some(signature) = self.lookup(target, name, *span) {
+                            if signature.variant == GrammarSubroutineVariant::Method {
+                                self.error(
+                                    format!(
+                                        "Can't call method {}.{} without an object",
+                                        target, name
+                                    ),
+                                    *span,
+                                );
+                            }
+                            self.check_args(target, name, &signature, args.len(), *span);
+                        }
+                    }
+                }
+                self.check_args_exprs(args);
+            }
+        }
+    }
+
+    fn lookup(&mut self, class_name: &str, name: &str, span: Span) -> Option<Signature> {
+        // A class compiled on its own may call classes which weren't given
+        if !self.signatures.has_class(class_name) {
+            self.warning(format!("Unknown class: {}", class_name), span);
+            return None;
+        }
+        let signature = self.signatures.lookup(class_name, name).cloned();
+        if signature.is_none() {
+            self.error(format!("Unknown subroutine: {}.{}", class_name, name), span);
+        }
+        signature
+    }
+
+    fn check_args(
+        &mut self,
+        class_name: &str,
+        name: &str,
+        signature: &Signature,
+        n_args: usize,
+        span: Span,
+    ) {
+        if signature.params.len() != n_args {
+            self.error(
+                format!(
+                    "{}.{} expects {} argument(s), got {}",
+                    class_name,
+                    name,
+                    signature.params.len(),
+                    n_args
+                ),
+                span,
+            );
+        }
+    }
+
+    fn check_args_exprs(&mut self, args: &[Expr]) {
+        for arg in args {
+            self.check_expression(arg);
+        }
+    }
+}
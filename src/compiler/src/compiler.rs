@@ -1,11 +1,16 @@
 use crate::{
     codegen::*,
+    diagnostic::Diagnostic,
     node::*,
     parser::ParseResult,
+    span::Span,
     symbol_table::{Entry, SubVarKind, SymbolTable},
     token::Keyword,
 };
-use std::{collections::HashSet, fmt::Write};
+use std::{
+    collections::{BTreeMap, HashSet},
+    fmt::Write,
+};
 
 type CompilerError = Box<dyn std::error::Error>;
 type Res<T = ()> = Result<T, CompilerError>;
@@ -15,22 +20,40 @@ struct CompilerState<'a> {
     label_id: usize,
     methods: HashSet<String>,
     sym_table: SymbolTable,
-    out: &'a mut (dyn Write),
+    out: &'a mut dyn Write,
+    /// Jack line of the code being compiled, 0 when unknown.
+    line: usize,
+    /// Jack line of every written VM line.
+    lines: Vec<usize>,
+    variables: BTreeMap<String, Vec<Variable>>,
+    /// Enclosing loops, innermost last.
+    loops: Vec<LoopLabels>,
+}
+
+/// Where `continue` and `break` jump to in a loop.
+struct LoopLabels {
+    continue_label: String,
+    break_label: String,
 }
 
 impl<'a> CompilerState<'a> {
-    fn new(class_name: String, sym_table: SymbolTable, out: &'a mut (dyn Write)) -> Self {
+    fn new(class_name: String, sym_table: SymbolTable, out: &'a mut dyn Write) -> Self {
         Self {
             class_name,
             label_id: 0,
             methods: Default::default(),
             sym_table,
             out,
+            line: 0,
+            lines: vec![],
+            variables: BTreeMap::new(),
+            loops: vec![],
         }
     }
 
     pub fn write<S: std::fmt::Display>(&mut self, s: S) {
         writeln!(self.out, "{}", s).expect("Error writing");
+        self.lines.push(self.line);
     }
 
     pub fn get_label(&mut self) -> String {
@@ -61,39 +84,76 @@ impl CompilerContext {
     }
 }
 
-fn lookup_var(state: &mut CompilerState, context: &CompilerContext, name: String) -> Res<Entry> {
-    let entry = state
-        .sym_table
-        .lookup(&name)
-        .ok_or(format!("Unknown var: {}", &name))?;
+fn error<T, S: Into<String>>(message: S, span: Span) -> Res<T> {
+    Err(Box::new(Diagnostic::error(message, span)))
+}
+
+fn lookup_var(
+    state: &mut CompilerState,
+    context: &CompilerContext,
+    name: String,
+    span: Span,
+) -> Res<Entry> {
+    let entry = match state.sym_table.lookup(&name) {
+        Some(entry) => entry,
+        None => return error(format!("Unknown var: {}", &name), span),
+    };
 
     if let (Some(GrammarSubroutineVariant::Function), "this") =
         (&context.function_variant, entry.kind.as_str())
     {
-        return Err(format!("Can't use field var in function: {}", name).into());
+        return error(format!("Can't use field var in function: {}", name), span);
     };
     Ok(entry)
 }
 
+/// Jack variable and where the compiled code keeps it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Variable {
+    pub name: String,
+    /// Jack type, e.g. `int` or a class name.
+    pub typ: String,
+    /// VM segment: `argument`, `local`, `this` or `static`.
+    pub segment: String,
+    pub index: u16,
+}
+
+/// VM code of a class, with what is needed to map it back to the Jack source.
+#[derive(Debug)]
+pub struct CompiledClass {
+    pub vm_code: String,
+    /// Jack line of every VM line, 0 when unknown.
+    pub lines: Vec<usize>,
+    /// Variables in scope in each function, by function name.
+    pub variables: BTreeMap<String, Vec<Variable>>,
+}
+
 pub fn compile_program(parse_result: ParseResult) -> Res<String> {
-    let mut out = String::new();
+    Ok(compile_program_mapped(parse_result)?.vm_code)
+}
+
+/// Same as `compile_program`, also giving the Jack lines and variables.
+pub fn compile_program_mapped(parse_result: ParseResult) -> Res<CompiledClass> {
+    let mut vm_code = String::new();
     let sym_table = SymbolTable::new();
-    let mut state = CompilerState::new(Default::default(), sym_table, &mut out);
+    let mut state = CompilerState::new(Default::default(), sym_table, &mut vm_code);
     let context = CompilerContext::new();
-    compile_class(&mut state, &context, parse_result.root).map_err(|e| {
-        // dbg!(state.class_name, state.sym_table);
-        e
-    })?;
-    Ok(out)
+    compile_class(&mut state, &context, parse_result.root)?;
+    let (lines, variables) = (state.lines, state.variables);
+    Ok(CompiledClass {
+        vm_code,
+        lines,
+        variables,
+    })
 }
 
 fn compile_class(
     state: &mut CompilerState,
     context: &CompilerContext,
-    Class(ident, var_decs, sub_decs): Class,
+    Class(ident, var_decs, sub_decs, _): Class,
 ) -> Res {
     state.class_name = ident;
-    for ClassVarDec(var_type, item_type, names) in var_decs {
+    for ClassVarDec(var_type, item_type, names, _) in var_decs {
         for name in names.iter() {
             state
                 .sym_table
@@ -112,14 +172,13 @@ fn compile_class(
 fn compile_subroutine_dec(
     state: &mut CompilerState,
     context: &CompilerContext,
-    SubroutineDec(variant, item_type, ident, params, sub): SubroutineDec,
+    SubroutineDec(variant, item_type, ident, params, sub, span): SubroutineDec,
 ) -> Res {
     state.sym_table.reset_subroutine_table();
+    state.line = span.line;
     let n_locals: u16 = sub.0.iter().map(|var_dec| var_dec.1.len() as u16).sum();
-    state.write(write_function(
-        format!("{}.{}", state.class_name, ident),
-        n_locals,
-    ));
+    let function_name = format!("{}.{}", state.class_name, ident);
+    state.write(write_function(function_name.clone(), n_locals));
     let mut sub_context = context.clone();
     sub_context.function_variant = Some(variant.clone());
     sub_context.return_type = Some(item_type.clone());
@@ -136,7 +195,7 @@ fn compile_subroutine_dec(
 
             // Offset arguments in methods by setting fake value, since we also pass 'this'
             state.sym_table.define_subroutine_var(
-                &"this".to_string(),
+                "this",
                 SubVarKind::Argument,
                 &GrammarItemType::Class(state.class_name.clone()),
             );
@@ -150,6 +209,21 @@ fn compile_subroutine_dec(
     }
 
     compile_subroutine(state, &sub_context, sub, item_type)?;
+
+    let variables = state
+        .sym_table
+        .entries()
+        .into_iter()
+        // Functions have no `this`
+        .filter(|(_, entry)| variant != GrammarSubroutineVariant::Function || entry.kind != "this")
+        .map(|(name, entry)| Variable {
+            name,
+            typ: entry.typ,
+            segment: entry.kind,
+            index: entry.index,
+        })
+        .collect();
+    state.variables.insert(function_name, variables);
     Ok(())
 }
 
@@ -159,7 +233,7 @@ fn compile_subroutine(
     Subroutine(var_decs, stmts): Subroutine,
     _typ: GrammarSubroutineReturnType,
 ) -> Res {
-    for VarDec(type_, names) in var_decs {
+    for VarDec(type_, names, _) in var_decs {
         for name in names.iter() {
             state
                 .sym_table
@@ -182,13 +256,26 @@ fn compile_statements(
 }
 
 fn compile_statement(state: &mut CompilerState, context: &CompilerContext, stmt: Statement) -> Res {
+    // Code after nested statements, like the jumps ending an `if`, belongs
+    // to the enclosing statement
+    let outer_line = std::mem::replace(&mut state.line, stmt.span().line);
     match stmt {
         Statement::LetStatement(s) => compile_statement_let(state, context, s)?,
         Statement::IfStatement(s) => compile_statement_if(state, context, s)?,
         Statement::WhileStatement(s) => compile_statement_while(state, context, s)?,
         Statement::DoStatement(s) => compile_statement_do(state, context, s)?,
         Statement::ReturnStatement(s) => compile_statement_return(state, context, s)?,
+        Statement::ForStatement(s) => compile_statement_for(state, context, s)?,
+        Statement::BreakStatement(s) => match state.loops.last() {
+            Some(labels) => state.write(write_goto(&labels.break_label)),
+            None => return error("Break outside of a loop", s.span),
+        },
+        Statement::ContinueStatement(s) => match state.loops.last() {
+            Some(labels) => state.write(write_goto(&labels.continue_label)),
+            None => return error("Continue outside of a loop", s.span),
+        },
     };
+    state.line = outer_line;
     Ok(())
 }
 
@@ -197,10 +284,11 @@ fn compile_statement_let(
     context: &CompilerContext,
     stmt: LetStatement,
 ) -> Res {
-    let var = state
-        .sym_table
-        .lookup(&stmt.name)
-        .ok_or(format!("Unknown var: {}", &stmt.name))?;
+    let var = state.sym_table.lookup(&stmt.name);
+    let var = match var {
+        Some(var) => var,
+        None => return error(format!("Unknown var: {}", &stmt.name), stmt.span),
+    };
     match stmt.index_expr {
         Some(expr) => {
             state.write(write_push(var.kind.as_str(), var.index));
@@ -255,12 +343,57 @@ fn compile_statement_while(
     compile_expression(state, context, stmt.cond_expr)?;
     state.write("not");
     state.write(write_if(&end_label));
-    compile_statements(state, context, stmt.statements)?;
+    compile_loop_body(state, context, stmt.statements, &start_label, &end_label)?;
     state.write(write_goto(&start_label));
     state.write(write_label(&end_label));
     Ok(())
 }
 
+fn compile_statement_for(
+    state: &mut CompilerState,
+    context: &CompilerContext,
+    stmt: ForStatement,
+) -> Res {
+    let start_label = state.get_label();
+    let step_label = state.get_label();
+    let end_label = state.get_label();
+    if let Some(init) = stmt.init {
+        compile_statement_let(state, context, *init)?;
+    }
+    state.write(write_label(&start_label));
+    if let Some(cond_expr) = stmt.cond_expr {
+        compile_expression(state, context, cond_expr)?;
+        state.write("not");
+        state.write(write_if(&end_label));
+    }
+    compile_loop_body(state, context, stmt.statements, &step_label, &end_label)?;
+    state.write(write_label(&step_label));
+    if let Some(step) = stmt.step {
+        compile_statement_let(state, context, *step)?;
+    }
+    state.write(write_goto(&start_label));
+    state.write(write_label(&end_label));
+    Ok(())
+}
+
+/// Compiles the statements of a loop, in which `continue` and `break` jump to
+/// the given labels.
+fn compile_loop_body(
+    state: &mut CompilerState,
+    context: &CompilerContext,
+    statements: Vec<Statement>,
+    continue_label: &str,
+    break_label: &str,
+) -> Res {
+    state.loops.push(LoopLabels {
+        continue_label: continue_label.into(),
+        break_label: break_label.into(),
+    });
+    let result = compile_statements(state, context, statements);
+    state.loops.pop();
+    result
+}
+
 fn compile_statement_do(
     state: &mut CompilerState,
     context: &CompilerContext,
@@ -280,10 +413,13 @@ fn compile_statement_return(
     // `return` statement validity check.
     match (&stmt.result, &context.return_type) {
         (Some(e), Some(GrammarSubroutineReturnType::Void)) => {
-            return Err(format!("Expected void return, got: {:?}", e).into());
+            return error(format!("Expected void return, got: {:?}", e), stmt.span);
         }
         (None, Some(GrammarSubroutineReturnType::Type(t))) => {
-            return Err(format!("Expected value return, got void. Expected type: {:?}", t).into());
+            return error(
+                format!("Expected value return, got void. Expected type: {:?}", t),
+                stmt.span,
+            );
         }
         _ => {}
     }
@@ -301,9 +437,9 @@ fn compile_statement_return(
 
 fn compile_call(state: &mut CompilerState, context: &CompilerContext, call: SubroutineCall) -> Res {
     let (func_name, args) = match call {
-        SubroutineCall::SimpleCall(method, args) => {
+        SubroutineCall::SimpleCall(method, args, span) => {
             if !state.has_method(&method) {
-                return Err(format!("Can't call non-method as method: {}", method).into());
+                return error(format!("Can't call non-method as method: {}", method), span);
             };
             get_method_call(
                 Term::KeywordConstant(Keyword::This),
@@ -312,10 +448,10 @@ fn compile_call(state: &mut CompilerState, context: &CompilerContext, call: Subr
                 args,
             )
         }
-        SubroutineCall::MethodCall(this_, method, args) => {
+        SubroutineCall::MethodCall(this_, method, args, span) => {
             let var = state.sym_table.lookup(&this_);
             match var {
-                Some(entry) => get_method_call(Term::VarName(this_), entry.typ, method, args),
+                Some(entry) => get_method_call(Term::VarName(this_, span), entry.typ, method, args),
                 None => (format!("{}.{}", this_, method), args),
             }
         }
@@ -371,8 +507,8 @@ fn compile_expression(
 
 fn compile_term(state: &mut CompilerState, context: &CompilerContext, term: Term) -> Res {
     match term {
-        Term::VarName(name) => {
-            let var = lookup_var(state, context, name)?;
+        Term::VarName(name, span) => {
+            let var = lookup_var(state, context, name, span)?;
             state.write(write_push(var.kind.as_str(), var.index));
         }
         Term::KeywordConstant(kw) => {
@@ -408,8 +544,8 @@ fn compile_term(state: &mut CompilerState, context: &CompilerContext, term: Term
         Term::ParenExpr(expr) => {
             compile_expression(state, context, *expr)?;
         }
-        Term::IndexExpr(name, expr) => {
-            let var = lookup_var(state, context, name)?;
+        Term::IndexExpr(name, expr, span) => {
+            let var = lookup_var(state, context, name, span)?;
             state.write(write_push(var.kind.as_str(), var.index));
             compile_expression(state, context, *expr)?;
             state.write("add");
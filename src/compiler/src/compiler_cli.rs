@@ -1,30 +1,192 @@
-use std::{error::Error, fs};
+use std::{
+    collections::BTreeMap,
+    error::Error,
+    fs,
+    path::{Path, PathBuf},
+};
 
-use crate::{compiler, config::Config, input, parser};
+use hasm::source_map::{Location, SourceMap};
+
+use crate::{
+    checker::{self, Signatures},
+    compiler::{self, Variable},
+    config::{CompileOptions, Config, Verbosity, STDIO},
+    diagnostic::{self, Diagnostic, Diagnostics, Severity},
+    input, lint, optimizer, parser, tokenizer, typecheck,
+};
 
 #[derive(Debug)]
 pub struct CompileResultSuccess {
     pub vm_code: String,
+    /// Jack line of the VM lines.
+    pub source_map: SourceMap,
+    /// Jack variables of every function, by function name.
+    pub variables: BTreeMap<String, Vec<Variable>>,
+}
+
+/// Maps the VM lines with a known Jack line to it.
+fn source_map(file: &Path, lines: &[usize]) -> SourceMap {
+    let mut map = SourceMap::new();
+    for (i, line) in lines.iter().enumerate().filter(|(_, line)| **line > 0) {
+        let location = Location {
+            file: file.to_string_lossy().into_owned(),
+            line: *line,
+        };
+        map.insert(i + 1, location);
+    }
+    map
+}
+
+#[derive(Debug)]
+pub struct CompileOutput {
+    pub files: Vec<(PathBuf, CompileResultSuccess)>,
+    pub warnings: Vec<Diagnostic>,
 }
 
 pub fn compile_file(file: &std::path::Path) -> Result<CompileResultSuccess, Box<dyn Error>> {
-    let vm_code = compiler::compile_program(
-        parser::parse(fs::read_to_string(file)?.as_str())
-            .map_err(|e| format!("Error parsing file {:?}:\n{}", file, e))?,
-    )?;
-    Ok(CompileResultSuccess { vm_code })
+    let source = fs::read_to_string(file)?;
+    let locate = |e| diagnostic::with_source(e, Some(file.to_owned()), &source);
+    let compiled =
+        compiler::compile_program_mapped(parser::parse(source.as_str()).map_err(locate)?)
+            .map_err(locate)?;
+    Ok(CompileResultSuccess {
+        source_map: source_map(file, &compiled.lines),
+        vm_code: compiled.vm_code,
+        variables: compiled.variables,
+    })
 }
 
-pub fn run_for_config(config: &Config) -> Result<(), Box<dyn Error>> {
-    let files = input::get_files(config.source_path.clone());
+/// Parses all files, checks the calls between them and to the OS, then compiles them.
+pub fn compile_files(
+    files: &[PathBuf],
+    options: &CompileOptions,
+) -> Result<CompileOutput, Box<dyn Error>> {
+    let mut sources = vec![];
     for file in files {
-        // eprintln!("Compiling file: {:?}", file.as_path());
-        let result = compile_file(&file)?;
+        sources.push((file.clone(), fs::read_to_string(file)?));
+    }
+    compile_sources(sources, options)
+}
+
+/// Same as `compile_files`, for sources which are already in memory. The
+/// paths are only used to locate diagnostics.
+pub fn compile_sources(
+    sources: Vec<(PathBuf, String)>,
+    options: &CompileOptions,
+) -> Result<CompileOutput, Box<dyn Error>> {
+    compile_sources_with_siblings(sources, &[], options)
+}
+
+/// Same as `compile_sources`, also checking calls to the classes of
+/// `siblings` without compiling them. Siblings which don't parse are left out.
+pub fn compile_sources_with_siblings(
+    sources: Vec<(PathBuf, String)>,
+    siblings: &[(PathBuf, String)],
+    options: &CompileOptions,
+) -> Result<CompileOutput, Box<dyn Error>> {
+    let mut parse_results = vec![];
+    for (file, source) in &sources {
+        let result = parser::parse_with(source.as_str(), options.extensions)
+            .map_err(|e| diagnostic::with_source(e, Some(file.clone()), source))?;
+        parse_results.push(result);
+    }
+
+    let mut signatures = Signatures::with_os()?;
+    for (_, source) in siblings {
+        if let Ok(result) = parser::parse_with(source.as_str(), options.extensions) {
+            signatures.add_class(&result.root);
+        }
+    }
+    for result in &parse_results {
+        signatures.add_class(&result.root);
+    }
+    let mut diagnostics = vec![];
+    for (result, (file, source)) in parse_results.iter().zip(&sources) {
+        let mut class_diagnostics = checker::check_class(&result.root, &signatures);
+        class_diagnostics.extend(lint::check_class(&result.root, &signatures, &options.lints));
+        if let Some(severity) = options.type_check {
+            class_diagnostics.extend(typecheck::check_class(&result.root, &signatures, severity));
+        }
+        diagnostics.extend(
+            class_diagnostics
+                .into_iter()
+                .map(|d| d.with_source(Some(file.clone()), source)),
+        );
+    }
+    let (errors, warnings): (Vec<_>, Vec<_>) = diagnostics
+        .into_iter()
+        .partition(|d| d.severity == Severity::Error);
+    if !errors.is_empty() {
+        return Err(Box::new(Diagnostics(errors)));
+    }
+
+    let files = parse_results
+        .into_iter()
+        .zip(sources)
+        .map(|(result, (file, source))| {
+            let compiled = compiler::compile_program_mapped(result)
+                .map_err(|e| diagnostic::with_source(e, Some(file.clone()), &source))?;
+            let (vm_code, lines) =
+                optimizer::optimize_mapped(&compiled.vm_code, &compiled.lines, options.opt_level);
+            let source_map = source_map(&file, &lines);
+            Ok((
+                file,
+                CompileResultSuccess {
+                    vm_code,
+                    source_map,
+                    variables: compiled.variables,
+                },
+            ))
+        })
+        .collect::<Result<_, Box<dyn Error>>>()?;
+    Ok(CompileOutput { files, warnings })
+}
+
+pub fn run_for_config(config: &Config) -> Result<(), Box<dyn Error>> {
+    let sources = input::read_sources(&config.source_path)?;
+    if config.verbosity >= Verbosity::Verbose {
+        for (file, _) in &sources {
+            eprintln!("Compiling: {}", file.display());
+        }
+    }
+    let tokens = if config.output_tokens && !config.check {
+        sources
+            .iter()
+            .map(|(file, source)| {
+                let tokens = tokenizer::tokenize_with(source, config.options.extensions)?;
+                let xml = tokenizer::tokens_to_xml(tokens);
+                Ok((config.tokens_path(file)?, xml))
+            })
+            .collect::<Result<Vec<_>, Box<dyn Error>>>()?
+    } else {
+        vec![]
+    };
+    // A single file can call the classes next to it
+    let source_path = Path::new(&config.source_path);
+    let siblings = if config.source_path != STDIO && source_path.is_file() {
+        input::read_siblings(source_path)
+    } else {
+        vec![]
+    };
+    let output = compile_sources_with_siblings(sources, &siblings, &config.options)?;
+    if config.verbosity >= Verbosity::Normal {
+        for warning in &output.warnings {
+            eprintln!("{}\n", warning);
+        }
+    }
+    if config.check {
+        return Ok(());
+    }
 
-        let mut target_path = file.clone();
-        target_path.set_extension("vm");
-        fs::write(target_path.as_path(), result.vm_code)?;
-        // eprintln!("Wrote to: {}", target_path.as_path().display());
+    let vm_files = output
+        .files
+        .into_iter()
+        .map(|(file, result)| (config.target_path(&file, "vm"), result.vm_code));
+    for (target_path, contents) in tokens.into_iter().chain(vm_files) {
+        input::write_output(&target_path, &contents)?;
+        if config.verbosity >= Verbosity::Verbose && !config.writes_stdout() {
+            eprintln!("Wrote to: {}", target_path.display());
+        }
     }
     Ok(())
 }
@@ -1,25 +1,156 @@
+use std::path::{Path, PathBuf};
+
+use crate::{diagnostic::Severity, lint::LintSet};
+
+#[derive(Debug, Default, Clone)]
+pub struct CompileOptions {
+    /// Runs the type checker, reporting problems with this severity.
+    pub type_check: Option<Severity>,
+    pub lints: LintSet,
+    /// VM code optimization level, see `optimizer::optimize`.
+    pub opt_level: u8,
+    /// Accepts the language extensions: `for` loops, `break` and `continue`.
+    pub extensions: bool,
+}
+
+impl CompileOptions {
+    /// Parses options following the source path, e.g. `-O2`, `--type-check=strict`
+    /// or `-Wno-unused-class-variable`.
+    pub fn from_args(args: &[String]) -> Result<Self, Box<dyn std::error::Error>> {
+        let mut options = Self::default();
+        for arg in args {
+            match arg.as_str() {
+                "--type-check" => options.type_check = Some(Severity::Warning),
+                "--type-check=strict" => options.type_check = Some(Severity::Error),
+                "-O" | "-O1" => options.opt_level = 1,
+                "-O0" => options.opt_level = 0,
+                "-O2" => options.opt_level = 2,
+                "--extensions" => options.extensions = true,
+                _ if options.lints.apply_switch(arg)? => {}
+                _ => return Err(format!("unknown option: {}", arg).into()),
+            }
+        }
+        Ok(options)
+    }
+}
+
+/// Path standing for stdin as the input, or stdout as the output.
+pub const STDIO: &str = "-";
+
+pub const USAGE: &str = "\
+Usage: compiler [options] <file.jack | directory | ->
+
+Compiles Jack classes into .vm files next to them. Reading from stdin (`-`)
+writes to stdout. `analyzer` and `parser` take the same options and write
+tokenizer and parser XML instead.
+
+Options:
+  -o, --output <path>     Output file, or directory when compiling a directory
+      --check             Only validate the input, don't write anything
+      --tokens            Also write the tokenizer XML of each class, named after
+                          the output with an .xml extension for -o FILE
+      --resolve-symbols   Annotate identifiers in the parser XML
+  -O0, -O1, -O2           VM code optimization level
+      --type-check[=strict]
+                          Report type errors as warnings, or as errors
+  -W<lint>, -Wno-<lint>   Enable or disable a lint, -Wall and -w for all
+      --extensions        Accept `for (init; cond; step) { ... }`, `break;` and
+                          `continue;`, where init and step are assignments
+                          without `let`
+  -v, --verbose           Report what was read and written
+  -q, --quiet             Only report errors
+  -h, --help              Print this help
+";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default)]
+pub enum Verbosity {
+    Quiet,
+    #[default]
+    Normal,
+    Verbose,
+}
+
+#[derive(Debug, Default)]
 pub struct Config {
-    pub output_tokens: bool,
+    /// Source file or directory, `STDIO` for stdin.
     pub source_path: String,
+    /// Output file, `STDIO` for stdout, or directory for a source directory.
+    pub output: Option<String>,
+    pub output_tokens: bool,
+    pub resolve_symbols: bool,
+    pub check: bool,
+    pub verbosity: Verbosity,
+    pub help: bool,
+    pub options: CompileOptions,
 }
 
 impl Config {
     pub fn new(args: &[String]) -> Result<Self, Box<dyn std::error::Error>> {
-        if args.len() < 2 {
-            return Err("not enough arguments".into());
+        let mut config = Config::default();
+        let mut source_path = None;
+        let mut compile_args = vec![];
+        let mut args = args.iter().skip(1);
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "-h" | "--help" => config.help = true,
+                "-o" | "--output" => {
+                    config.output = Some(args.next().ok_or("missing output path")?.clone())
+                }
+                "--tokens" => config.output_tokens = true,
+                "--resolve-symbols" => config.resolve_symbols = true,
+                "--check" => config.check = true,
+                "-v" | "--verbose" => config.verbosity = Verbosity::Verbose,
+                "-q" | "--quiet" => config.verbosity = Verbosity::Quiet,
+                _ if arg.starts_with('-') && arg != STDIO => compile_args.push(arg.clone()),
+                _ if source_path.is_none() => source_path = Some(arg.clone()),
+                _ => return Err(format!("unexpected argument: {}", arg).into()),
+            }
         }
-
-        let source_path = args[1].clone();
-        Ok(Config {
-            output_tokens: true,
-            source_path,
-        })
+        if !config.help {
+            config.source_path = source_path.ok_or("not enough arguments")?;
+        }
+        config.options = CompileOptions::from_args(&compile_args)?;
+        Ok(config)
     }
 
     pub fn for_path(source_path: String) -> Result<Self, Box<dyn std::error::Error>> {
         Ok(Config {
-            output_tokens: false,
             source_path,
+            ..Default::default()
         })
     }
+
+    pub fn writes_stdout(&self) -> bool {
+        self.target_path(Path::new(""), "") == Path::new(STDIO)
+    }
+
+    /// Where to write the output for a source file, `STDIO` for stdout.
+    pub fn target_path(&self, file: &Path, extension: &str) -> PathBuf {
+        match &self.output {
+            Some(output) if Path::new(&self.source_path).is_dir() => {
+                let file_name = file.file_name().unwrap_or_default();
+                Path::new(output).join(file_name).with_extension(extension)
+            }
+            Some(output) => PathBuf::from(output),
+            None if self.source_path == STDIO => PathBuf::from(STDIO),
+            None => file.with_extension(extension),
+        }
+    }
+
+    /// Where to write the tokenizer XML of a source file: next to its VM code,
+    /// or with the output file name and an `.xml` extension for `-o FILE`.
+    pub fn tokens_path(&self, file: &Path) -> Result<PathBuf, Box<dyn std::error::Error>> {
+        match &self.output {
+            Some(output) if output != STDIO && !Path::new(&self.source_path).is_dir() => {
+                let target = Path::new(output).with_extension("xml");
+                if target == Path::new(output) {
+                    return Err(
+                        format!("the tokens XML would overwrite the output: {}", output).into(),
+                    );
+                }
+                Ok(target)
+            }
+            _ => Ok(self.target_path(file, "out.xml")),
+        }
+    }
 }
@@ -0,0 +1,151 @@
+use std::{fmt, path::PathBuf};
+
+use crate::span::Span;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub message: String,
+    pub span: Option<Span>,
+    pub file: Option<PathBuf>,
+    /// Source line the span starts at, used to render the snippet.
+    pub source_line: Option<String>,
+}
+
+impl Diagnostic {
+    pub fn new<S: Into<String>>(severity: Severity, message: S, span: Option<Span>) -> Self {
+        Self {
+            severity,
+            message: message.into(),
+            span: span.filter(|s| !s.is_unknown()),
+            file: None,
+            source_line: None,
+        }
+    }
+
+    pub fn error<S: Into<String>>(message: S, span: Span) -> Self {
+        Self::new(Severity::Error, message, Some(span))
+    }
+
+    pub fn warning<S: Into<String>>(message: S, span: Span) -> Self {
+        Self::new(Severity::Warning, message, Some(span))
+    }
+
+    /// Attaches the file name and the source needed to render a snippet.
+    pub fn with_source(mut self, file: Option<PathBuf>, source: &str) -> Self {
+        if file.is_some() {
+            self.file = file;
+        }
+        if let Some(span) = self.span {
+            self.source_line = source.lines().nth(span.line - 1).map(String::from);
+        }
+        self
+    }
+
+    pub fn location(&self) -> String {
+        let file = self
+            .file
+            .as_ref()
+            .map(|f| f.display().to_string())
+            .unwrap_or_else(|| "<input>".into());
+        match self.span {
+            Some(span) => format!("{}:{}:{}", file, span.line, span.column),
+            None => file,
+        }
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.severity, self.message)?;
+        if self.span.is_none() && self.file.is_none() {
+            return Ok(());
+        }
+        write!(f, "\n --> {}", self.location())?;
+        if let (Some(span), Some(line)) = (self.span, &self.source_line) {
+            let gutter = span.line.to_string().len();
+            let line_len = line.chars().count() + 1;
+            let end_column = if span.end_line == span.line {
+                span.end_column.min(line_len)
+            } else {
+                line_len
+            };
+            let carets = end_column.saturating_sub(span.column).max(1);
+            write!(
+                f,
+                "\n{:gutter$} |\n{} | {}\n{:gutter$} | {:pad$}{}",
+                "",
+                span.line,
+                line,
+                "",
+                "",
+                "^".repeat(carets),
+                gutter = gutter,
+                pad = span.column - 1
+            )?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for Diagnostic {}
+
+/// Several diagnostics reported at once, e.g. every syntax error in a file.
+#[derive(Debug, Clone, Default)]
+pub struct Diagnostics(pub Vec<Diagnostic>);
+
+impl Diagnostics {
+    pub fn with_source(self, file: Option<PathBuf>, source: &str) -> Self {
+        Diagnostics(
+            self.0
+                .into_iter()
+                .map(|d| d.with_source(file.clone(), source))
+                .collect(),
+        )
+    }
+}
+
+impl fmt::Display for Diagnostics {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, diagnostic) in self.0.iter().enumerate() {
+            if i > 0 {
+                f.write_str("\n\n")?;
+            }
+            write!(f, "{}", diagnostic)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for Diagnostics {}
+
+/// Attaches file and source to `Diagnostic(s)` errors, other errors are passed through.
+pub fn with_source(
+    err: Box<dyn std::error::Error>,
+    file: Option<PathBuf>,
+    source: &str,
+) -> Box<dyn std::error::Error> {
+    let err = match err.downcast::<Diagnostic>() {
+        Ok(diagnostic) => return Box::new(diagnostic.with_source(file, source)),
+        Err(err) => err,
+    };
+    match err.downcast::<Diagnostics>() {
+        Ok(diagnostics) => Box::new(diagnostics.with_source(file, source)),
+        Err(err) => err,
+    }
+}
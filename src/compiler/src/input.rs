@@ -1,4 +1,14 @@
-use std::path;
+use std::{
+    error::Error,
+    fs,
+    io::{self, Read, Write},
+    path,
+};
+
+use crate::config::STDIO;
+
+/// Name standing for stdin in diagnostics.
+pub const STDIN_NAME: &str = "<stdin>";
 
 pub fn get_files(path: String) -> Vec<path::PathBuf> {
     let arg_path = path::PathBuf::from(path);
@@ -19,3 +29,54 @@ pub fn get_files(path: String) -> Vec<path::PathBuf> {
         vec![arg_path]
     }
 }
+
+/// Reads the `.jack` files of a directory sorted by name, a single file or
+/// stdin, as `(path, source)`.
+pub fn read_sources(path: &str) -> Result<Vec<(path::PathBuf, String)>, Box<dyn Error>> {
+    if path == STDIO {
+        let mut source = String::new();
+        io::stdin().read_to_string(&mut source)?;
+        return Ok(vec![(path::PathBuf::from(STDIN_NAME), source)]);
+    }
+    let mut files = get_files(path.to_string());
+    files.sort();
+    let mut sources = vec![];
+    for file in files {
+        let source = fs::read_to_string(&file)?;
+        sources.push((file, source));
+    }
+    Ok(sources)
+}
+
+/// Reads the other `.jack` files next to a source file, leaving out the ones
+/// which can't be read.
+pub fn read_siblings(file: &path::Path) -> Vec<(path::PathBuf, String)> {
+    let Some(dir) = file.parent() else {
+        return vec![];
+    };
+    let dir = if dir.as_os_str().is_empty() {
+        path::Path::new(".")
+    } else {
+        dir
+    };
+    let mut files = get_files(dir.to_string_lossy().into_owned());
+    files.retain(|sibling| sibling.file_name() != file.file_name());
+    files.sort();
+    files
+        .into_iter()
+        .filter_map(|sibling| Some((sibling.clone(), fs::read_to_string(sibling).ok()?)))
+        .collect()
+}
+
+/// Writes to a file, creating its directory if needed, or to stdout for `STDIO`.
+pub fn write_output(target: &path::Path, contents: &str) -> Result<(), Box<dyn Error>> {
+    if target == path::Path::new(STDIO) {
+        io::stdout().write_all(contents.as_bytes())?;
+        return Ok(());
+    }
+    if let Some(dir) = target.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+        fs::create_dir_all(dir)?;
+    }
+    fs::write(target, contents)?;
+    Ok(())
+}
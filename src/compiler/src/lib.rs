@@ -1,13 +1,20 @@
+pub mod checker;
 pub mod codegen;
 pub mod compiler;
 pub mod compiler_cli;
 pub mod config;
+pub mod diagnostic;
 pub mod input;
 pub mod line_chars;
+pub mod lint;
 pub mod node;
 pub mod node_printer;
+pub mod optimizer;
+pub mod os;
 pub mod parser;
+pub mod span;
 pub mod symbol_table;
 pub mod token;
 pub mod tokenizer;
+pub mod typecheck;
 pub mod xml;
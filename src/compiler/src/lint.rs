@@ -0,0 +1,475 @@
+use std::collections::HashSet;
+
+use crate::{
+    checker::{Scope, Signatures},
+    diagnostic::Diagnostic,
+    node::*,
+    span::Span,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Lint {
+    UnusedVariable,
+    UnusedParameter,
+    UnusedClassVariable,
+    UnreachableCode,
+    MissingReturn,
+    Uninitialized,
+    UnusedResult,
+}
+
+impl Lint {
+    pub const ALL: [Lint; 7] = [
+        Lint::UnusedVariable,
+        Lint::UnusedParameter,
+        Lint::UnusedClassVariable,
+        Lint::UnreachableCode,
+        Lint::MissingReturn,
+        Lint::Uninitialized,
+        Lint::UnusedResult,
+    ];
+
+    /// Name used in `-W<name>` and `-Wno-<name>` switches.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Lint::UnusedVariable => "unused-variable",
+            Lint::UnusedParameter => "unused-parameter",
+            Lint::UnusedClassVariable => "unused-class-variable",
+            Lint::UnreachableCode => "unreachable-code",
+            Lint::MissingReturn => "missing-return",
+            Lint::Uninitialized => "uninitialized",
+            Lint::UnusedResult => "unused-result",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Lint> {
+        Lint::ALL.iter().copied().find(|lint| lint.name() == name)
+    }
+}
+
+/// Enabled lints. Everything but `unused-result` is on by default, as Jack
+/// code routinely drops e.g. the result of `String.appendChar`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LintSet(HashSet<Lint>);
+
+impl Default for LintSet {
+    fn default() -> Self {
+        let mut lints = Self::all();
+        lints.disable(Lint::UnusedResult);
+        lints
+    }
+}
+
+impl LintSet {
+    pub fn all() -> Self {
+        LintSet(Lint::ALL.iter().copied().collect())
+    }
+
+    pub fn none() -> Self {
+        LintSet(HashSet::new())
+    }
+
+    pub fn enable(&mut self, lint: Lint) {
+        self.0.insert(lint);
+    }
+
+    pub fn disable(&mut self, lint: Lint) {
+        self.0.remove(&lint);
+    }
+
+    pub fn is_enabled(&self, lint: Lint) -> bool {
+        self.0.contains(&lint)
+    }
+
+    /// Applies a gcc style switch: `-Wall`, `-w`, `-W<name>` or `-Wno-<name>`.
+    /// Returns `false` for anything else.
+    pub fn apply_switch(&mut self, arg: &str) -> Result<bool, Box<dyn std::error::Error>> {
+        let name = match arg {
+            "-Wall" => {
+                *self = Self::all();
+                return Ok(true);
+            }
+            "-w" => {
+                *self = Self::none();
+                return Ok(true);
+            }
+            _ => match arg.strip_prefix("-W") {
+                Some(name) => name,
+                None => return Ok(false),
+            },
+        };
+        let (enable, name) = match name.strip_prefix("no-") {
+            Some(name) => (false, name),
+            None => (true, name),
+        };
+        let lint = Lint::from_name(name).ok_or_else(|| format!("unknown warning: {}", arg))?;
+        if enable {
+            self.enable(lint);
+        } else {
+            self.disable(lint);
+        }
+        Ok(true)
+    }
+}
+
+/// Reports the enabled lints for the class as warnings.
+pub fn check_class(class: &Class, signatures: &Signatures, lints: &LintSet) -> Vec<Diagnostic> {
+    let Class(class_name, class_var_decs, sub_decs, _) = class;
+    let mut linter = Linter {
+        signatures,
+        lints,
+        class_name,
+        scope: Scope::for_class(class_var_decs),
+        warnings: vec![],
+    };
+    let mut class_var_reads = HashSet::new();
+    for sub_dec in sub_decs {
+        class_var_reads.extend(linter.check_subroutine(sub_dec));
+    }
+    for ClassVarDec(kind, _, names, span) in class_var_decs {
+        let kind = match kind {
+            GrammarClassVarType::Static => "static variable",
+            GrammarClassVarType::Field => "field",
+        };
+        for name in names {
+            if !class_var_reads.contains(name) {
+                let message = format!("unused {}: {}", kind, name);
+                linter.warn(Lint::UnusedClassVariable, message, *span);
+            }
+        }
+    }
+    linter.warnings
+}
+
+struct Linter<'a> {
+    signatures: &'a Signatures,
+    lints: &'a LintSet,
+    class_name: &'a str,
+    scope: Scope,
+    warnings: Vec<Diagnostic>,
+}
+
+impl<'a> Linter<'a> {
+    fn warn(&mut self, lint: Lint, message: String, span: Span) {
+        if self.lints.is_enabled(lint) {
+            let message = format!("{} [-W{}]", message, lint.name());
+            self.warnings.push(Diagnostic::warning(message, span));
+        }
+    }
+
+    /// Returns names read in the subroutine which aren't locals or parameters,
+    /// i.e. the class variables it uses.
+    fn check_subroutine(&mut self, sub_dec: &SubroutineDec) -> HashSet<String> {
+        let SubroutineDec(_, return_type, name, params, Subroutine(var_decs, statements), span) =
+            sub_dec;
+        self.scope.enter_subroutine(params, var_decs);
+
+        let mut reads = vec![];
+        statements_reads(statements, &mut reads);
+        let read_names: HashSet<String> = reads.into_iter().map(|(name, _)| name).collect();
+
+        let mut locals = HashSet::new();
+        for VarDec(_, names, span) in var_decs {
+            for name in names {
+                locals.insert(name.clone());
+                if !read_names.contains(name) {
+                    self.warn(
+                        Lint::UnusedVariable,
+                        format!("unused variable: {}", name),
+                        *span,
+                    );
+                }
+            }
+        }
+        for param in params {
+            if !read_names.contains(&param.ident) && !locals.contains(&param.ident) {
+                let message = format!("unused parameter: {}", param.ident);
+                self.warn(Lint::UnusedParameter, message, param.span);
+            }
+        }
+
+        self.check_unreachable(statements);
+        if !always_returns(statements) {
+            let message = match return_type {
+                GrammarSubroutineReturnType::Void => {
+                    format!("{}.{} can end without a return", self.class_name, name)
+                }
+                GrammarSubroutineReturnType::Type(..) => {
+                    format!(
+                        "{}.{} doesn't return a value on every path",
+                        self.class_name, name
+                    )
+                }
+            };
+            self.warn(Lint::MissingReturn, message, *span);
+        }
+
+        let mut assigned = HashSet::new();
+        let mut reported = HashSet::new();
+        self.check_uninitialized(statements, &locals, &mut assigned, &mut reported);
+        self.check_unused_results(statements);
+
+        let params: HashSet<&String> = params.iter().map(|p| &p.ident).collect();
+        read_names
+            .into_iter()
+            .filter(|name| !locals.contains(name) && !params.contains(name))
+            .collect()
+    }
+
+    fn check_unreachable(&mut self, statements: &[Statement]) {
+        let mut returned = false;
+        for statement in statements {
+            if returned {
+                self.warn(
+                    Lint::UnreachableCode,
+                    "unreachable statement".into(),
+                    statement.span(),
+                );
+                break;
+            }
+            for block in nested_blocks(statement) {
+                self.check_unreachable(block);
+            }
+            returned = always_returns(std::slice::from_ref(statement))
+                || matches!(
+                    statement,
+                    Statement::BreakStatement(..) | Statement::ContinueStatement(..)
+                );
+        }
+    }
+
+    /// Tracks which locals are definitely assigned on every path to each read.
+    fn check_uninitialized(
+        &mut self,
+        statements: &[Statement],
+        locals: &HashSet<String>,
+        assigned: &mut HashSet<String>,
+        reported: &mut HashSet<String>,
+    ) {
+        for statement in statements {
+            if let Statement::ForStatement(stmt) = statement {
+                self.check_uninitialized_for(stmt, locals, assigned, reported);
+                continue;
+            }
+            let mut reads = vec![];
+            statement_own_reads(statement, &mut reads);
+            self.report_uninitialized(reads, locals, assigned, reported);
+            match statement {
+                Statement::LetStatement(stmt) if stmt.index_expr.is_none() => {
+                    assigned.insert(stmt.name.clone());
+                }
+                Statement::IfStatement(stmt) => {
+                    let mut if_assigned = assigned.clone();
+                    self.check_uninitialized(
+                        &stmt.if_statements,
+                        locals,
+                        &mut if_assigned,
+                        reported,
+                    );
+                    let mut else_assigned = assigned.clone();
+                    if let Some(statements) = &stmt.else_statements {
+                        self.check_uninitialized(statements, locals, &mut else_assigned, reported);
+                    }
+                    *assigned = if_assigned.intersection(&else_assigned).cloned().collect();
+                }
+                Statement::WhileStatement(stmt) => {
+                    let mut body_assigned = assigned.clone();
+                    self.check_uninitialized(
+                        &stmt.statements,
+                        locals,
+                        &mut body_assigned,
+                        reported,
+                    );
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// The parts of a `for` run in turn: `init` once, then the condition,
+    /// body and step.
+    fn check_uninitialized_for(
+        &mut self,
+        stmt: &ForStatement,
+        locals: &HashSet<String>,
+        assigned: &mut HashSet<String>,
+        reported: &mut HashSet<String>,
+    ) {
+        if let Some(init) = &stmt.init {
+            let mut reads = vec![];
+            let_reads(init, &mut reads);
+            self.report_uninitialized(reads, locals, assigned, reported);
+            if init.index_expr.is_none() {
+                assigned.insert(init.name.clone());
+            }
+        }
+        let mut reads = vec![];
+        if let Some(expr) = &stmt.cond_expr {
+            expr_reads(expr, &mut reads);
+        }
+        self.report_uninitialized(reads, locals, assigned, reported);
+        let mut body_assigned = assigned.clone();
+        self.check_uninitialized(&stmt.statements, locals, &mut body_assigned, reported);
+        if let Some(step) = &stmt.step {
+            let mut reads = vec![];
+            let_reads(step, &mut reads);
+            self.report_uninitialized(reads, locals, &body_assigned, reported);
+        }
+    }
+
+    fn report_uninitialized(
+        &mut self,
+        reads: Vec<(String, Span)>,
+        locals: &HashSet<String>,
+        assigned: &HashSet<String>,
+        reported: &mut HashSet<String>,
+    ) {
+        for (name, span) in reads {
+            if locals.contains(&name) && !assigned.contains(&name) && reported.insert(name.clone())
+            {
+                let message = format!("{} is read before being assigned", name);
+                self.warn(Lint::Uninitialized, message, span);
+            }
+        }
+    }
+
+    fn check_unused_results(&mut self, statements: &[Statement]) {
+        for statement in statements {
+            if let Statement::DoStatement(DoStatement { call, span }) = statement {
+                if let Some((class_name, name)) = self.resolve_call(call) {
+                    let returns_value = self
+                        .signatures
+                        .lookup(&class_name, &name)
+                        .is_some_and(|s| s.return_type != GrammarSubroutineReturnType::Void);
+                    if returns_value {
+                        let message = format!("result of {}.{} is discarded", class_name, name);
+                        self.warn(Lint::UnusedResult, message, *span);
+                    }
+                }
+            }
+            for block in nested_blocks(statement) {
+                self.check_unused_results(block);
+            }
+        }
+    }
+
+    fn resolve_call(&self, call: &SubroutineCall) -> Option<(String, String)> {
+        match call {
+            SubroutineCall::SimpleCall(name, ..) => Some((self.class_name.into(), name.clone())),
+            SubroutineCall::MethodCall(target, name, ..) => match self.scope.lookup(target) {
+                Some(GrammarItemType::Class(class_name)) => {
+                    Some((class_name.clone(), name.clone()))
+                }
+                Some(_) => None,
+                None => Some((target.clone(), name.clone())),
+            },
+        }
+    }
+}
+
+fn nested_blocks(statement: &Statement) -> Vec<&[Statement]> {
+    match statement {
+        Statement::IfStatement(stmt) => {
+            let mut blocks = vec![stmt.if_statements.as_slice()];
+            if let Some(statements) = &stmt.else_statements {
+                blocks.push(statements);
+            }
+            blocks
+        }
+        Statement::WhileStatement(stmt) => vec![&stmt.statements],
+        Statement::ForStatement(stmt) => vec![&stmt.statements],
+        _ => vec![],
+    }
+}
+
+/// Whether every path through the statements ends with a `return`.
+fn always_returns(statements: &[Statement]) -> bool {
+    statements.iter().any(|statement| match statement {
+        Statement::ReturnStatement(..) => true,
+        Statement::IfStatement(IfStatement {
+            if_statements,
+            else_statements: Some(else_statements),
+            ..
+        }) => always_returns(if_statements) && always_returns(else_statements),
+        _ => false,
+    })
+}
+
+/// Reads done by the statement itself, not by the blocks nested in it.
+fn statement_own_reads(statement: &Statement, reads: &mut Vec<(String, Span)>) {
+    match statement {
+        Statement::LetStatement(stmt) => let_reads(stmt, reads),
+        Statement::IfStatement(stmt) => expr_reads(&stmt.if_expr, reads),
+        Statement::WhileStatement(stmt) => expr_reads(&stmt.cond_expr, reads),
+        Statement::DoStatement(stmt) => call_reads(&stmt.call, reads),
+        Statement::ReturnStatement(stmt) => {
+            if let Some(expr) = &stmt.result {
+                expr_reads(expr, reads);
+            }
+        }
+        Statement::ForStatement(stmt) => {
+            if let Some(init) = &stmt.init {
+                let_reads(init, reads);
+            }
+            if let Some(expr) = &stmt.cond_expr {
+                expr_reads(expr, reads);
+            }
+            if let Some(step) = &stmt.step {
+                let_reads(step, reads);
+            }
+        }
+        Statement::BreakStatement(..) | Statement::ContinueStatement(..) => {}
+    }
+}
+
+fn let_reads(stmt: &LetStatement, reads: &mut Vec<(String, Span)>) {
+    if let Some(index) = &stmt.index_expr {
+        reads.push((stmt.name.clone(), stmt.span));
+        expr_reads(index, reads);
+    }
+    expr_reads(&stmt.value_expr, reads);
+}
+
+fn statements_reads(statements: &[Statement], reads: &mut Vec<(String, Span)>) {
+    for statement in statements {
+        statement_own_reads(statement, reads);
+        for block in nested_blocks(statement) {
+            statements_reads(block, reads);
+        }
+    }
+}
+
+fn expr_reads(Expr(term, terms): &Expr, reads: &mut Vec<(String, Span)>) {
+    term_reads(term, reads);
+    for (_, term) in terms {
+        term_reads(term, reads);
+    }
+}
+
+fn term_reads(term: &Term, reads: &mut Vec<(String, Span)>) {
+    match term {
+        Term::VarName(name, span) => reads.push((name.clone(), *span)),
+        Term::IndexExpr(name, index, span) => {
+            reads.push((name.clone(), *span));
+            expr_reads(index, reads);
+        }
+        Term::UnaryOp(_, term) => term_reads(term, reads),
+        Term::ParenExpr(expr) => expr_reads(expr, reads),
+        Term::SubroutineCall(call) => call_reads(call, reads),
+        Term::KeywordConstant(..) | Term::IntegerConstant(..) | Term::StringConst(..) => {}
+    }
+}
+
+fn call_reads(call: &SubroutineCall, reads: &mut Vec<(String, Span)>) {
+    let args = match call {
+        SubroutineCall::SimpleCall(_, args, _) => args,
+        SubroutineCall::MethodCall(target, _, args, span) => {
+            // Class names are recorded too, they never match a variable
+            reads.push((target.clone(), *span));
+            args
+        }
+    };
+    for arg in args {
+        expr_reads(arg, reads);
+    }
+}
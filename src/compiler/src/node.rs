@@ -1,4 +1,7 @@
-use crate::token::{Keyword, Token};
+use crate::{
+    span::Span,
+    token::{Keyword, Token},
+};
 
 pub type Identifier = String;
 
@@ -63,23 +66,30 @@ pub fn sub_variant_from_token(t: Token) -> Option<GrammarSubroutineVariant> {
 pub struct GrammarParamDec {
     pub type_: GrammarItemType,
     pub ident: Identifier,
+    pub span: Span,
 }
 
 #[derive(Debug, Clone)]
 pub struct Type(pub GrammarItemType);
 
 #[derive(Debug, Clone)]
-pub struct Class(pub Identifier, pub Vec<ClassVarDec>, pub Vec<SubroutineDec>);
+pub struct Class(
+    pub Identifier,
+    pub Vec<ClassVarDec>,
+    pub Vec<SubroutineDec>,
+    pub Span,
+);
 
 #[derive(Debug, Clone)]
 pub struct ClassVarDec(
     pub GrammarClassVarType,
     pub GrammarItemType,
     pub Vec<Identifier>,
+    pub Span,
 );
 
 #[derive(Debug, Clone)]
-pub struct VarDec(pub GrammarItemType, pub Vec<Identifier>);
+pub struct VarDec(pub GrammarItemType, pub Vec<Identifier>, pub Span);
 
 #[derive(Debug, Clone)]
 pub struct SubroutineDec(
@@ -88,6 +98,7 @@ pub struct SubroutineDec(
     pub Identifier,
     pub Vec<GrammarParamDec>,
     pub Subroutine,
+    pub Span,
 );
 
 #[derive(Debug, Clone)]
@@ -100,6 +111,9 @@ pub enum Statement {
     WhileStatement(WhileStatement),
     DoStatement(DoStatement),
     ReturnStatement(ReturnStatement),
+    ForStatement(ForStatement),
+    BreakStatement(BreakStatement),
+    ContinueStatement(ContinueStatement),
 }
 
 #[derive(Debug, Clone)]
@@ -107,6 +121,7 @@ pub struct LetStatement {
     pub name: Identifier,
     pub index_expr: Option<Expr>,
     pub value_expr: Expr,
+    pub span: Span,
 }
 
 #[derive(Debug, Clone)]
@@ -114,22 +129,50 @@ pub struct IfStatement {
     pub if_expr: Expr,
     pub if_statements: Vec<Statement>,
     pub else_statements: Option<Vec<Statement>>,
+    pub span: Span,
 }
 
 #[derive(Debug, Clone)]
 pub struct WhileStatement {
     pub cond_expr: Expr,
     pub statements: Vec<Statement>,
+    pub span: Span,
+}
+
+/// `for (init; cond; step) { ... }`, a language extension. `init` and `step`
+/// are assignments without `let`, any of the three parts can be left out.
+#[derive(Debug, Clone)]
+pub struct ForStatement {
+    pub init: Option<Box<LetStatement>>,
+    pub cond_expr: Option<Expr>,
+    pub step: Option<Box<LetStatement>>,
+    pub statements: Vec<Statement>,
+    pub span: Span,
+}
+
+/// Leaves the innermost `while` or `for`, a language extension.
+#[derive(Debug, Clone)]
+pub struct BreakStatement {
+    pub span: Span,
+}
+
+/// Goes on with the next iteration of the innermost `while` or `for`, after
+/// the `for` step, a language extension.
+#[derive(Debug, Clone)]
+pub struct ContinueStatement {
+    pub span: Span,
 }
 
 #[derive(Debug, Clone)]
 pub struct DoStatement {
     pub call: SubroutineCall,
+    pub span: Span,
 }
 
 #[derive(Debug, Clone)]
 pub struct ReturnStatement {
     pub result: Option<Expr>,
+    pub span: Span,
 }
 
 #[derive(Debug, Clone)]
@@ -139,13 +182,13 @@ pub type ExprList = Vec<Expr>;
 
 #[derive(Debug, Clone)]
 pub enum Term {
-    VarName(Identifier),
+    VarName(Identifier, Span),
     KeywordConstant(Keyword),
     IntegerConstant(u16),
     StringConst(String),
     UnaryOp(Op, Box<Term>),
     ParenExpr(Box<Expr>),
-    IndexExpr(Identifier, Box<Expr>),
+    IndexExpr(Identifier, Box<Expr>, Span),
     SubroutineCall(SubroutineCall),
 }
 
@@ -154,6 +197,29 @@ pub struct Op(pub String);
 
 #[derive(Debug, Clone)]
 pub enum SubroutineCall {
-    SimpleCall(Identifier, ExprList),
-    MethodCall(Identifier, Identifier, ExprList),
+    SimpleCall(Identifier, ExprList, Span),
+    MethodCall(Identifier, Identifier, ExprList, Span),
+}
+
+impl SubroutineCall {
+    pub fn span(&self) -> Span {
+        match self {
+            SubroutineCall::SimpleCall(.., span) | SubroutineCall::MethodCall(.., span) => *span,
+        }
+    }
+}
+
+impl Statement {
+    pub fn span(&self) -> Span {
+        match self {
+            Statement::LetStatement(s) => s.span,
+            Statement::IfStatement(s) => s.span,
+            Statement::WhileStatement(s) => s.span,
+            Statement::DoStatement(s) => s.span,
+            Statement::ReturnStatement(s) => s.span,
+            Statement::ForStatement(s) => s.span,
+            Statement::BreakStatement(s) => s.span,
+            Statement::ContinueStatement(s) => s.span,
+        }
+    }
 }
@@ -24,6 +24,8 @@ pub enum Node {
     ExprList(Vec<Expr>),
     SubroutineCall(SubroutineCall),
     VarIdentifier(String, bool),
+    /// `init` or `step` of a `for` statement.
+    Assignment(LetStatement),
 }
 
 const XML_LEVEL_INDENT: usize = 2;
@@ -62,7 +64,7 @@ pub fn print_to_xml(
     );
 
     match node {
-        Node::Class(Class(ident, var_dec, sub_dec)) => {
+        Node::Class(Class(ident, var_dec, sub_dec, _)) => {
             w!("<class>", indent);
             w!(xwd("keyword", "class"));
             w!(xwd("identifier", ident.as_str()));
@@ -78,7 +80,7 @@ pub fn print_to_xml(
             w!(xwd("symbol", "}"));
             w!("</class>", indent);
         }
-        Node::SubroutineDec(SubroutineDec(variant, type_, ident, params, sub)) => {
+        Node::SubroutineDec(SubroutineDec(variant, type_, ident, params, sub, _)) => {
             w!("<subroutineDec>", indent);
             w!(xwd(
                 "keyword",
@@ -132,7 +134,7 @@ pub fn print_to_xml(
             w!(xwd("symbol", "}"));
             w!("</subroutineBody>", indent);
         }
-        Node::ClassVarDec(ClassVarDec(class_var_type, item_type, names)) => {
+        Node::ClassVarDec(ClassVarDec(class_var_type, item_type, names, _)) => {
             w!("<classVarDec>", indent);
             w!(xwd(
                 "keyword",
@@ -158,7 +160,7 @@ pub fn print_to_xml(
             w!(xwd("symbol", ";"));
             w!("</classVarDec>", indent);
         }
-        Node::VarDec(VarDec(type_, names)) => {
+        Node::VarDec(VarDec(type_, names, _)) => {
             w!("<varDec>", indent);
             w!(xwd("keyword", "var"));
             w!(print_type_to_xml(&type_));
@@ -197,7 +199,7 @@ pub fn print_to_xml(
                 .for_each(|x| print_child!(Node::Statement(x)));
             w!("</statements>", indent);
         }
-        Node::Statement(Statement::ReturnStatement(ReturnStatement { result })) => {
+        Node::Statement(Statement::ReturnStatement(ReturnStatement { result, .. })) => {
             w!("<returnStatement>", indent);
             w!(xwd("keyword", "return"));
             if let Some(expr) = result {
@@ -210,6 +212,7 @@ pub fn print_to_xml(
             index_expr,
             name,
             value_expr,
+            ..
         })) => {
             w!("<letStatement>", indent);
             w!(xwd("keyword", "let"));
@@ -228,6 +231,7 @@ pub fn print_to_xml(
             if_expr,
             if_statements,
             else_statements,
+            ..
         })) => {
             w!("<ifStatement>", indent);
             w!(xwd("keyword", "if"));
@@ -248,6 +252,7 @@ pub fn print_to_xml(
         Node::Statement(Statement::WhileStatement(WhileStatement {
             cond_expr,
             statements,
+            ..
         })) => {
             w!("<whileStatement>", indent);
             w!(xwd("keyword", "while"));
@@ -259,7 +264,63 @@ pub fn print_to_xml(
             w!(xwd("symbol", "}"));
             w!("</whileStatement>", indent);
         }
-        Node::Statement(Statement::DoStatement(DoStatement { call })) => {
+        Node::Statement(Statement::ForStatement(ForStatement {
+            init,
+            cond_expr,
+            step,
+            statements,
+            ..
+        })) => {
+            w!("<forStatement>", indent);
+            w!(xwd("keyword", "for"));
+            w!(xwd("symbol", "("));
+            if let Some(init) = init {
+                print_child!(Node::Assignment(*init));
+            }
+            w!(xwd("symbol", ";"));
+            if let Some(expr) = cond_expr {
+                print_child!(Node::Expr(expr));
+            }
+            w!(xwd("symbol", ";"));
+            if let Some(step) = step {
+                print_child!(Node::Assignment(*step));
+            }
+            w!(xwd("symbol", ")"));
+            w!(xwd("symbol", "{"));
+            print_child!(Node::Statements(statements));
+            w!(xwd("symbol", "}"));
+            w!("</forStatement>", indent);
+        }
+        Node::Assignment(LetStatement {
+            index_expr,
+            name,
+            value_expr,
+            ..
+        }) => {
+            w!("<assignment>", indent);
+            print_child!(Node::VarIdentifier(name, true));
+            if let Some(expr) = index_expr {
+                w!(xwd("symbol", "["));
+                print_child!(Node::Expr(expr));
+                w!(xwd("symbol", "]"));
+            }
+            w!(xwd("symbol", "="));
+            print_child!(Node::Expr(value_expr));
+            w!("</assignment>", indent);
+        }
+        Node::Statement(Statement::BreakStatement(..)) => {
+            w!("<breakStatement>", indent);
+            w!(xwd("keyword", "break"));
+            w!(xwd("symbol", ";"));
+            w!("</breakStatement>", indent);
+        }
+        Node::Statement(Statement::ContinueStatement(..)) => {
+            w!("<continueStatement>", indent);
+            w!(xwd("keyword", "continue"));
+            w!(xwd("symbol", ";"));
+            w!("</continueStatement>", indent);
+        }
+        Node::Statement(Statement::DoStatement(DoStatement { call, .. })) => {
             w!("<doStatement>", indent);
             w!(xwd("keyword", "do"));
             print_child!(Node::SubroutineCall(call), indent);
@@ -268,8 +329,8 @@ pub fn print_to_xml(
         }
         Node::SubroutineCall(call) => {
             let (this_ident, method, args) = match call {
-                SubroutineCall::SimpleCall(method, args) => (None, method, args),
-                SubroutineCall::MethodCall(this_ident, method, args) => {
+                SubroutineCall::SimpleCall(method, args, _) => (None, method, args),
+                SubroutineCall::MethodCall(this_ident, method, args, _) => {
                     (Some(this_ident), method, args)
                 }
             };
@@ -295,7 +356,7 @@ pub fn print_to_xml(
         Node::Term(term) => {
             w!("<term>", indent);
             match term {
-                Term::VarName(ident) => print_child!(Node::VarIdentifier(ident, true)),
+                Term::VarName(ident, _) => print_child!(Node::VarIdentifier(ident, true)),
                 Term::KeywordConstant(kw) => w!(xwd("keyword", keyword_to_string(&kw))),
                 Term::IntegerConstant(i) => w!(xwd("integerConstant", i.to_string().as_str())),
                 Term::StringConst(s) => w!(xwd("stringConstant", s.as_str())),
@@ -306,7 +367,7 @@ pub fn print_to_xml(
                 Term::ParenExpr(expr) => {
                     print_child!(Node::ParenExpr(*expr), indent);
                 }
-                Term::IndexExpr(ident, expr) => {
+                Term::IndexExpr(ident, expr, _) => {
                     print_child!(Node::VarIdentifier(ident, true));
                     w!(xwd("symbol", "["));
                     print_child!(Node::Expr(*expr));
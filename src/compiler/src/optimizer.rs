@@ -0,0 +1,367 @@
+use std::collections::{HashMap, HashSet};
+
+use vm::instruction::{Instruction, PushPop, PushPopInstruction};
+
+/// Instruction with `push constant` lifted to a signed value, so negative and
+/// folded constants can be matched like any other constant.
+#[derive(Debug, Clone, PartialEq)]
+enum Op {
+    Const(i16),
+    Inst(Instruction),
+}
+
+fn arithmetic(op: &Op) -> Option<&str> {
+    match op {
+        Op::Inst(Instruction::Arithmetic(name)) => Some(name.as_str()),
+        _ => None,
+    }
+}
+
+fn fold_binary(op: &str, a: i16, b: i16) -> Option<i16> {
+    let bool_value = |b: bool| if b { -1 } else { 0 };
+    Some(match op {
+        "add" => a.wrapping_add(b),
+        "sub" => a.wrapping_sub(b),
+        "and" => a & b,
+        "or" => a | b,
+        "eq" => bool_value(a == b),
+        "gt" => bool_value(a > b),
+        "lt" => bool_value(a < b),
+        _ => return None,
+    })
+}
+
+fn fold_unary(op: &str, a: i16) -> Option<i16> {
+    match op {
+        "neg" => Some(a.wrapping_neg()),
+        "not" => Some(!a),
+        _ => None,
+    }
+}
+
+fn lift(inst: Instruction) -> Op {
+    match &inst {
+        Instruction::PushPop(PushPopInstruction {
+            segment,
+            addr,
+            inst_type: PushPop::Push,
+        }) if segment == "constant" && *addr <= i16::MAX as u16 => Op::Const(*addr as i16),
+        _ => Op::Inst(inst),
+    }
+}
+
+fn push_constant(value: u16) -> Instruction {
+    Instruction::PushPop(PushPopInstruction {
+        segment: "constant".into(),
+        addr: value,
+        inst_type: PushPop::Push,
+    })
+}
+
+fn lower(op: Op) -> Vec<Instruction> {
+    match op {
+        Op::Const(value) if value >= 0 => vec![push_constant(value as u16)],
+        Op::Const(i16::MIN) => vec![
+            push_constant(i16::MAX as u16),
+            Instruction::Arithmetic("not".into()),
+        ],
+        Op::Const(value) => vec![
+            push_constant(-value as u16),
+            Instruction::Arithmetic("neg".into()),
+        ],
+        Op::Inst(inst) => vec![inst],
+    }
+}
+
+/// Rewrites the end of `out` after an op was appended, returns whether it changed.
+fn reduce_tail(out: &mut Vec<Op>, level: u8) -> bool {
+    use Instruction::*;
+    let n = out.len();
+    let tail = |len: usize| if n >= len { &out[n - len..] } else { &[][..] };
+
+    // Constant folding
+    if let [Op::Const(a), Op::Const(b), op] = tail(3) {
+        let folded = match op {
+            Op::Inst(Arithmetic(op)) => fold_binary(op, *a, *b),
+            Op::Inst(Call(name, 2)) if level >= 2 && name == "Math.multiply" => {
+                Some(a.wrapping_mul(*b))
+            }
+            _ => None,
+        };
+        if let Some(value) = folded {
+            out.truncate(n - 3);
+            out.push(Op::Const(value));
+            return true;
+        }
+    }
+    if let [Op::Const(a), Op::Inst(Arithmetic(op))] = tail(2) {
+        if let Some(value) = fold_unary(op, *a) {
+            out.truncate(n - 2);
+            out.push(Op::Const(value));
+            return true;
+        }
+    }
+    // `not; not` is the identity on every value
+    if let [a, b] = tail(2) {
+        if arithmetic(a) == Some("not") && arithmetic(b) == Some("not") {
+            out.truncate(n - 2);
+            return true;
+        }
+    }
+    // `push x; pop x` doesn't change anything
+    if let [Op::Inst(PushPop(push)), Op::Inst(PushPop(pop))] = tail(2) {
+        if push.inst_type == vm::instruction::PushPop::Push
+            && pop.inst_type == vm::instruction::PushPop::Pop
+            && push.segment == pop.segment
+            && push.addr == pop.addr
+        {
+            out.truncate(n - 2);
+            return true;
+        }
+    }
+    // `if-goto` already tests for non-zero
+    if let [Op::Const(0), eq, not, Op::Inst(IfGoto(..))] = tail(4) {
+        if arithmetic(eq) == Some("eq") && arithmetic(not) == Some("not") {
+            out.drain(n - 4..n - 1);
+            return true;
+        }
+    }
+    if let [Op::Const(value), Op::Inst(IfGoto(label, context))] = tail(2) {
+        let jump = Op::Inst(Goto(label.clone(), context.clone()));
+        let is_taken = *value != 0;
+        out.truncate(n - 2);
+        if is_taken {
+            out.push(jump);
+        }
+        return true;
+    }
+    // Loops and `if`s branch on the negated condition. Against a constant the
+    // negation is a comparison the other way with the next constant:
+    // `push constant 10; lt; not; if-goto A` => `push constant 9; gt; if-goto A`
+    if let [Op::Const(value), cmp, not, Op::Inst(IfGoto(..))] = tail(4) {
+        let inverted = match arithmetic(cmp) {
+            Some("lt") => value.checked_sub(1).map(|value| (value, "gt")),
+            Some("gt") => value.checked_add(1).map(|value| (value, "lt")),
+            _ => None,
+        };
+        if let (Some((value, cmp)), Some("not")) = (inverted, arithmetic(not)) {
+            let branch = out[n - 1].clone();
+            out.truncate(n - 4);
+            out.push(Op::Const(value));
+            out.push(Op::Inst(Arithmetic(cmp.into())));
+            out.push(branch);
+            return true;
+        }
+    }
+    // Comparisons give 0 or -1, so the `not` can be fused with the branch
+    // by swapping the targets:
+    // `lt; not; if-goto A; goto B; label A` => `lt; if-goto B; label A`
+    if let [cmp, not, Op::Inst(IfGoto(a, _)), Op::Inst(Goto(b, context)), Op::Inst(Label(label, _))] =
+        tail(5)
+    {
+        if matches!(arithmetic(cmp), Some("eq" | "gt" | "lt"))
+            && arithmetic(not) == Some("not")
+            && a == label
+        {
+            let fused = Op::Inst(IfGoto(b.clone(), context.clone()));
+            let label = out[n - 1].clone();
+            out.truncate(n - 4);
+            out.push(fused);
+            out.push(label);
+            return true;
+        }
+    }
+    false
+}
+
+/// Lines of the ops rewritten by `reduce_tail`: the unchanged ops at both
+/// ends of the tail keep theirs, new ops get the line of the first replaced one.
+fn relocate(lines: &mut Vec<usize>, start: usize, before: &[Op], after: &[Op]) {
+    let old = lines.split_off(start);
+    let prefix = before.iter().zip(after).take_while(|(a, b)| a == b).count();
+    let suffix = before[prefix..]
+        .iter()
+        .rev()
+        .zip(after[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let replaced = old.get(prefix).copied().unwrap_or_default();
+    lines.extend(&old[..prefix]);
+    lines.extend(std::iter::repeat_n(replaced, after.len() - prefix - suffix));
+    lines.extend(&old[old.len() - suffix..]);
+}
+
+fn peephole(ops: Vec<(Op, usize)>, level: u8) -> Vec<(Op, usize)> {
+    let mut out = Vec::with_capacity(ops.len());
+    let mut lines = Vec::with_capacity(ops.len());
+    for (op, line) in ops {
+        out.push(op);
+        lines.push(line);
+        loop {
+            // `reduce_tail` only rewrites the last 5 ops
+            let start = out.len().saturating_sub(5);
+            let before = out[start..].to_vec();
+            if !reduce_tail(&mut out, level) {
+                break;
+            }
+            relocate(&mut lines, start, &before, &out[start..]);
+        }
+    }
+    out.into_iter().zip(lines).collect()
+}
+
+fn jump_target(op: &Op) -> Option<&String> {
+    match op {
+        Op::Inst(Instruction::Goto(label, _)) | Op::Inst(Instruction::IfGoto(label, _)) => {
+            Some(label)
+        }
+        _ => None,
+    }
+}
+
+fn label_name(op: &Op) -> Option<&String> {
+    match op {
+        Op::Inst(Instruction::Label(label, _)) => Some(label),
+        _ => None,
+    }
+}
+
+/// Control flow cleanup of a single function: jump threading, dead code,
+/// jumps to the next instruction and unused labels.
+fn simplify_jumps(mut ops: Vec<(Op, usize)>) -> Vec<(Op, usize)> {
+    // Labels directly followed by a `goto` forward to its target
+    let mut forwards = HashMap::new();
+    for (i, (op, _)) in ops.iter().enumerate() {
+        if let Some(label) = label_name(op) {
+            let next = ops[i + 1..].iter().find(|(op, _)| label_name(op).is_none());
+            if let Some((Op::Inst(Instruction::Goto(target, _)), _)) = next {
+                forwards.insert(label.clone(), target.clone());
+            }
+        }
+    }
+    for (op, _) in ops.iter_mut() {
+        if let Op::Inst(Instruction::Goto(label, _)) | Op::Inst(Instruction::IfGoto(label, _)) = op
+        {
+            let mut seen = HashSet::new();
+            while let Some(target) = forwards.get(label.as_str()) {
+                if !seen.insert(target.clone()) {
+                    break;
+                }
+                *label = target.clone();
+            }
+        }
+    }
+
+    // Nothing after `goto` or `return` runs until the next label
+    let mut reachable = true;
+    ops.retain(|(op, _)| {
+        if label_name(op).is_some() || matches!(op, Op::Inst(Instruction::Function(..))) {
+            reachable = true;
+        }
+        let keep = reachable;
+        if matches!(
+            op,
+            Op::Inst(Instruction::Goto(..)) | Op::Inst(Instruction::Return())
+        ) {
+            reachable = false;
+        }
+        keep
+    });
+
+    // Jumps to the labels right after them
+    let mut out: Vec<(Op, usize)> = Vec::with_capacity(ops.len());
+    for (i, (op, line)) in ops.iter().enumerate() {
+        let falls_through = jump_target(op).is_some_and(|target| {
+            ops[i + 1..]
+                .iter()
+                .map_while(|(op, _)| label_name(op))
+                .any(|label| label == target)
+        });
+        match op {
+            Op::Inst(Instruction::Goto(..)) if falls_through => {}
+            Op::Inst(Instruction::IfGoto(..)) if falls_through => out.push((
+                Op::Inst(Instruction::PushPop(PushPopInstruction {
+                    segment: "temp".into(),
+                    addr: 0,
+                    inst_type: PushPop::Pop,
+                })),
+                *line,
+            )),
+            _ => out.push((op.clone(), *line)),
+        }
+    }
+
+    let used: HashSet<String> = out
+        .iter()
+        .filter_map(|(op, _)| jump_target(op))
+        .cloned()
+        .collect();
+    out.retain(|(op, _)| label_name(op).is_none_or(|label| used.contains(label)));
+    out
+}
+
+/// Splits at `function` commands, so control flow is analysed per function.
+fn split_functions(ops: Vec<(Op, usize)>) -> Vec<Vec<(Op, usize)>> {
+    let mut functions: Vec<Vec<(Op, usize)>> = vec![];
+    for op in ops {
+        match (&op.0, functions.last_mut()) {
+            (Op::Inst(Instruction::Function(..)), _) | (_, None) => functions.push(vec![op]),
+            (_, Some(function)) => function.push(op),
+        }
+    }
+    functions
+}
+
+/// Optimizes generated VM code.
+///
+/// - `-O1`: constant folding, `not`/`if-goto` fusion and `push`/`pop` pair elimination.
+/// - `-O2`: also folds `Math.multiply` of constants and removes dead jumps, code and labels.
+pub fn optimize(vm_code: &str, level: u8) -> String {
+    optimize_mapped(vm_code, &[], level).0
+}
+
+/// Same as `optimize`, carrying the source line of every VM line given in
+/// `lines` over to the optimized code. Missing lines are 0.
+pub fn optimize_mapped(vm_code: &str, lines: &[usize], level: u8) -> (String, Vec<usize>) {
+    if level == 0 {
+        let lines = (0..vm_code.lines().count())
+            .map(|i| lines.get(i).copied().unwrap_or_default())
+            .collect();
+        return (vm_code.to_string(), lines);
+    }
+    let mut parser = vm::parser::create(vm_code, "");
+    let ops: Vec<(Op, usize)> = parser
+        .parse()
+        .commands
+        .into_iter()
+        .map(|command| {
+            let line = lines.get(command.line - 1).copied().unwrap_or_default();
+            (lift(command.inst), line)
+        })
+        .collect();
+
+    let mut functions = split_functions(ops);
+    for function in functions.iter_mut() {
+        loop {
+            let before = function.len();
+            *function = peephole(std::mem::take(function), level);
+            if level >= 2 {
+                *function = simplify_jumps(std::mem::take(function));
+            }
+            if function.len() == before {
+                break;
+            }
+        }
+    }
+
+    let mut out = String::new();
+    let mut out_lines = vec![];
+    for (op, line) in functions.into_iter().flatten() {
+        for inst in lower(op) {
+            out.push_str(&inst.to_string());
+            out.push('\n');
+            out_lines.push(line);
+        }
+    }
+    (out, out_lines)
+}
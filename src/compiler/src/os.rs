@@ -0,0 +1,12 @@
+/// Sources of the Jack OS classes, used to know the OS API when checking
+/// programs which are compiled without the OS next to them.
+pub const SOURCES: [(&str, &str); 8] = [
+    ("Array", include_str!("../../os/Array.jack")),
+    ("Keyboard", include_str!("../../os/Keyboard.jack")),
+    ("Math", include_str!("../../os/Math.jack")),
+    ("Memory", include_str!("../../os/Memory.jack")),
+    ("Output", include_str!("../../os/Output.jack")),
+    ("Screen", include_str!("../../os/Screen.jack")),
+    ("String", include_str!("../../os/String.jack")),
+    ("Sys", include_str!("../../os/Sys.jack")),
+];
@@ -1,79 +1,118 @@
-use std::iter::{Enumerate, Peekable};
-use std::slice::Iter;
-
 use crate::{
+    diagnostic::{Diagnostic, Diagnostics},
     node::*,
-    token::{Keyword, Token},
-    tokenizer::tokenize,
+    span::Span,
+    token::{Keyword, SpannedToken, Token},
+    tokenizer::{tokenize_spanned, tokenize_spanned_with},
 };
 
 type ParseError = Box<dyn std::error::Error>;
 type Res<T = ()> = Result<T, ParseError>;
 
+/// The extension keywords are only tokenized as keywords with the extensions on.
+const STATEMENT_KEYWORDS: [Keyword; 8] = [
+    Keyword::Let,
+    Keyword::If,
+    Keyword::While,
+    Keyword::Do,
+    Keyword::Return,
+    Keyword::For,
+    Keyword::Break,
+    Keyword::Continue,
+];
+
+const SUBROUTINE_KEYWORDS: [Keyword; 3] =
+    [Keyword::Constructor, Keyword::Function, Keyword::Method];
+
+/// Recursive descent parser which recovers from syntax errors: a failed
+/// declaration or statement is recorded, the parser skips to the next `;`,
+/// `}` or keyword which starts a declaration or statement and carries on.
 #[derive(Debug)]
 pub struct Parser<'a> {
-    tokens: Peekable<Enumerate<Iter<'a, Token>>>,
-    tokens_seq: &'a [Token],
+    tokens: &'a [SpannedToken],
     pos: usize,
+    errors: Vec<Diagnostic>,
 }
 
 impl<'a> Parser<'a> {
-    pub fn new(tokens: &'a[Token]) -> Self {
+    pub fn new(tokens: &'a [SpannedToken]) -> Self {
         Self {
-            tokens: tokens.iter().enumerate().peekable(),
-            tokens_seq: tokens,
+            tokens,
             pos: 0,
+            errors: vec![],
+        }
+    }
+
+    /// Fails with every syntax error found if there are any.
+    pub fn parse(self) -> Res<ParseResult> {
+        let (result, errors) = self.parse_partial();
+        if errors.is_empty() {
+            Ok(result)
+        } else {
+            Err(Box::new(Diagnostics(errors)))
         }
     }
 
-    pub fn parse(mut self) -> Res<ParseResult> {
-        let class_node = self.parse_class().map_err(|x| self.parsing_error(x))?;
-        let result = ParseResult { root: class_node };
-        Ok(result)
+    /// Returns the tree with the nodes which failed to parse left out, and the errors.
+    pub fn parse_partial(mut self) -> (ParseResult, Vec<Diagnostic>) {
+        let class_node = self.parse_class();
+        (ParseResult { root: class_node }, self.errors)
     }
 
-    fn parse_class(&mut self) -> Res<Class> {
+    fn parse_class(&mut self) -> Class {
+        let start = self.peek_span();
+        let identifier = match self.parse_class_header() {
+            Ok(identifier) => identifier,
+            Err(e) => {
+                self.record(e);
+                self.synchronize(self.pos);
+                String::new()
+            }
+        };
+        let var_decs = self.parse_class_var_decs();
+        let sub_decs = self.parse_subroutine_decs();
+        if let Err(e) = self.expect(t::symbol("}")) {
+            self.record(e);
+        }
+        Class(identifier, var_decs, sub_decs, self.span_from(start))
+    }
+
+    fn parse_class_header(&mut self) -> Res<Identifier> {
         self.expect(Token::Keyword(Keyword::Class))?;
         let identifier = self.parse_identifier()?;
         self.expect(t::symbol("{"))?;
-        let var_decs = self.parse_class_var_decs()?;
-        let sub_decs = self.parse_subroutine_decs()?;
-        self.expect(t::symbol("}"))?;
-        Ok(Class(identifier, var_decs, sub_decs))
+        Ok(identifier)
     }
 
-    fn parse_class_var_decs(&mut self) -> Res<Vec<ClassVarDec>> {
+    fn parse_class_var_decs(&mut self) -> Vec<ClassVarDec> {
         let mut nodes: Vec<ClassVarDec> = vec![];
         while let Ok(class_var_type) = expect::one_of(
             self.peek(),
             &[t::kw(Keyword::Static), t::kw(Keyword::Field)],
         ) {
+            let start = self.peek_span();
+            let start_pos = self.pos;
             self.next();
-            let (decl_type, var_names) = self.parse_var_decs_inner()?;
-            nodes.push(ClassVarDec(
-                class_var_type_from_token(class_var_type).unwrap(),
-                decl_type,
-                var_names,
-            ));
+            match self.parse_var_decs_inner() {
+                Ok((decl_type, var_names)) => nodes.push(ClassVarDec(
+                    class_var_type_from_token(class_var_type).unwrap(),
+                    decl_type,
+                    var_names,
+                    self.span_from(start),
+                )),
+                Err(e) => {
+                    self.record(e);
+                    self.synchronize(start_pos);
+                }
+            }
         }
-        Ok(nodes)
+        nodes
     }
 
     fn parse_var_decs_inner(&mut self) -> Res<(GrammarItemType, Vec<Identifier>)> {
-        let decl_type = match expect::something(self.next())? {
-            token @ Token::Identifier(..) => item_type_from_token(token).unwrap(),
-            token => item_type_from_token(expect::one_of(
-                Some(token),
-                &[
-                    t::kw(Keyword::Int),
-                    t::kw(Keyword::Boolean),
-                    t::kw(Keyword::Char),
-                ],
-            )?)
-            .unwrap(),
-        };
+        let decl_type = self.parse_type()?;
         let mut var_names = vec![self.parse_identifier()?];
-        while let Ok(..) = self.try_expect(t::symbol(",")) {
+        while self.try_expect(t::symbol(",")).is_ok() {
             self.next();
             var_names.push(self.parse_identifier()?);
         }
@@ -81,42 +120,58 @@ impl<'a> Parser<'a> {
         Ok((decl_type, var_names))
     }
 
-    fn parse_subroutine_decs(&mut self) -> Res<Vec<SubroutineDec>> {
+    fn parse_subroutine_decs(&mut self) -> Vec<SubroutineDec> {
         let mut nodes: Vec<SubroutineDec> = vec![];
-
-        while let Ok(sub_variant) = expect::one_of(
-            self.peek(),
-            &[
-                t::kw(Keyword::Constructor),
-                t::kw(Keyword::Function),
-                t::kw(Keyword::Method),
-            ],
-        ) {
-            self.next();
-            let return_type = match expect::something(self.next())? {
-                Token::Keyword(Keyword::Void) => GrammarSubroutineReturnType::Void,
-                token @ (Token::Identifier(..) | Token::Keyword(..)) => {
-                    GrammarSubroutineReturnType::Type(item_type_from_token(token).unwrap())
+        loop {
+            let start_pos = self.pos;
+            match self.peek() {
+                None => break,
+                Some(token) if token == t::symbol("}") => break,
+                Some(Token::Keyword(kw)) if SUBROUTINE_KEYWORDS.contains(&kw) => {
+                    match self.parse_subroutine_dec() {
+                        Ok(node) => nodes.push(node),
+                        Err(e) => {
+                            self.record(e);
+                            self.synchronize(start_pos);
+                        }
+                    }
                 }
-                token => unreachable!("Unexpected subroutine type: {:?}", token),
-            };
-            let name = self.parse_identifier()?;
-
-            self.expect(t::symbol("("))?;
-            let params = self.parse_parameters_list()?;
-            self.expect(t::symbol(")"))?;
-
-            let body = self.parse_subroutine_body()?;
-            let node = SubroutineDec(
-                sub_variant_from_token(sub_variant).unwrap(),
-                return_type,
-                name,
-                params,
-                body,
-            );
-            nodes.push(node);
+                Some(token) => {
+                    let e = format!("Expected subroutine declaration, got: {:?}", token);
+                    self.errors.push(Diagnostic::error(e, self.peek_span()));
+                    self.synchronize(start_pos);
+                }
+            }
         }
-        Ok(nodes)
+        nodes
+    }
+
+    fn parse_subroutine_dec(&mut self) -> Res<SubroutineDec> {
+        let start = self.peek_span();
+        let sub_variant = self.expect_something()?;
+        self.next();
+        let return_type = match self.try_expect(t::kw(Keyword::Void)) {
+            Ok(..) => {
+                self.next();
+                GrammarSubroutineReturnType::Void
+            }
+            _ => GrammarSubroutineReturnType::Type(self.parse_type()?),
+        };
+        let name = self.parse_identifier()?;
+
+        self.expect(t::symbol("("))?;
+        let params = self.parse_parameters_list()?;
+        self.expect(t::symbol(")"))?;
+
+        let body = self.parse_subroutine_body()?;
+        Ok(SubroutineDec(
+            sub_variant_from_token(sub_variant).unwrap(),
+            return_type,
+            name,
+            params,
+            body,
+            self.span_from(start),
+        ))
     }
 
     fn parse_parameters_list(&mut self) -> Res<Vec<GrammarParamDec>> {
@@ -125,10 +180,14 @@ impl<'a> Parser<'a> {
             if param_token == t::symbol(")") {
                 break;
             }
-            let type_ = item_type_from_token(param_token).unwrap();
-            self.next();
+            let start = self.peek_span();
+            let type_ = self.parse_type()?;
             let ident = self.parse_identifier()?;
-            params.push(GrammarParamDec { type_, ident });
+            params.push(GrammarParamDec {
+                type_,
+                ident,
+                span: self.span_from(start),
+            });
             if self.try_expect(t::symbol(",")).is_ok() {
                 self.next();
             } else {
@@ -141,52 +200,89 @@ impl<'a> Parser<'a> {
     fn parse_subroutine_body(&mut self) -> Res<Subroutine> {
         self.expect(t::symbol("{"))?;
         let mut var_decs: Vec<VarDec> = vec![];
-        while let Ok(..) = self.try_expect(t::kw(Keyword::Var)) {
+        while self.try_expect(t::kw(Keyword::Var)).is_ok() {
+            let start = self.peek_span();
+            let start_pos = self.pos;
             self.next();
-            let (decl_type, var_names) = self.parse_var_decs_inner()?;
-            var_decs.push(VarDec(decl_type, var_names));
+            match self.parse_var_decs_inner() {
+                Ok((decl_type, var_names)) => {
+                    var_decs.push(VarDec(decl_type, var_names, self.span_from(start)))
+                }
+                Err(e) => {
+                    self.record(e);
+                    self.synchronize(start_pos);
+                }
+            }
         }
 
         let statements = self.parse_statements()?;
 
-        self.expect(t::symbol("}"))?;
+        // The body is kept when only the closing brace is missing.
+        if let Err(e) = self.expect(t::symbol("}")) {
+            self.record(e);
+        }
         Ok(Subroutine(var_decs, statements))
     }
 
     fn parse_statements(&mut self) -> Res<Vec<Statement>> {
         let mut statements: Vec<Statement> = vec![];
-        while let Ok(..) = expect::one_of(
-            self.peek(),
-            &[
-                t::kw(Keyword::Let),
-                t::kw(Keyword::If),
-                t::kw(Keyword::While),
-                t::kw(Keyword::Do),
-                t::kw(Keyword::Return),
-            ],
-        ) {
-            statements.push(self.parse_statement()?);
+        loop {
+            let start_pos = self.pos;
+            match self.peek() {
+                None => break,
+                Some(token) if token == t::symbol("}") => break,
+                Some(Token::Keyword(kw)) if STATEMENT_KEYWORDS.contains(&kw) => {
+                    match self.parse_statement() {
+                        Ok(statement) => statements.push(statement),
+                        Err(e) => {
+                            self.record(e);
+                            self.synchronize(start_pos);
+                        }
+                    }
+                }
+                Some(Token::Keyword(kw)) if SUBROUTINE_KEYWORDS.contains(&kw) => break,
+                Some(token) => {
+                    let e = format!("Expected statement, got: {:?}", token);
+                    self.errors.push(Diagnostic::error(e, self.peek_span()));
+                    self.synchronize(start_pos);
+                }
+            }
         }
         Ok(statements)
     }
 
     fn parse_statement(&mut self) -> Res<Statement> {
-        Ok(match expect::something(self.peek())? {
+        Ok(match self.expect_something()? {
             Token::Keyword(Keyword::Let) => self.parse_statement_let()?,
             Token::Keyword(Keyword::If) => self.parse_statement_if()?,
             Token::Keyword(Keyword::While) => self.parse_statement_while()?,
             Token::Keyword(Keyword::Do) => self.parse_statement_do()?,
             Token::Keyword(Keyword::Return) => self.parse_statement_return()?,
+            Token::Keyword(Keyword::For) => self.parse_statement_for()?,
+            Token::Keyword(Keyword::Break) => self.parse_statement_break()?,
+            Token::Keyword(Keyword::Continue) => self.parse_statement_continue()?,
             statement_token => {
-                return Err(
-                    format!("Unexpected statement token type: {:?}", statement_token).into(),
+                return self.error(
+                    format!("Unexpected statement token type: {:?}", statement_token),
+                    self.peek_span(),
                 )
             }
         })
     }
 
     fn parse_statement_let(&mut self) -> Res<Statement> {
+        let start = self.peek_span();
         self.expect(t::kw(Keyword::Let))?;
+        let mut stmt = self.parse_assignment()?;
+        self.expect(t::symbol(";"))?;
+        stmt.span = self.span_from(start);
+        Ok(Statement::LetStatement(stmt))
+    }
+
+    /// `varName ('[' expression ']')? '=' expression`, a `let` statement
+    /// without the keyword and `;`.
+    fn parse_assignment(&mut self) -> Res<LetStatement> {
+        let start = self.peek_span();
         let name = self.parse_identifier()?;
         let index_expr = match self.try_expect(t::symbol("[")) {
             Ok(..) => {
@@ -199,15 +295,16 @@ impl<'a> Parser<'a> {
         };
         self.expect(t::symbol("="))?;
         let value_expr = self.parse_expression()?;
-        self.expect(t::symbol(";"))?;
-        Ok(Statement::LetStatement(LetStatement {
+        Ok(LetStatement {
             name,
             index_expr,
             value_expr,
-        }))
+            span: self.span_from(start),
+        })
     }
 
     fn parse_statement_if(&mut self) -> Res<Statement> {
+        let start = self.peek_span();
         self.expect(t::kw(Keyword::If))?;
         self.expect(t::symbol("("))?;
         let if_expr = self.parse_expression()?;
@@ -229,10 +326,12 @@ impl<'a> Parser<'a> {
             if_expr,
             if_statements,
             else_statements,
+            span: self.span_from(start),
         }))
     }
 
     fn parse_statement_while(&mut self) -> Res<Statement> {
+        let start = self.peek_span();
         self.expect(t::kw(Keyword::While))?;
         self.expect(t::symbol("("))?;
         let cond_expr = self.parse_expression()?;
@@ -243,30 +342,88 @@ impl<'a> Parser<'a> {
         Ok(Statement::WhileStatement(WhileStatement {
             cond_expr,
             statements,
+            span: self.span_from(start),
+        }))
+    }
+
+    fn parse_statement_for(&mut self) -> Res<Statement> {
+        let start = self.peek_span();
+        self.expect(t::kw(Keyword::For))?;
+        self.expect(t::symbol("("))?;
+        let init = match self.try_expect(t::symbol(";")) {
+            Ok(..) => None,
+            _ => Some(Box::new(self.parse_assignment()?)),
+        };
+        self.expect(t::symbol(";"))?;
+        let cond_expr = match self.try_expect(t::symbol(";")) {
+            Ok(..) => None,
+            _ => Some(self.parse_expression()?),
+        };
+        self.expect(t::symbol(";"))?;
+        let step = match self.try_expect(t::symbol(")")) {
+            Ok(..) => None,
+            _ => Some(Box::new(self.parse_assignment()?)),
+        };
+        self.expect(t::symbol(")"))?;
+        self.expect(t::symbol("{"))?;
+        let statements = self.parse_statements()?;
+        self.expect(t::symbol("}"))?;
+        Ok(Statement::ForStatement(ForStatement {
+            init,
+            cond_expr,
+            step,
+            statements,
+            span: self.span_from(start),
+        }))
+    }
+
+    fn parse_statement_break(&mut self) -> Res<Statement> {
+        let start = self.peek_span();
+        self.expect(t::kw(Keyword::Break))?;
+        self.expect(t::symbol(";"))?;
+        Ok(Statement::BreakStatement(BreakStatement {
+            span: self.span_from(start),
+        }))
+    }
+
+    fn parse_statement_continue(&mut self) -> Res<Statement> {
+        let start = self.peek_span();
+        self.expect(t::kw(Keyword::Continue))?;
+        self.expect(t::symbol(";"))?;
+        Ok(Statement::ContinueStatement(ContinueStatement {
+            span: self.span_from(start),
         }))
     }
 
     fn parse_statement_do(&mut self) -> Res<Statement> {
+        let start = self.peek_span();
         self.expect(t::kw(Keyword::Do))?;
         let call = self.parse_subroutine_call(None)?;
         self.expect(t::symbol(";"))?;
-        Ok(Statement::DoStatement(DoStatement { call }))
+        Ok(Statement::DoStatement(DoStatement {
+            call,
+            span: self.span_from(start),
+        }))
     }
 
     fn parse_statement_return(&mut self) -> Res<Statement> {
+        let start = self.peek_span();
         self.expect(t::kw(Keyword::Return))?;
-        let result = match expect::something(self.peek())? {
+        let result = match self.expect_something()? {
             Token::Symbol(s) if s == ";" => None,
             _ => Some(self.parse_expression()?),
         };
         self.expect(t::symbol(";"))?;
-        Ok(Statement::ReturnStatement(ReturnStatement { result }))
+        Ok(Statement::ReturnStatement(ReturnStatement {
+            result,
+            span: self.span_from(start),
+        }))
     }
 
     fn parse_expression(&mut self) -> Res<Expr> {
         let term = self.parse_term()?;
         let mut terms: Vec<(Op, Term)> = vec![];
-        while let Some(op) = expect::something(self.peek())?.get_op() {
+        while let Some(op) = self.expect_something()?.get_op() {
             self.next();
             terms.push((Op(op), self.parse_term()?));
         }
@@ -274,7 +431,8 @@ impl<'a> Parser<'a> {
     }
 
     fn parse_term(&mut self) -> Res<Term> {
-        Ok(match expect::something(self.peek())? {
+        let start = self.peek_span();
+        Ok(match self.expect_something()? {
             Token::IntegerConst(i) => {
                 self.next();
                 Term::IntegerConstant(i)
@@ -297,27 +455,27 @@ impl<'a> Parser<'a> {
             }
             Token::Symbol(op) => {
                 if !Token::is_unary_op(op.as_str()) {
-                    return Err("Invalid unary op".into());
+                    return self.error(format!("Invalid unary op: {}", op), start);
                 }
                 self.next();
                 Term::UnaryOp(Op(op), Box::new(self.parse_term()?))
             }
             Token::Identifier(ident) => {
                 self.next();
-                match expect::something(self.peek())? {
+                match self.expect_something()? {
                     Token::Symbol(s) if s == "[" => {
                         self.next();
                         let expr = self.parse_expression()?;
                         self.expect(t::symbol("]"))?;
-                        Term::IndexExpr(ident, Box::new(expr))
+                        Term::IndexExpr(ident, Box::new(expr), self.span_from(start))
                     }
                     Token::Symbol(s) if s == "." || s == "(" => {
-                        Term::SubroutineCall(self.parse_subroutine_call(Some(ident))?)
+                        Term::SubroutineCall(self.parse_subroutine_call(Some((ident, start)))?)
                     }
-                    _ => Term::VarName(ident),
+                    _ => Term::VarName(ident, start),
                 }
             }
-            _token => todo!("Unsupported term: {:?}", _token),
+            token => return self.error(format!("Expected expression, got: {:?}", token), start),
         })
     }
 
@@ -337,71 +495,180 @@ impl<'a> Parser<'a> {
         Ok(list)
     }
 
-    fn parse_subroutine_call(&mut self, maybe_name: Option<String>) -> Res<SubroutineCall> {
-        let name: String = match maybe_name {
-            Some(s) => s,
-            None => self.parse_identifier()?,
+    fn parse_subroutine_call(&mut self, maybe_name: Option<(String, Span)>) -> Res<SubroutineCall> {
+        let (name, start): (String, Span) = match maybe_name {
+            Some(name) => name,
+            None => (self.parse_identifier()?, self.last_span()),
         };
-        match expect::something(self.peek())? {
+        match self.expect_something()? {
             Token::Symbol(x) if x == "." => {
                 self.next();
                 let method_name = self.parse_identifier()?;
                 self.expect(t::symbol("("))?;
                 let expr_list = self.parse_expression_list()?;
                 self.expect(t::symbol(")"))?;
-                Ok(SubroutineCall::MethodCall(name, method_name, expr_list))
+                Ok(SubroutineCall::MethodCall(
+                    name,
+                    method_name,
+                    expr_list,
+                    self.span_from(start),
+                ))
             }
             Token::Symbol(x) if x == "(" => {
                 self.next();
                 let expr_list = self.parse_expression_list()?;
                 self.expect(t::symbol(")"))?;
-                Ok(SubroutineCall::SimpleCall(name, expr_list))
+                Ok(SubroutineCall::SimpleCall(
+                    name,
+                    expr_list,
+                    self.span_from(start),
+                ))
             }
-            _ => Err("Can't parse subroutine call".into()),
+            _ => self.error("Can't parse subroutine call", self.peek_span()),
         }
     }
 
     fn parse_identifier(&mut self) -> Res<String> {
         // dbg!(self.tokens.peek());
-        expect::identifier(self.next())
+        let span = self.peek_span();
+        expect::identifier(self.next()).or_else(|e| self.error(e.to_string(), span))
+    }
+
+    fn parse_type(&mut self) -> Res<GrammarItemType> {
+        let span = self.peek_span();
+        match self.expect_something()? {
+            token @ Token::Identifier(..) => {
+                self.next();
+                Ok(item_type_from_token(token).unwrap())
+            }
+            token => {
+                let token = expect::one_of(
+                    Some(token),
+                    &[
+                        t::kw(Keyword::Int),
+                        t::kw(Keyword::Boolean),
+                        t::kw(Keyword::Char),
+                    ],
+                )
+                .or_else(|e| self.error(e.to_string(), span))?;
+                self.next();
+                Ok(item_type_from_token(token).unwrap())
+            }
+        }
     }
 
     fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).map(|x| x.token.clone());
         self.pos += 1;
-        self.tokens.next().map(|x| x.1).cloned() //.map(|x| dbg!(x))
+        token //.map(|x| dbg!(x))
     }
 
     fn peek(&mut self) -> Option<Token> {
-        self.tokens.peek().map(|x| x.1).cloned() //.map(|x| dbg!(x))
+        self.tokens.get(self.pos).map(|x| x.token.clone()) //.map(|x| dbg!(x))
+    }
+
+    /// Span of the next token, or of the last one at the end of input.
+    fn peek_span(&self) -> Span {
+        self.tokens
+            .get(self.pos)
+            .or_else(|| self.tokens.last())
+            .map(|x| x.span)
+            .unwrap_or_default()
+    }
+
+    /// Span of the last consumed token.
+    fn last_span(&self) -> Span {
+        self.tokens
+            .get(self.pos.saturating_sub(1))
+            .map(|x| x.span)
+            .unwrap_or_default()
+    }
+
+    fn span_from(&self, start: Span) -> Span {
+        start.to(self.last_span())
     }
 
+    /// Consumes the next token if it is the expected one.
     fn expect(&mut self, token: Token) -> Res<Token> {
-        expect::specific(self.next(), token)
+        let span = self.peek_span();
+        let token =
+            expect::specific(self.peek(), token).or_else(|e| self.error(e.to_string(), span))?;
+        self.next();
+        Ok(token)
+    }
+
+    fn expect_something(&mut self) -> Res<Token> {
+        let span = self.peek_span();
+        expect::something(self.peek()).or_else(|e| self.error(e.to_string(), span))
     }
 
     fn try_expect(&mut self, token: Token) -> Res<Token> {
         expect::specific(self.peek(), token)
     }
 
-    fn parsing_error(&self, err: ParseError) -> ParseError {
-        let last_n_size = 5;
-        let pos = self.pos;
-
-        format!(
-            "{}\n\
-            Last tokens:\n{}",
-            err,
-            self.tokens_seq
-                .iter()
-                .skip(pos - last_n_size)
-                .take(last_n_size)
-                .enumerate()
-                .map(|(i, t)| format!("{}: {:?}", i + pos - last_n_size, t))
-                .collect::<Vec<_>>()
-                .join("\n")
-        )
-        .into()
+    fn error<T, S: Into<String>>(&self, message: S, span: Span) -> Res<T> {
+        Err(Box::new(Diagnostic::error(message, span)))
+    }
+
+    /// Locates errors which don't carry a span at the last consumed token.
+    fn parsing_error(&self, err: ParseError) -> Diagnostic {
+        match err.downcast::<Diagnostic>() {
+            Ok(diagnostic) => *diagnostic,
+            Err(err) => Diagnostic::error(err.to_string(), self.last_span()),
+        }
     }
+
+    fn record(&mut self, err: ParseError) {
+        let diagnostic = self.parsing_error(err);
+        self.errors.push(diagnostic);
+    }
+
+    /// Skips tokens after an error until the parser is at a point it can continue from:
+    /// past a `;`, or before a `}` or a keyword which starts a declaration or statement.
+    /// Blocks are skipped whole. Always makes progress when nothing was consumed since
+    /// `start_pos`, so the callers' loops terminate.
+    fn synchronize(&mut self, start_pos: usize) {
+        if self.pos == start_pos {
+            self.skip_token();
+        }
+        while let Some(token) = self.peek() {
+            match token {
+                Token::Symbol(s) if s == ";" => {
+                    self.next();
+                    return;
+                }
+                Token::Symbol(s) if s == "}" => return,
+                Token::Keyword(kw) if is_sync_keyword(&kw) => return,
+                _ => self.skip_token(),
+            }
+        }
+    }
+
+    /// Skips a token, or a whole `{ .. }` block if it opens one.
+    fn skip_token(&mut self) {
+        if self.next() != Some(t::symbol("{")) {
+            return;
+        }
+        let mut depth = 1;
+        while let Some(token) = self.next() {
+            match token {
+                Token::Symbol(s) if s == "{" => depth += 1,
+                Token::Symbol(s) if s == "}" => {
+                    depth -= 1;
+                    if depth == 0 {
+                        return;
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+fn is_sync_keyword(kw: &Keyword) -> bool {
+    STATEMENT_KEYWORDS.contains(kw)
+        || SUBROUTINE_KEYWORDS.contains(kw)
+        || [Keyword::Var, Keyword::Static, Keyword::Field].contains(kw)
 }
 
 /// Utilities for reading tokens.
@@ -444,10 +711,7 @@ mod expect {
     #[must_use = "Handle the result"]
     pub fn one_of(token: Option<Token>, whitelist: &[Token]) -> Res<Token> {
         let token = self::something(token)?;
-        if whitelist
-            .iter()
-            .any(|allowed_token| *allowed_token == token)
-        {
+        if whitelist.contains(&token) {
             Ok(token)
         } else {
             Err(format!(
@@ -478,7 +742,32 @@ pub struct ParseResult {
 }
 
 pub fn parse(input: &str) -> Result<ParseResult, Box<dyn std::error::Error>> {
-    let tokens = tokenize(input)?;
+    parse_with(input, false)
+}
+
+/// Like `parse`, with the language extensions if `extensions`: `for` loops,
+/// `break` and `continue`.
+pub fn parse_with(
+    input: &str,
+    extensions: bool,
+) -> Result<ParseResult, Box<dyn std::error::Error>> {
+    let tokens = tokenize_spanned_with(input, extensions)?;
     let parser = Parser::new(&tokens);
-    parser.parse()
+    parser
+        .parse()
+        .map_err(|e| crate::diagnostic::with_source(e, None, input))
+}
+
+/// Like `parse`, but returns the partial tree along with every syntax error.
+/// Tokenizer errors still fail the whole parse.
+pub fn parse_partial(
+    input: &str,
+) -> Result<(ParseResult, Vec<Diagnostic>), Box<dyn std::error::Error>> {
+    let tokens = tokenize_spanned(input)?;
+    let (result, errors) = Parser::new(&tokens).parse_partial();
+    let errors = errors
+        .into_iter()
+        .map(|e| e.with_source(None, input))
+        .collect();
+    Ok((result, errors))
 }
@@ -103,6 +103,28 @@ impl SymbolTable {
         None
     }
 
+    /// Variables in scope, the subroutine ones first, each group ordered by
+    /// segment and index.
+    pub fn entries(&self) -> Vec<(SymbolName, Entry)> {
+        let mut sub: Vec<(SymbolName, Entry)> = self
+            .sub
+            .entry_dict
+            .iter()
+            .map(|(name, e)| (name.clone(), e.into()))
+            .collect();
+        let mut class: Vec<(SymbolName, Entry)> = self
+            .class
+            .entry_dict
+            .iter()
+            .filter(|(name, _)| !self.sub.entry_dict.contains_key(*name))
+            .map(|(name, e)| (name.clone(), e.into()))
+            .collect();
+        sub.sort_by(|(_, a), (_, b)| (&a.kind, a.index).cmp(&(&b.kind, b.index)));
+        class.sort_by(|(_, a), (_, b)| (&a.kind, a.index).cmp(&(&b.kind, b.index)));
+        sub.extend(class);
+        sub
+    }
+
     pub fn count_instance_fields(&self) -> u16 {
         *self
             .class
@@ -128,12 +150,7 @@ impl SymbolTable {
         dict.entry_dict.insert(name.into(), entry);
     }
 
-    pub fn define_subroutine_var(
-        &mut self,
-        name: &str,
-        kind: SubVarKind,
-        typ: &GrammarItemType,
-    ) {
+    pub fn define_subroutine_var(&mut self, name: &str, kind: SubVarKind, typ: &GrammarItemType) {
         let dict = &mut self.sub;
         let index = dict.index_dict.entry(kind.clone()).or_insert(0);
         let entry = EntrySub {
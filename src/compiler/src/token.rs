@@ -1,3 +1,4 @@
+use crate::span::Span;
 use crate::xml::*;
 
 // #[derive(Debug)]
@@ -18,6 +19,12 @@ pub enum Token {
     StringConst(String),
 }
 
+#[derive(Debug, PartialEq, Clone)]
+pub struct SpannedToken {
+    pub token: Token,
+    pub span: Span,
+}
+
 impl Token {
     pub fn as_xml_decl(&self) -> String {
         match self {
@@ -94,6 +101,17 @@ pub enum Keyword {
     Else,
     While,
     Return,
+    For,
+    Break,
+    Continue,
+}
+
+impl Keyword {
+    /// Whether the keyword belongs to the language extensions. Without them
+    /// it's an ordinary identifier, so standard Jack programs can use it.
+    pub fn is_extension(&self) -> bool {
+        matches!(self, Keyword::For | Keyword::Break | Keyword::Continue)
+    }
 }
 
 pub fn keyword_from_string(s: &str) -> Option<Keyword> {
@@ -119,6 +137,9 @@ pub fn keyword_from_string(s: &str) -> Option<Keyword> {
         "else" => Keyword::Else,
         "while" => Keyword::While,
         "return" => Keyword::Return,
+        "for" => Keyword::For,
+        "break" => Keyword::Break,
+        "continue" => Keyword::Continue,
         _ => return None,
     })
 }
@@ -146,5 +167,8 @@ pub fn keyword_to_string(keyword: &Keyword) -> &'static str {
         Keyword::Else => "else",
         Keyword::While => "while",
         Keyword::Return => "return",
+        Keyword::For => "for",
+        Keyword::Break => "break",
+        Keyword::Continue => "continue",
     }
 }
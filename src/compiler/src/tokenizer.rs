@@ -1,85 +1,107 @@
+use crate::diagnostic::Diagnostic;
 use crate::line_chars::LineChars;
+use crate::span::Span;
 use crate::token::*;
 use crate::xml::*;
 
 #[derive(Debug)]
 pub struct Tokenizer<'a> {
     source: &'a str,
+    /// Whether the keywords of the language extensions are recognized.
+    extensions: bool,
 }
 
 impl<'a> Tokenizer<'a> {
     pub fn new(source: &'a str) -> Self {
-        Self { source }
+        Self {
+            source,
+            extensions: false,
+        }
+    }
+
+    pub fn with_extensions(mut self, extensions: bool) -> Self {
+        self.extensions = extensions;
+        self
     }
 
-    pub fn tokenize(&self) -> Result<Vec<Token>, Box<dyn std::error::Error>> {
-        let mut tokens: Vec<Token> = vec![];
+    pub fn tokenize(&self) -> Result<Vec<SpannedToken>, Box<dyn std::error::Error>> {
+        let mut tokens: Vec<SpannedToken> = vec![];
         let mut chars = LineChars::new(self.source.char_indices());
 
         while let Some(ch) = chars.peek() {
             // dbg!((ch, chars.line, chars.line_index));
+            let (line, column) = (chars.line, chars.line_index);
 
-            match ch {
+            let token = match ch {
                 _ if ch.is_whitespace() => {
                     chars.next();
+                    None
                 }
                 '\n' => {
                     chars.next();
+                    None
                 }
                 '/' => {
                     chars.next();
                     let next_char = chars.peek();
                     match next_char {
-                        None => {}
+                        None => None,
                         Some('/') => {
                             chars.next();
                             Self::consume_until(&mut chars, "\n");
+                            None
                         }
                         Some('*') => {
                             chars.next();
                             Self::consume_until(&mut chars, "*/");
+                            None
                         }
-                        Some(..) => {
-                            tokens.push(Token::Symbol(ch.to_string()));
-                        }
-                    };
+                        Some(..) => Some(Token::Symbol(ch.to_string())),
+                    }
                 }
                 // '/' is handled separately
                 '{' | '}' | '(' | ')' | '[' | ']' | '.' | ',' | ';' | '+' | '-' | '*' | '&'
                 | '|' | '<' | '>' | '=' | '~' => {
-                    tokens.push(Token::Symbol(ch.to_string()));
                     chars.next();
+                    Some(Token::Symbol(ch.to_string()))
                 }
                 '"' => {
                     chars.next();
-                    tokens.push(Self::parse_string(&mut chars));
-                }
-                _ if ch.is_numeric() => {
-                    tokens.push(Self::parse_numeric(&mut chars)?);
+                    Some(Self::parse_string(&mut chars))
                 }
+                _ if ch.is_numeric() => Some(Self::parse_numeric(&mut chars).map_err(|e| {
+                    self.tokenization_error(
+                        e,
+                        Span::new(line, column, chars.line, chars.line_index),
+                    )
+                })?),
                 _ if Self::is_identifier_start_char(ch) => {
-                    tokens.push(Self::parse_identifier_or_keyword(&mut chars)?);
+                    Some(self.parse_identifier_or_keyword(&mut chars))
                 }
                 _ => {
-                    return self.tokenization_error(&mut chars, ch);
+                    return Err(self.tokenization_error(
+                        format!("Can't tokenize '{}'", ch),
+                        Span::new(line, column, line, column + 1),
+                    ))
                 }
+            };
+            if let Some(token) = token {
+                tokens.push(SpannedToken {
+                    token,
+                    span: Span::new(line, column, chars.line, chars.line_index),
+                });
             }
         }
 
         Ok(tokens)
     }
 
-    fn parse_identifier_or_keyword(
-        chars: &mut LineChars,
-    ) -> Result<Token, Box<dyn std::error::Error>> {
+    fn parse_identifier_or_keyword(&self, chars: &mut LineChars) -> Token {
         let s = Self::consume_while(chars, Self::is_identifier_char);
-        Ok(
-            if let Some(token_keyword) = keyword_from_string(s.as_str()) {
-                Token::Keyword(token_keyword)
-            } else {
-                Token::Identifier(s)
-            },
-        )
+        match keyword_from_string(s.as_str()) {
+            Some(keyword) if self.extensions || !keyword.is_extension() => Token::Keyword(keyword),
+            _ => Token::Identifier(s),
+        }
     }
 
     fn parse_string(chars: &mut LineChars) -> Token {
@@ -90,10 +112,10 @@ impl<'a> Tokenizer<'a> {
         Token::StringConst(s)
     }
 
-    fn parse_numeric(chars: &mut LineChars) -> Result<Token, Box<dyn std::error::Error>> {
+    fn parse_numeric(chars: &mut LineChars) -> Result<Token, String> {
         let num = Self::consume_while(chars, |ch| ch.is_numeric());
         str::parse::<u16>(num.as_str())
-            .map_err(|_| format!("Can't parse num: {}", num).into())
+            .map_err(|_| format!("Can't parse num: {}", num))
             .map(Token::IntegerConst)
     }
 
@@ -136,28 +158,35 @@ impl<'a> Tokenizer<'a> {
         Self::consume_n_chars(chars, end.len());
     }
 
-    fn tokenization_error(
-        &self,
-        chars: &mut LineChars,
-        ch: char,
-    ) -> Result<Vec<Token>, Box<dyn std::error::Error>> {
-        chars.next();
-        // dbg!(&tokens.iter().rev().take(30).rev().collect::<Vec<_>>());
-        Err(format!(
-            "Can't tokenize at {1}:{2}: '{0}'\n\
-            At line:\n\
-            {line}\n",
-            ch,
-            chars.line,
-            chars.line_index,
-            line = self.source.lines().nth(chars.line - 1).unwrap_or_default()
-        )
-        .into())
+    fn tokenization_error(&self, message: String, span: Span) -> Box<dyn std::error::Error> {
+        Box::new(Diagnostic::error(message, span).with_source(None, self.source))
     }
 }
 
 pub fn tokenize(input: &str) -> Result<Vec<Token>, Box<dyn std::error::Error>> {
-    let tokenizer = Tokenizer::new(input);
+    tokenize_with(input, false)
+}
+
+/// Like `tokenize`, with the keywords of the language extensions if `extensions`.
+pub fn tokenize_with(
+    input: &str,
+    extensions: bool,
+) -> Result<Vec<Token>, Box<dyn std::error::Error>> {
+    Ok(tokenize_spanned_with(input, extensions)?
+        .into_iter()
+        .map(|spanned| spanned.token)
+        .collect())
+}
+
+pub fn tokenize_spanned(input: &str) -> Result<Vec<SpannedToken>, Box<dyn std::error::Error>> {
+    tokenize_spanned_with(input, false)
+}
+
+pub fn tokenize_spanned_with(
+    input: &str,
+    extensions: bool,
+) -> Result<Vec<SpannedToken>, Box<dyn std::error::Error>> {
+    let tokenizer = Tokenizer::new(input).with_extensions(extensions);
     tokenizer.tokenize()
 }
 
@@ -0,0 +1,339 @@
+use std::fmt;
+
+use crate::{
+    checker::{type_name, Scope, Signatures},
+    diagnostic::{Diagnostic, Severity},
+    node::*,
+    span::Span,
+    token::Keyword,
+};
+
+/// Inferred type of an expression.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Type {
+    Item(GrammarItemType),
+    /// Type of `null`, assignable to any class.
+    Null,
+    /// Result of a void subroutine.
+    Void,
+    /// Not known statically, e.g. an array element or an unknown call.
+    Any,
+}
+
+impl fmt::Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Type::Item(typ) => f.write_str(type_name(typ)),
+            Type::Null => f.write_str("null"),
+            Type::Void => f.write_str("void"),
+            Type::Any => f.write_str("any"),
+        }
+    }
+}
+
+const INT: Type = Type::Item(GrammarItemType::Int);
+const BOOLEAN: Type = Type::Item(GrammarItemType::Boolean);
+
+fn is_array(typ: &GrammarItemType) -> bool {
+    matches!(typ, GrammarItemType::Class(name) if name == "Array")
+}
+
+/// Whether a value of type `from` can be stored in `to`. Classic Jack is
+/// weakly typed, so `int` and `char` mix freely and `Array` stands in for
+/// any object or address.
+fn is_assignable(to: &GrammarItemType, from: &Type) -> bool {
+    use GrammarItemType::*;
+    match (to, from) {
+        (_, Type::Any) => true,
+        (_, Type::Void) => false,
+        (Class(..), Type::Null) => true,
+        (_, Type::Null) => false,
+        (Int | Char, Type::Item(Int | Char)) => true,
+        (to, Type::Item(from)) if is_array(to) => !matches!(from, Boolean | Char),
+        (Int, Type::Item(from)) if is_array(from) => true,
+        (Class(..), Type::Item(from)) if is_array(from) => true,
+        (to, Type::Item(from)) => to == from,
+    }
+}
+
+fn is_numeric(typ: &Type) -> bool {
+    is_assignable(&GrammarItemType::Int, typ) && *typ != Type::Null
+}
+
+/// Infers expression types and checks them against declarations, reporting
+/// problems with the given severity.
+pub fn check_class(class: &Class, signatures: &Signatures, severity: Severity) -> Vec<Diagnostic> {
+    let Class(class_name, var_decs, sub_decs, _) = class;
+    let mut checker = TypeChecker {
+        signatures,
+        class_name,
+        scope: Scope::for_class(var_decs),
+        return_type: GrammarSubroutineReturnType::Void,
+        span: Span::default(),
+        severity,
+        diagnostics: vec![],
+    };
+    for SubroutineDec(_, return_type, _, params, Subroutine(var_decs, statements), _) in sub_decs {
+        checker.return_type = return_type.clone();
+        checker.scope.enter_subroutine(params, var_decs);
+        checker.check_statements(statements);
+    }
+    checker.diagnostics
+}
+
+struct TypeChecker<'a> {
+    signatures: &'a Signatures,
+    class_name: &'a str,
+    scope: Scope,
+    return_type: GrammarSubroutineReturnType,
+    /// Span of the statement being checked, expressions don't carry their own.
+    span: Span,
+    severity: Severity,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl<'a> TypeChecker<'a> {
+    fn report<S: Into<String>>(&mut self, message: S, span: Span) {
+        let diagnostic = Diagnostic::new(self.severity, message, Some(span));
+        self.diagnostics.push(diagnostic);
+    }
+
+    fn expect(&mut self, expected: &GrammarItemType, actual: &Type, what: &str) {
+        if !is_assignable(expected, actual) {
+            let message = format!("{}: expected {}, got {}", what, type_name(expected), actual);
+            self.report(message, self.span);
+        }
+    }
+
+    fn check_statements(&mut self, statements: &[Statement]) {
+        for statement in statements {
+            self.span = statement.span();
+            self.check_statement(statement);
+        }
+    }
+
+    fn check_statement(&mut self, statement: &Statement) {
+        match statement {
+            Statement::LetStatement(stmt) => self.check_let(stmt),
+            Statement::IfStatement(stmt) => {
+                let cond = self.infer_expression(&stmt.if_expr);
+                self.expect(&GrammarItemType::Boolean, &cond, "If condition");
+                self.check_statements(&stmt.if_statements);
+                if let Some(statements) = &stmt.else_statements {
+                    self.check_statements(statements);
+                }
+            }
+            Statement::WhileStatement(stmt) => {
+                let cond = self.infer_expression(&stmt.cond_expr);
+                self.expect(&GrammarItemType::Boolean, &cond, "While condition");
+                self.check_statements(&stmt.statements);
+            }
+            Statement::DoStatement(stmt) => {
+                self.infer_call(&stmt.call);
+            }
+            Statement::ReturnStatement(stmt) => {
+                let value = stmt.result.as_ref().map(|e| self.infer_expression(e));
+                // Missing or unexpected values are reported by the compiler
+                if let (GrammarSubroutineReturnType::Type(typ), Some(value)) =
+                    (&self.return_type.clone(), value)
+                {
+                    self.expect(typ, &value, "Return value");
+                }
+            }
+            Statement::ForStatement(stmt) => {
+                if let Some(init) = &stmt.init {
+                    self.check_let(init);
+                }
+                if let Some(expr) = &stmt.cond_expr {
+                    let cond = self.infer_expression(expr);
+                    self.expect(&GrammarItemType::Boolean, &cond, "For condition");
+                }
+                if let Some(step) = &stmt.step {
+                    self.check_let(step);
+                }
+                self.check_statements(&stmt.statements);
+            }
+            Statement::BreakStatement(..) | Statement::ContinueStatement(..) => {}
+        }
+    }
+
+    fn check_let(&mut self, stmt: &LetStatement) {
+        let value = self.infer_expression(&stmt.value_expr);
+        let target = self.scope.lookup(&stmt.name).cloned();
+        match (&stmt.index_expr, target) {
+            (Some(index), target) => {
+                self.check_index(&stmt.name, target.as_ref(), index);
+            }
+            (None, Some(target)) => {
+                self.expect(&target, &value, &format!("Assignment to {}", stmt.name));
+            }
+            (None, None) => {}
+        }
+    }
+
+    fn check_index(&mut self, name: &str, target: Option<&GrammarItemType>, index: &Expr) {
+        if let Some(target) = target {
+            if !is_array(target) {
+                let message = format!("Can't index {} of type {}", name, type_name(target));
+                self.report(message, self.span);
+            }
+        }
+        let index = self.infer_expression(index);
+        self.expect(&GrammarItemType::Int, &index, "Array index");
+    }
+
+    fn infer_expression(&mut self, Expr(term, terms): &Expr) -> Type {
+        let mut left = self.infer_term(term);
+        for (Op(op), term) in terms {
+            let right = self.infer_term(term);
+            left = self.infer_binary_op(op, left, right);
+        }
+        left
+    }
+
+    fn infer_binary_op(&mut self, op: &str, left: Type, right: Type) -> Type {
+        let operands_are = |check: &dyn Fn(&Type) -> bool| check(&left) && check(&right);
+        match op {
+            "+" | "-" | "*" | "/" => {
+                if !operands_are(&is_numeric) {
+                    self.report_operands(op, "int", &left, &right);
+                }
+                INT
+            }
+            "<" | ">" => {
+                if !operands_are(&is_numeric) {
+                    self.report_operands(op, "int", &left, &right);
+                }
+                BOOLEAN
+            }
+            "&" | "|" => {
+                let is_boolean = |t: &Type| matches!(t, Type::Any) || *t == BOOLEAN;
+                if operands_are(&is_boolean) {
+                    BOOLEAN
+                } else if operands_are(&is_numeric) {
+                    INT
+                } else {
+                    self.report_operands(op, "int or boolean", &left, &right);
+                    Type::Any
+                }
+            }
+            "=" => {
+                let comparable = match (&left, &right) {
+                    (Type::Item(l), r) => is_assignable(l, r),
+                    (l, Type::Item(r)) => is_assignable(r, l),
+                    (Type::Void, _) | (_, Type::Void) => false,
+                    _ => true,
+                };
+                if !comparable {
+                    let message = format!("Can't compare {} with {}", left, right);
+                    self.report(message, self.span);
+                }
+                BOOLEAN
+            }
+            _ => Type::Any,
+        }
+    }
+
+    fn report_operands(&mut self, op: &str, expected: &str, left: &Type, right: &Type) {
+        let message = format!(
+            "Operator {} expects {} operands, got {} and {}",
+            op, expected, left, right
+        );
+        self.report(message, self.span);
+    }
+
+    fn infer_term(&mut self, term: &Term) -> Type {
+        match term {
+            Term::IntegerConstant(..) => INT,
+            Term::StringConst(..) => Type::Item(GrammarItemType::Class("String".into())),
+            Term::KeywordConstant(Keyword::True | Keyword::False) => BOOLEAN,
+            Term::KeywordConstant(Keyword::Null) => Type::Null,
+            Term::KeywordConstant(Keyword::This) => {
+                Type::Item(GrammarItemType::Class(self.class_name.into()))
+            }
+            Term::KeywordConstant(..) => Type::Any,
+            Term::VarName(name, _) => match self.scope.lookup(name) {
+                Some(typ) => Type::Item(typ.clone()),
+                None => Type::Any,
+            },
+            Term::IndexExpr(name, index, _) => {
+                let target = self.scope.lookup(name).cloned();
+                self.check_index(name, target.as_ref(), index);
+                Type::Any
+            }
+            Term::ParenExpr(expr) => self.infer_expression(expr),
+            Term::UnaryOp(Op(op), term) => {
+                let typ = self.infer_term(term);
+                match op.as_str() {
+                    "-" => {
+                        if !is_numeric(&typ) {
+                            let message = format!("Operator - expects an int operand, got {}", typ);
+                            self.report(message, self.span);
+                        }
+                        INT
+                    }
+                    _ if typ == BOOLEAN => BOOLEAN,
+                    _ => {
+                        if !is_numeric(&typ) {
+                            let message = format!(
+                                "Operator {} expects an int or boolean operand, got {}",
+                                op, typ
+                            );
+                            self.report(message, self.span);
+                        }
+                        INT
+                    }
+                }
+            }
+            Term::SubroutineCall(call) => {
+                let typ = self.infer_call(call);
+                if typ == Type::Void {
+                    let message = "Void subroutine call can't be used as a value";
+                    self.report(message, call.span());
+                }
+                typ
+            }
+        }
+    }
+
+    /// Checks argument types and returns the result type of the call.
+    fn infer_call(&mut self, call: &SubroutineCall) -> Type {
+        let (class_name, name, args, span) = match call {
+            SubroutineCall::SimpleCall(name, args, span) => {
+                (self.class_name.to_string(), name, args, span)
+            }
+            SubroutineCall::MethodCall(target, name, args, span) => {
+                match self.scope.lookup(target) {
+                    Some(GrammarItemType::Class(class_name)) => {
+                        (class_name.clone(), name, args, span)
+                    }
+                    Some(_) => return Type::Any,
+                    None => (target.clone(), name, args, span),
+                }
+            }
+        };
+        let arg_types: Vec<Type> = args.iter().map(|arg| self.infer_expression(arg)).collect();
+        let signature = match self.signatures.lookup(&class_name, name) {
+            Some(signature) => signature,
+            // Unknown subroutines are reported by the checker
+            None => return Type::Any,
+        };
+        for (i, (param, arg)) in signature.params.iter().zip(&arg_types).enumerate() {
+            if !is_assignable(param, arg) {
+                let message = format!(
+                    "Argument {} of {}.{}: expected {}, got {}",
+                    i + 1,
+                    class_name,
+                    name,
+                    type_name(param),
+                    arg
+                );
+                self.report(message, *span);
+            }
+        }
+        match &signature.return_type {
+            GrammarSubroutineReturnType::Void => Type::Void,
+            GrammarSubroutineReturnType::Type(typ) => Type::Item(typ.clone()),
+        }
+    }
+}
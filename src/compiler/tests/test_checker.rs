@@ -0,0 +1,133 @@
+use std::path::PathBuf;
+
+use compiler::{
+    checker::{self, Signatures},
+    compiler_cli,
+    config::{CompileOptions, Config},
+    diagnostic::Severity,
+    parser,
+};
+
+fn check(sources: &[&str]) -> Vec<String> {
+    let mut signatures = Signatures::with_os().unwrap();
+    let classes: Vec<_> = sources
+        .iter()
+        .map(|source| parser::parse(source).unwrap().root)
+        .collect();
+    for class in &classes {
+        signatures.add_class(class);
+    }
+    classes
+        .iter()
+        .flat_map(|class| checker::check_class(class, &signatures))
+        .map(|e| {
+            format!(
+                "{}:{}: {}",
+                e.span.unwrap().line,
+                e.span.unwrap().column,
+                e.message
+            )
+        })
+        .collect()
+}
+
+const FOO: &str = "class Foo {
+  field int x;
+  constructor Foo new(int ax) { let x = ax; return this; }
+  method int get() { return x; }
+  function int twice(int a) { return a + a; }
+}
+";
+
+#[test]
+fn test_valid_calls() {
+    let main = "class Main {
+  function void main() {
+    var Foo foo;
+    let foo = Foo.new(1);
+    do Output.printInt(foo.get() + Foo.twice(2));
+    return;
+  }
+}
+";
+    assert_eq!(check(&[FOO, main]), Vec::<String>::new());
+}
+
+#[test]
+fn test_invalid_calls() {
+    let main = "class Main {
+  field int n;
+  function void main() {
+    var Foo foo;
+    let foo = Foo.new();
+    do Bar.baz();
+    do Foo.missing();
+    do Foo.get();
+    do foo.twice(1);
+    do n.get();
+    do helper();
+    return;
+  }
+  method void helper() {
+    do Math.multiply(1, 2, 3);
+    return;
+  }
+}
+";
+    assert_eq!(
+        check(&[FOO, main]),
+        vec![
+            "5:15: Foo.new expects 1 argument(s), got 0",
+            "6:8: Unknown class: Bar",
+            "7:8: Unknown subroutine: Foo.missing",
+            "8:8: Can't call method Foo.get without an object",
+            "9:8: Can't call function Foo.twice on an object",
+            "10:8: Can't call get on n of type int",
+            "11:8: Can't call method Main.helper from a function",
+            "15:8: Math.multiply expects 2 argument(s), got 3",
+        ]
+    );
+}
+
+#[test]
+fn test_unknown_class_is_a_warning() {
+    let main = "class Main {\n  function void main() {\n    do Bar.baz();\n    return;\n  }\n}\n";
+    let mut signatures = Signatures::with_os().unwrap();
+    let class = parser::parse(main).unwrap().root;
+    signatures.add_class(&class);
+    let diagnostics = checker::check_class(&class, &signatures);
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(diagnostics[0].severity, Severity::Warning);
+}
+
+/// A class compiled on its own can call the classes next to it, and the OS
+/// `Sys` can call the program's `Main`.
+#[test]
+fn test_single_file_with_siblings() {
+    let dir = env!("CARGO_MANIFEST_DIR");
+    for file in ["tests/inputs/11/Pong/PongGame.jack", "../os/Sys.jack"] {
+        let args = ["compiler", "--check", &format!("{}/{}", dir, file)].map(String::from);
+        let config = Config::new(&args).unwrap();
+        if let Err(e) = compiler_cli::run_for_config(&config) {
+            panic!("{}: {}", file, e);
+        }
+    }
+}
+
+#[test]
+fn test_calls_to_siblings_are_checked() {
+    let main = "class Main {\n  function void main() {\n    do Foo.new();\n    return;\n  }\n}\n";
+    let sources = || vec![(PathBuf::from("Main.jack"), main.to_string())];
+    let siblings = [(PathBuf::from("Foo.jack"), FOO.to_string())];
+    let options = CompileOptions::default();
+    let output = compiler_cli::compile_sources(sources(), &options).unwrap();
+    assert_eq!(output.warnings[0].message, "Unknown class: Foo");
+    let err = compiler_cli::compile_sources_with_siblings(sources(), &siblings, &options)
+        .unwrap_err()
+        .to_string();
+    assert!(
+        err.contains("Foo.new expects 1 argument(s), got 0"),
+        "{}",
+        err
+    );
+}
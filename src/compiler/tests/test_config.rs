@@ -0,0 +1,77 @@
+use std::path::{Path, PathBuf};
+
+use compiler::config::{Config, Verbosity, STDIO};
+
+fn config(args: &[&str]) -> Result<Config, Box<dyn std::error::Error>> {
+    let args: Vec<String> = std::iter::once("compiler")
+        .chain(args.iter().copied())
+        .map(String::from)
+        .collect();
+    Config::new(&args)
+}
+
+#[test]
+fn test_options_in_any_order() {
+    let config = config(&["-O2", "--check", "src/Main.jack", "-o", "out.vm", "-q"]).unwrap();
+    assert_eq!(config.source_path, "src/Main.jack");
+    assert_eq!(config.output.as_deref(), Some("out.vm"));
+    assert!(config.check);
+    assert_eq!(config.verbosity, Verbosity::Quiet);
+    assert_eq!(config.options.opt_level, 2);
+}
+
+#[test]
+fn test_argument_errors() {
+    assert!(config(&[]).is_err());
+    assert!(config(&["Main.jack", "-o"]).is_err());
+    assert!(config(&["Main.jack", "Other.jack"]).is_err());
+    assert!(config(&["Main.jack", "--bogus"]).is_err());
+    assert!(config(&["--help"]).unwrap().help);
+}
+
+#[test]
+fn test_target_path() {
+    let file = Path::new("src/Main.jack");
+    let default = config(&["src/Main.jack"]).unwrap();
+    assert_eq!(
+        default.target_path(file, "vm"),
+        PathBuf::from("src/Main.vm")
+    );
+
+    let output = config(&["src/Main.jack", "-o", "out.vm"]).unwrap();
+    assert_eq!(output.target_path(file, "vm"), PathBuf::from("out.vm"));
+
+    let dir = env!("CARGO_MANIFEST_DIR");
+    let output_dir = config(&[dir, "-o", "build"]).unwrap();
+    assert_eq!(
+        output_dir.target_path(file, "vm"),
+        PathBuf::from("build/Main.vm")
+    );
+
+    let stdin = config(&[STDIO]).unwrap();
+    assert!(stdin.writes_stdout());
+    assert!(!default.writes_stdout());
+}
+
+#[test]
+fn test_tokens_path() {
+    let file = Path::new("src/Main.jack");
+    let default = config(&["src/Main.jack", "--tokens"]).unwrap();
+    assert_eq!(
+        default.tokens_path(file).unwrap(),
+        PathBuf::from("src/Main.out.xml")
+    );
+
+    // Not the output file, which gets the VM code
+    let output = config(&["src/Main.jack", "--tokens", "-o", "out.vm"]).unwrap();
+    assert_eq!(output.tokens_path(file).unwrap(), PathBuf::from("out.xml"));
+    let output = config(&["src/Main.jack", "--tokens", "-o", "out.xml"]).unwrap();
+    assert!(output.tokens_path(file).is_err());
+
+    let dir = env!("CARGO_MANIFEST_DIR");
+    let output_dir = config(&[dir, "--tokens", "-o", "build"]).unwrap();
+    assert_eq!(
+        output_dir.tokens_path(file).unwrap(),
+        PathBuf::from("build/Main.out.xml")
+    );
+}
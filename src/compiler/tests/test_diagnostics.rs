@@ -0,0 +1,61 @@
+use compiler::{compiler::compile_program, diagnostic, node::Class, parser};
+
+fn compile_error(source: &str) -> String {
+    parser::parse(source)
+        .and_then(compile_program)
+        .map_err(|e| diagnostic::with_source(e, None, source))
+        .expect_err("Expected an error")
+        .to_string()
+}
+
+#[test]
+fn test_unknown_var_is_located() {
+    let source = "class Main {\n  function void main() {\n    let x = 1;\n    return;\n  }\n}\n";
+    assert_eq!(
+        compile_error(source),
+        "error: Unknown var: x\n --> <input>:3:5\n  |\n3 |     let x = 1;\n  |     ^^^^^^^^^^"
+    );
+}
+
+#[test]
+fn test_parse_error_is_located() {
+    let source = "class Main {\n  function void main() {\n    let = 1;\n  }\n}\n";
+    let message = compile_error(source);
+    assert!(message.contains("--> <input>:3:9"), "{}", message);
+    assert!(message.contains("3 |     let = 1;"), "{}", message);
+}
+
+#[test]
+fn test_parser_reports_every_syntax_error() {
+    let source = "class Main {
+  field int x
+  function void main() {
+    let = 1;
+    do Output.printInt(;
+    let y = 2;
+    return;
+  }
+  method int get() {
+    return x + ;
+  }
+  function void other() {
+    return;
+  }
+}
+";
+    let (result, errors) = parser::parse_partial(source).unwrap();
+    let lines: Vec<usize> = errors.iter().map(|e| e.span.unwrap().line).collect();
+    assert_eq!(lines, vec![3, 4, 5, 10]);
+
+    let Class(name, _, sub_decs, _) = result.root;
+    assert_eq!(name, "Main");
+    let names: Vec<&str> = sub_decs.iter().map(|s| s.2.as_str()).collect();
+    assert_eq!(names, vec!["main", "get", "other"]);
+}
+
+#[test]
+fn test_parser_doesnt_panic_on_unexpected_term() {
+    let source = "class Main {\n  function void main() {\n    let x = else;\n    return;\n  }\n}\n";
+    let message = compile_error(source);
+    assert!(message.contains("Expected expression"), "{}", message);
+}
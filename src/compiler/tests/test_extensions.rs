@@ -0,0 +1,200 @@
+use std::path::PathBuf;
+
+use compiler::{
+    checker::Signatures,
+    compiler_cli,
+    config::CompileOptions,
+    lint::{self, LintSet},
+    node_printer, parser,
+    token::{Keyword, Token},
+    tokenizer,
+};
+use vm::interpreter::{Interpreter, Program};
+
+const MAIN: &str = "class Main {
+  function void main() {
+    var int i, j, sum, count;
+    let sum = 0;
+    for (i = 0; i < 10; i = i + 1) {
+      if (i = 3) {
+        continue;
+      }
+      if (i = 6) {
+        break;
+      }
+      let sum = sum + i;
+    }
+    do Memory.poke(8000, sum);
+    do Memory.poke(8001, i);
+    let count = 0;
+    for (;;) {
+      let j = 0;
+      while (true) {
+        let j = j + 1;
+        if (j > 2) {
+          break;
+        }
+        let count = count + 1;
+      }
+      if (count > 5) {
+        break;
+      }
+    }
+    do Memory.poke(8002, count);
+    return;
+  }
+}
+";
+
+fn options(extensions: bool) -> CompileOptions {
+    CompileOptions {
+        extensions,
+        ..Default::default()
+    }
+}
+
+fn compile(source: &str) -> Result<String, Box<dyn std::error::Error>> {
+    let sources = vec![(PathBuf::from("Main.jack"), source.to_string())];
+    let output = compiler_cli::compile_sources(sources, &options(true))?;
+    Ok(output.files[0].1.vm_code.clone())
+}
+
+fn main_body(statements: &str) -> String {
+    format!(
+        "class Main {{\n  function void main() {{\n    var int i;\n{}\n    return;\n  }}\n}}\n",
+        statements
+    )
+}
+
+#[test]
+fn test_loops_run() {
+    let vm_code = compile(MAIN).unwrap();
+    let mut program = Program::from_modules(&[("Main", vm_code.as_str())]).unwrap();
+    program.link_builtins();
+    let mut vm = Interpreter::new(program);
+    vm.bootstrap().unwrap();
+    vm.run(100_000).unwrap();
+    assert!(vm.is_halted());
+    // 0 + 1 + 2 + 4 + 5, stopped at 6; two counts per `for` iteration
+    let results: Vec<i16> = (8000..8003).map(|addr| vm.peek(addr)).collect();
+    assert_eq!(results, vec![12, 6, 6]);
+}
+
+#[test]
+fn test_keywords_need_extensions() {
+    let source = "for break continue";
+    assert_eq!(
+        tokenizer::tokenize(source).unwrap(),
+        vec![
+            Token::Identifier("for".into()),
+            Token::Identifier("break".into()),
+            Token::Identifier("continue".into()),
+        ]
+    );
+    assert_eq!(
+        tokenizer::tokenize_with(source, true).unwrap(),
+        vec![
+            Token::Keyword(Keyword::For),
+            Token::Keyword(Keyword::Break),
+            Token::Keyword(Keyword::Continue),
+        ]
+    );
+
+    // Standard Jack programs can keep using them as names
+    let source = main_body("    var int for;\n    let for = 1;");
+    let sources = vec![(PathBuf::from("Main.jack"), source.clone())];
+    assert!(compiler_cli::compile_sources(sources.clone(), &options(false)).is_ok());
+    assert!(compiler_cli::compile_sources(sources, &options(true)).is_err());
+
+    let source = main_body("    for (i = 0; i < 3; i = i + 1) {}");
+    assert!(parser::parse(&source).is_err());
+    assert!(parser::parse_with(&source, true).is_ok());
+    assert!(
+        CompileOptions::from_args(&["--extensions".into()])
+            .unwrap()
+            .extensions
+    );
+}
+
+#[test]
+fn test_parser_xml() {
+    let source = main_body("    for (i = 0; ; i = i + 1) { break; continue; }");
+    let xml = node_printer::result_to_xml(parser::parse_with(&source, true).unwrap(), None);
+    let tags: Vec<&str> = xml
+        .lines()
+        .map(str::trim)
+        .skip_while(|line| *line != "<forStatement>")
+        .take_while(|line| *line != "</forStatement>")
+        .filter(|line| !line.starts_with("<term>") && !line.starts_with("</term>"))
+        .collect();
+    assert_eq!(
+        tags,
+        vec![
+            "<forStatement>",
+            "<keyword> for </keyword>",
+            "<symbol> ( </symbol>",
+            "<assignment>",
+            "<identifier> i </identifier>",
+            "<symbol> = </symbol>",
+            "<expression>",
+            "<integerConstant> 0 </integerConstant>",
+            "</expression>",
+            "</assignment>",
+            "<symbol> ; </symbol>",
+            "<symbol> ; </symbol>",
+            "<assignment>",
+            "<identifier> i </identifier>",
+            "<symbol> = </symbol>",
+            "<expression>",
+            "<identifier> i </identifier>",
+            "<symbol> + </symbol>",
+            "<integerConstant> 1 </integerConstant>",
+            "</expression>",
+            "</assignment>",
+            "<symbol> ) </symbol>",
+            "<symbol> { </symbol>",
+            "<statements>",
+            "<breakStatement>",
+            "<keyword> break </keyword>",
+            "<symbol> ; </symbol>",
+            "</breakStatement>",
+            "<continueStatement>",
+            "<keyword> continue </keyword>",
+            "<symbol> ; </symbol>",
+            "</continueStatement>",
+            "</statements>",
+            "<symbol> } </symbol>",
+        ]
+    );
+}
+
+#[test]
+fn test_jumps_outside_loops() {
+    let err = compile(&main_body("    break;")).unwrap_err().to_string();
+    assert!(err.contains("Break outside of a loop"), "{}", err);
+    let err = compile(&main_body("    if (true) { continue; }"))
+        .unwrap_err()
+        .to_string();
+    assert!(err.contains("Continue outside of a loop"), "{}", err);
+}
+
+#[test]
+fn test_lints() {
+    let source = main_body(
+        "    var int n;\n    for (i = 0; i < n; i = i + 1) {\n      break;\n      let i = 2;\n    }",
+    );
+    let class = parser::parse_with(&source, true).unwrap().root;
+    let mut signatures = Signatures::with_os().unwrap();
+    signatures.add_class(&class);
+    let warnings: Vec<String> = lint::check_class(&class, &signatures, &LintSet::default())
+        .into_iter()
+        .map(|d| format!("{}: {}", d.span.unwrap().line, d.message))
+        .collect();
+    assert_eq!(
+        warnings,
+        vec![
+            "7: unreachable statement [-Wunreachable-code]",
+            "5: n is read before being assigned [-Wuninitialized]",
+        ]
+    );
+}
@@ -0,0 +1,64 @@
+use compiler::{
+    checker::Signatures,
+    config::CompileOptions,
+    lint::{self, Lint, LintSet},
+    parser,
+};
+
+const SOURCE: &str = "class Main {
+  field int unused, used;
+  static boolean flag;
+  method int get(int a, int b) {
+    var int x, y;
+    if (a > 0) { let x = 1; } else { let x = 2; }
+    let used = x + a + y;
+    do Math.abs(used);
+    if (a < 0) { return 1; } else { return 2; }
+    let x = 3;
+  }
+  method void noReturn() {
+    while (true) { return; }
+  }
+}
+";
+
+fn lint(lints: &LintSet) -> Vec<String> {
+    let class = parser::parse(SOURCE).unwrap().root;
+    let mut signatures = Signatures::with_os().unwrap();
+    signatures.add_class(&class);
+    lint::check_class(&class, &signatures, lints)
+        .into_iter()
+        .map(|d| format!("{}: {}", d.span.unwrap().line, d.message))
+        .collect()
+}
+
+#[test]
+fn test_default_lints() {
+    assert_eq!(
+        lint(&LintSet::default()),
+        vec![
+            "4: unused parameter: b [-Wunused-parameter]",
+            "10: unreachable statement [-Wunreachable-code]",
+            "7: y is read before being assigned [-Wuninitialized]",
+            "12: Main.noReturn can end without a return [-Wmissing-return]",
+            "2: unused field: unused [-Wunused-class-variable]",
+            "3: unused static variable: flag [-Wunused-class-variable]",
+        ]
+    );
+}
+
+#[test]
+fn test_lint_switches() {
+    let args: Vec<String> = ["-w", "-Wunused-result", "-Wmissing-return", "-Wno-missing-return"]
+        .iter()
+        .map(|s| s.to_string())
+        .collect();
+    let options = CompileOptions::from_args(&args).unwrap();
+    assert!(options.lints.is_enabled(Lint::UnusedResult));
+    assert!(!options.lints.is_enabled(Lint::MissingReturn));
+    assert_eq!(
+        lint(&options.lints),
+        vec!["8: result of Math.abs is discarded [-Wunused-result]"]
+    );
+    assert!(CompileOptions::from_args(&["-Wbogus".to_string()]).is_err());
+}
@@ -0,0 +1,256 @@
+use compiler::{
+    compiler::{compile_program, compile_program_mapped},
+    optimizer, os, parser,
+};
+use vm::interpreter::{Interpreter, Program};
+
+const SYS: &str = "class Sys {
+  function void init() {
+    do Memory.init();
+    do Math.init();
+    do Main.main();
+    return;
+  }
+  function void error(int code) {
+    return;
+  }
+}
+";
+
+const MAIN: &str = "class Main {
+  function void main() {
+    var int i, sum;
+    var boolean flag;
+    var Array a;
+    let a = Array.new(4);
+    // Jack has no operator precedence
+    do Memory.poke(8000, 2 + 3 * 4);
+    do Memory.poke(8001, -(7 - 10) & 15);
+    do Memory.poke(8002, ~(1 = 1));
+    do Memory.poke(8003, Main.sign(-5) + Main.sign(0) + Main.sign(9));
+    let i = 0;
+    let sum = 0;
+    while (i < 10) {
+      if (~(Main.odd(i) = 0)) {
+        let sum = sum + i;
+      } else {
+        let sum = sum - 1;
+      }
+      let i = i + 1;
+    }
+    do Memory.poke(8004, sum);
+    let flag = true;
+    while (flag) {
+      let flag = false;
+      let a[1] = 5;
+    }
+    if (true) {
+      do Memory.poke(8005, a[1]);
+    }
+    if (false | (i > 100)) {
+      do Memory.poke(8006, 1);
+    }
+    do Memory.poke(8007, -32767 - 1);
+    return;
+  }
+  function int sign(int x) {
+    if (x < 0) {
+      return -1;
+    } else {
+      if (x > 0) {
+        return 1;
+      }
+    }
+    return 0;
+  }
+  function int odd(int x) {
+    return x & 1;
+  }
+}
+";
+
+/// Runs `Main.main` on top of the parts of the OS it needs, returns the
+/// results it stored and the number of executed VM commands.
+fn run(level: u8) -> (Vec<i16>, u64) {
+    let mut sources: Vec<(&str, &str)> = os::SOURCES
+        .iter()
+        .copied()
+        .filter(|(name, _)| ["Array", "Math", "Memory"].contains(name))
+        .collect();
+    sources.push(("Sys", SYS));
+    sources.push(("Main", MAIN));
+    let modules: Vec<(&str, String)> = sources
+        .iter()
+        .map(|(name, source)| {
+            let vm_code = compile_program(parser::parse(source).unwrap()).unwrap();
+            (*name, optimizer::optimize(&vm_code, level))
+        })
+        .collect();
+    let modules: Vec<(&str, &str)> = modules.iter().map(|(n, c)| (*n, c.as_str())).collect();
+    let mut vm = Interpreter::new(Program::from_modules(&modules).unwrap());
+    vm.bootstrap().unwrap();
+    let steps = vm.run(1_000_000).unwrap();
+    assert!(vm.is_halted(), "-O{} didn't halt", level);
+    ((8000..8008).map(|addr| vm.peek(addr)).collect(), steps)
+}
+
+#[test]
+fn test_optimized_code_behaves_the_same() {
+    let (expected, steps_o0) = run(0);
+    assert_eq!(expected, vec![20, 3, 0, 0, 20, 5, 0, -32768]);
+    let (result_o1, steps_o1) = run(1);
+    let (result_o2, steps_o2) = run(2);
+    assert_eq!(result_o1, expected);
+    assert_eq!(result_o2, expected);
+    assert!(steps_o1 < steps_o0);
+    assert!(steps_o2 < steps_o1);
+}
+
+#[test]
+fn test_peephole_rewrites() {
+    let code = "function Main.f 0
+push constant 2
+push constant 3
+add
+push constant 1
+neg
+add
+pop local 0
+push local 0
+pop local 0
+push argument 0
+push argument 1
+gt
+not
+if-goto L1
+goto L2
+label L1
+push constant 0
+return
+label L2
+push constant 0
+not
+not
+return
+goto L3
+label L3
+";
+    assert_eq!(
+        optimizer::optimize(code, 1),
+        "function Main.f 0
+push constant 4
+pop local 0
+push argument 0
+push argument 1
+gt
+if-goto L2
+label L1
+push constant 0
+return
+label L2
+push constant 0
+return
+goto L3
+label L3
+"
+    );
+    assert_eq!(
+        optimizer::optimize(code, 2),
+        "function Main.f 0
+push constant 4
+pop local 0
+push argument 0
+push argument 1
+gt
+if-goto L2
+push constant 0
+return
+label L2
+push constant 0
+return
+"
+    );
+}
+
+#[test]
+fn test_optimized_source_lines() {
+    let source = "class Main {
+    function int f(int x) {
+        var int y;
+        let y = 2 + 3;
+        if (x > 1) {
+            return y;
+        }
+        return 0;
+    }
+}";
+    let compiled = compile_program_mapped(parser::parse(source).unwrap()).unwrap();
+    assert_eq!(compiled.lines.len(), compiled.vm_code.lines().count());
+    let (optimized, lines) = optimizer::optimize_mapped(&compiled.vm_code, &compiled.lines, 2);
+    let mapped: Vec<(&str, usize)> = optimized.lines().zip(lines).collect();
+    assert_eq!(
+        mapped,
+        vec![
+            ("function Main.f 1", 2),
+            ("push constant 5", 4),
+            ("pop local 0", 4),
+            ("push argument 0", 5),
+            ("push constant 2", 5),
+            ("lt", 5),
+            ("if-goto __VM_LABEL_1", 5),
+            ("push local 0", 6),
+            ("return", 6),
+            ("label __VM_LABEL_1", 5),
+            ("push constant 0", 8),
+            ("return", 8),
+        ]
+    );
+}
+
+#[test]
+fn test_compiled_branches_drop_not() {
+    let source = "class Main {
+    function int f(int x) {
+        while (x < 10) {
+            let x = x + 1;
+        }
+        if (x > -3) {
+            return x;
+        }
+        if (x = 4) {
+            return 0;
+        }
+        return 1;
+    }
+}";
+    let vm_code = compile_program(parser::parse(source).unwrap()).unwrap();
+    let optimized = optimizer::optimize(&vm_code, 1);
+    let branches: Vec<&str> = optimized
+        .lines()
+        .filter(|line| !line.starts_with("push argument") && !line.contains("LABEL"))
+        .collect();
+    assert_eq!(
+        branches,
+        vec![
+            "function Main.f 0",
+            // `x < 10` is false when `x > 9`
+            "push constant 9",
+            "gt",
+            "push constant 1",
+            "add",
+            "pop argument 0",
+            "push constant 2",
+            "neg",
+            "lt",
+            "return",
+            // Can't be inverted without `not`
+            "push constant 4",
+            "eq",
+            "not",
+            "push constant 0",
+            "return",
+            "push constant 1",
+            "return",
+        ]
+    );
+}
@@ -86,7 +86,7 @@ fn test_compiler_output() -> Result<(), Box<dyn std::error::Error>> {
     glob!("inputs/**/*.jack", |path| {
         let result =
             compiler_cli::compile_file(path).map_err(|e| format!("Compiling error:\n{e}", e = e));
-        let CompileResultSuccess { vm_code } = result.unwrap();
+        let CompileResultSuccess { vm_code, .. } = result.unwrap();
         assert_snapshot!(
             format!(
                 "Compiler vm code: {path}",
@@ -107,7 +107,7 @@ fn test_compiler_examples() -> Result<(), Box<dyn std::error::Error>> {
     _macro_support::glob_exec(base_path, "**/*.jack", |path| {
         let result =
             compiler_cli::compile_file(path).map_err(|e| format!("Compiling error:\n{e}", e = e));
-        let CompileResultSuccess { vm_code } = result.unwrap();
+        let CompileResultSuccess { vm_code, .. } = result.unwrap();
         assert_snapshot!(
             format!(
                 "Compiler vm code example: {path}",
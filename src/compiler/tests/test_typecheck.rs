@@ -0,0 +1,68 @@
+use compiler::{
+    checker::Signatures,
+    diagnostic::Severity,
+    parser,
+    typecheck::{self, Type},
+};
+
+fn type_check(source: &str, severity: Severity) -> Vec<String> {
+    let class = parser::parse(source).unwrap().root;
+    let mut signatures = Signatures::with_os().unwrap();
+    signatures.add_class(&class);
+    typecheck::check_class(&class, &signatures, severity)
+        .into_iter()
+        .map(|d| format!("{}: {}:{}", d.severity, d.span.unwrap().line, d.message))
+        .collect()
+}
+
+#[test]
+fn test_well_typed_class() {
+    let source = "class Main {
+  field Array items;
+  method char first(String s) {
+    var boolean b;
+    let b = ~(s.length() = 0) & true;
+    if (b) { let items[0] = s.charAt(0) + 1; }
+    return s.charAt(0);
+  }
+  constructor Main new() {
+    let items = Memory.alloc(1);
+    return this;
+  }
+  function void main() {
+    var Main m;
+    let m = null;
+    while (m = null) { let m = Main.new(); }
+    return;
+  }
+}
+";
+    assert_eq!(type_check(source, Severity::Error), Vec::<String>::new());
+}
+
+#[test]
+fn test_type_errors() {
+    let source = "class Main {
+  function boolean main(int x) {
+    var String s;
+    let s = 1;
+    let x = true + 1;
+    if (x) { do Output.printInt(s); }
+    let x[1] = 0;
+    return x;
+  }
+}
+";
+    assert_eq!(
+        type_check(source, Severity::Warning),
+        vec![
+            "warning: 4:Assignment to s: expected String, got int",
+            "warning: 5:Operator + expects int operands, got boolean and int",
+            "warning: 6:If condition: expected boolean, got int",
+            "warning: 6:Argument 1 of Output.printInt: expected int, got String",
+            "warning: 7:Can't index x of type int",
+            "warning: 8:Return value: expected boolean, got int",
+        ]
+    );
+    assert_eq!(Type::Null.to_string(), "null");
+}
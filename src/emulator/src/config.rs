@@ -0,0 +1,51 @@
+pub const DEFAULT_MAX_CYCLES: u64 = 1_000_000;
+
+pub const USAGE: &str = "Usage: emulator FILE.hack [MAX_CYCLES] [--screen=IMAGE] [--dump=FILE]
+
+Options:
+  --screen=IMAGE  Write the screen after the run, as PPM or PBM if IMAGE ends
+                  with .ppm or .pbm, else PNG
+  --dump=FILE     Write the non-zero RAM words after the run
+  -h, --help      Print this help";
+
+#[derive(Debug)]
+pub struct Config {
+    pub filename: String,
+    pub max_cycles: u64,
+    pub screen: Option<String>,
+    pub dump: Option<String>,
+}
+
+impl Config {
+    pub fn new(args: &[String]) -> Result<Self, Box<dyn std::error::Error>> {
+        let mut positional = vec![];
+        let mut screen = None;
+        let mut dump = None;
+        for arg in args.iter().skip(1) {
+            if let Some(file) = arg.strip_prefix("--screen=") {
+                screen = Some(file.to_string());
+            } else if let Some(file) = arg.strip_prefix("--dump=") {
+                dump = Some(file.to_string());
+            } else if arg.starts_with("--") {
+                return Err(format!("unknown option: {}", arg).into());
+            } else {
+                positional.push(arg);
+            }
+        }
+        if positional.is_empty() {
+            return Err("not enough arguments".into());
+        }
+
+        let filename = positional[0].clone();
+        let max_cycles = match positional.get(1) {
+            Some(cycles) => str::parse::<u64>(cycles).map_err(|_| "invalid cycle limit")?,
+            None => DEFAULT_MAX_CYCLES,
+        };
+        Ok(Config {
+            filename,
+            max_cycles,
+            screen,
+            dump,
+        })
+    }
+}
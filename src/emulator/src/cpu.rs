@@ -0,0 +1,163 @@
+use hasm::instruction::default_symbols;
+
+pub const ROM_SIZE: usize = 0x8000;
+pub const RAM_SIZE: usize = 0x8000;
+
+/// Memory mapped I/O addresses, as defined by the assembler's predefined symbols.
+#[derive(Debug, Clone)]
+pub struct MemoryMap {
+    pub screen: u16,
+    pub keyboard: u16,
+}
+
+impl Default for MemoryMap {
+    fn default() -> Self {
+        let symbols = default_symbols();
+        Self {
+            screen: symbols["SCREEN"],
+            keyboard: symbols["KBD"],
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Cpu {
+    rom: Vec<u16>,
+    ram: Vec<i16>,
+    pub a: i16,
+    pub d: i16,
+    pub pc: u16,
+    pub cycles: u64,
+    pub memory_map: MemoryMap,
+}
+
+impl Cpu {
+    pub fn new(program: &[u16]) -> Self {
+        let mut rom = vec![0u16; ROM_SIZE];
+        rom[..program.len()].copy_from_slice(program);
+        Self {
+            rom,
+            ram: vec![0i16; RAM_SIZE],
+            a: 0,
+            d: 0,
+            pc: 0,
+            cycles: 0,
+            memory_map: Default::default(),
+        }
+    }
+
+    /// Sets registers back to the power-on state, RAM is kept intact.
+    pub fn reset(&mut self) {
+        self.a = 0;
+        self.d = 0;
+        self.pc = 0;
+        self.cycles = 0;
+    }
+
+    pub fn ram(&self) -> &[i16] {
+        &self.ram
+    }
+
+    pub fn ram_mut(&mut self) -> &mut [i16] {
+        &mut self.ram
+    }
+
+    pub fn peek(&self, address: u16) -> i16 {
+        self.ram[address as usize % RAM_SIZE]
+    }
+
+    pub fn poke(&mut self, address: u16, value: i16) {
+        self.ram[address as usize % RAM_SIZE] = value;
+    }
+
+    pub fn screen(&self) -> &[i16] {
+        &self.ram[self.memory_map.screen as usize..self.memory_map.keyboard as usize]
+    }
+
+    pub fn set_key(&mut self, key: i16) {
+        self.poke(self.memory_map.keyboard, key);
+    }
+
+    /// Executes a single instruction.
+    pub fn step(&mut self) {
+        let inst = self.rom[self.pc as usize % ROM_SIZE];
+        self.cycles += 1;
+        if inst & 0x8000 == 0 {
+            self.a = inst as i16;
+            self.pc = self.pc.wrapping_add(1);
+            return;
+        }
+
+        let address = self.a as u16;
+        let y = if inst & 0x1000 != 0 {
+            self.peek(address)
+        } else {
+            self.a
+        };
+        let out = alu(self.d, y, (inst >> 6) & 0b11_1111);
+
+        if inst & 0b001_000 != 0 {
+            self.poke(address, out);
+        }
+        if inst & 0b100_000 != 0 {
+            self.a = out;
+        }
+        if inst & 0b010_000 != 0 {
+            self.d = out;
+        }
+
+        let jump = inst & 0b111;
+        let should_jump = (jump & 0b100 != 0 && out < 0)
+            || (jump & 0b010 != 0 && out == 0)
+            || (jump & 0b001 != 0 && out > 0);
+        self.pc = if should_jump {
+            address
+        } else {
+            self.pc.wrapping_add(1)
+        };
+    }
+
+    /// Whether the CPU spins in `@X` `0;JMP` at address `X`, the assembly of
+    /// a VM `goto` to the label right before it, and will forever.
+    pub fn is_self_jump(&self) -> bool {
+        let start = if self.rom[self.pc as usize % ROM_SIZE] & 0x8000 == 0 {
+            self.pc
+        } else if self.a as u16 == self.pc.wrapping_sub(1) {
+            self.a as u16
+        } else {
+            return false;
+        };
+        let jump = self.rom[start.wrapping_add(1) as usize % ROM_SIZE];
+        // No destination and an unconditional jump
+        self.rom[start as usize % ROM_SIZE] == start
+            && jump & 0xe000 == 0xe000
+            && jump & 0b111_111 == 0b111
+    }
+
+    /// Runs until `max_cycles` instructions were executed, returns the number of executed ones.
+    pub fn run(&mut self, max_cycles: u64) -> u64 {
+        for _ in 0..max_cycles {
+            self.step();
+        }
+        max_cycles
+    }
+}
+
+/// Hack ALU, `control` holds the `zx nx zy ny f no` bits of a C-instruction.
+fn alu(x: i16, y: i16, control: u16) -> i16 {
+    let bit = |n: u16| control & (1 << n) != 0;
+    let mut x = if bit(5) { 0 } else { x };
+    if bit(4) {
+        x = !x;
+    }
+    let mut y = if bit(3) { 0 } else { y };
+    if bit(2) {
+        y = !y;
+    }
+    let out = if bit(1) { x.wrapping_add(y) } else { x & y };
+    if bit(0) {
+        !out
+    } else {
+        out
+    }
+}
@@ -0,0 +1,39 @@
+//! RAM dump files: one `address: value` line per non-zero word, values in decimal.
+//!
+//! Files with bare values, one per line starting from address 0, are read too.
+
+use std::error::Error;
+use std::fmt::Write;
+
+use crate::cpu::RAM_SIZE;
+
+pub fn parse_dump(contents: &str) -> Result<Vec<i16>, Box<dyn Error>> {
+    let mut ram = vec![0i16; RAM_SIZE];
+    let mut next = 0;
+    for (i, line) in contents.lines().enumerate() {
+        let line = line.split("//").next().unwrap_or_default().trim();
+        if line.is_empty() {
+            continue;
+        }
+        let invalid = || format!("Invalid RAM dump line {}: {}", i + 1, line);
+        let (address, value) = match line.split_once(':') {
+            Some((address, value)) => (
+                address.trim().parse::<usize>().map_err(|_| invalid())?,
+                value.trim(),
+            ),
+            None => (next, line),
+        };
+        let value = value.parse::<i16>().map_err(|_| invalid())?;
+        *ram.get_mut(address).ok_or_else(invalid)? = value;
+        next = address + 1;
+    }
+    Ok(ram)
+}
+
+pub fn write_dump(ram: &[i16]) -> String {
+    let mut dump = String::new();
+    for (address, value) in ram.iter().enumerate().filter(|(_, value)| **value != 0) {
+        writeln!(dump, "{}: {}", address, value).unwrap();
+    }
+    dump
+}
@@ -0,0 +1,139 @@
+//! Timed keyboard input, for running interactive programs without a user.
+//!
+//! A key script has one key press per line, holding a key code in `KBD` from a
+//! time to another one, measured in CPU cycles or VM steps depending on the
+//! runner:
+//!
+//! ```text
+//! // from-to key
+//! 1000-5000 RIGHT
+//! 8000-9000 q
+//! 9000-9500 65
+//! 12000- NEWLINE
+//! ```
+//!
+//! The end is excluded and may be left out to hold the key until the end of
+//! the run. A key is a name from [`KEY_NAMES`], a single character, or a
+//! decimal key code, so `7` is the digit and `55` its code. `KBD` is 0 outside
+//! of the presses, which can't overlap.
+
+use std::error::Error;
+
+use crate::cpu::Cpu;
+
+/// Hack key codes of the keys which aren't printable characters.
+pub const KEY_NAMES: [(&str, i16); 15] = [
+    ("NEWLINE", 128),
+    ("ENTER", 128),
+    ("BACKSPACE", 129),
+    ("LEFT", 130),
+    ("UP", 131),
+    ("RIGHT", 132),
+    ("DOWN", 133),
+    ("HOME", 134),
+    ("END", 135),
+    ("PAGEUP", 136),
+    ("PAGEDOWN", 137),
+    ("INSERT", 138),
+    ("DELETE", 139),
+    ("ESC", 140),
+    ("SPACE", 32),
+];
+
+/// Key code of a key name, a single character or a decimal code.
+pub fn key_code(key: &str) -> Option<i16> {
+    let upper = key.to_ascii_uppercase();
+    if let Some((_, code)) = KEY_NAMES.iter().find(|(name, _)| *name == upper) {
+        return Some(*code);
+    }
+    // F1 - F12 are 141 - 152
+    if let Some(n) = upper.strip_prefix('F').and_then(|n| n.parse::<i16>().ok()) {
+        return (1..=12).contains(&n).then_some(140 + n);
+    }
+    let mut chars = key.chars();
+    match (chars.next(), chars.next()) {
+        (Some(ch), None) if ch.is_ascii_graphic() => Some(ch as i16),
+        _ => key.parse().ok(),
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KeyPress {
+    pub key: i16,
+    pub from: u64,
+    /// Excluded, `None` holds the key forever.
+    pub to: Option<u64>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct KeyScript {
+    /// Sorted by time.
+    pub presses: Vec<KeyPress>,
+}
+
+impl KeyScript {
+    pub fn parse(contents: &str) -> Result<Self, Box<dyn Error>> {
+        let mut presses = vec![];
+        for (i, line) in contents.lines().enumerate() {
+            let line = line.split("//").next().unwrap_or_default().trim();
+            if line.is_empty() {
+                continue;
+            }
+            let invalid = || format!("Invalid key script line {}: {}", i + 1, line);
+            let (times, key) = line.split_once(char::is_whitespace).ok_or_else(invalid)?;
+            let (from, to) = times.split_once('-').ok_or_else(invalid)?;
+            let from = from.parse().map_err(|_| invalid())?;
+            let to = match to {
+                "" => None,
+                to => Some(to.parse().map_err(|_| invalid())?),
+            };
+            if to.is_some_and(|to| to <= from) {
+                return Err(format!("Empty key press at line {}: {}", i + 1, line).into());
+            }
+            let key = key_code(key.trim())
+                .ok_or_else(|| format!("Unknown key at line {}: {}", i + 1, key.trim()))?;
+            presses.push(KeyPress { key, from, to });
+        }
+        presses.sort_by_key(|press| press.from);
+        for pair in presses.windows(2) {
+            if pair[0].to.is_none_or(|to| to > pair[1].from) {
+                return Err(format!(
+                    "Key presses overlap at {}: {} and {}",
+                    pair[1].from, pair[0].key, pair[1].key
+                )
+                .into());
+            }
+        }
+        Ok(Self { presses })
+    }
+
+    /// Time from which the key held doesn't change anymore.
+    pub fn end(&self) -> u64 {
+        self.presses
+            .iter()
+            .map(|press| press.to.unwrap_or(press.from))
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// Key code held at a time, 0 if none.
+    pub fn key_at(&self, time: u64) -> i16 {
+        let i = self.presses.partition_point(|press| press.from <= time);
+        match i.checked_sub(1).map(|i| self.presses[i]) {
+            Some(press) if press.to.is_none_or(|to| time < to) => press.key,
+            _ => 0,
+        }
+    }
+}
+
+impl Cpu {
+    /// Runs like [`Cpu::run`], with `KBD` holding the key of the script at the
+    /// current cycle before each instruction.
+    pub fn run_with_keys(&mut self, keys: &KeyScript, max_cycles: u64) -> u64 {
+        for _ in 0..max_cycles {
+            self.set_key(keys.key_at(self.cycles));
+            self.step();
+        }
+        max_cycles
+    }
+}
@@ -0,0 +1,39 @@
+pub mod config;
+pub mod cpu;
+pub mod dump;
+pub mod keyboard;
+pub mod rom;
+pub mod screen;
+
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+
+use config::Config;
+use cpu::Cpu;
+use screen::Screen;
+
+pub fn load_file(filename: &str) -> Result<Cpu, Box<dyn Error>> {
+    let contents = fs::read_to_string(filename)?;
+    let program = rom::parse_hack(&contents)?;
+    Ok(Cpu::new(&program))
+}
+
+pub fn run(config: Config) -> Result<(), Box<dyn Error>> {
+    let mut cpu = load_file(&config.filename)?;
+    cpu.run(config.max_cycles);
+    println!(
+        "Cycles: {}\nPC: {}, A: {}, D: {}",
+        cpu.cycles, cpu.pc, cpu.a, cpu.d
+    );
+    for (address, value) in cpu.ram().iter().take(16).enumerate() {
+        println!("RAM[{}]: {}", address, value);
+    }
+    if let Some(path) = &config.dump {
+        fs::write(path, dump::write_dump(cpu.ram()))?;
+    }
+    if let Some(path) = &config.screen {
+        Screen::from_cpu(&cpu).save(Path::new(path))?;
+    }
+    Ok(())
+}
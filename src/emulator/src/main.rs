@@ -0,0 +1,20 @@
+use std::env;
+
+use emulator::config::{Config, USAGE};
+
+fn main() {
+    let args: Vec<String> = env::args().collect();
+    if args.iter().any(|arg| arg == "-h" || arg == "--help") {
+        println!("{}", USAGE);
+        return;
+    }
+    let config = Config::new(&args).unwrap_or_else(|err| {
+        println!("Problem parsing arguments: {}\n\n{}", err, USAGE);
+        std::process::exit(1);
+    });
+
+    if let Err(e) = emulator::run(config) {
+        println!("Application error: {}", e);
+        std::process::exit(1);
+    }
+}
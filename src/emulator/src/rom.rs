@@ -0,0 +1,32 @@
+use std::error::Error;
+
+use crate::cpu::ROM_SIZE;
+
+/// Parses the textual `.hack` format: one 16 character binary word per line.
+pub fn parse_hack(contents: &str) -> Result<Vec<u16>, Box<dyn Error>> {
+    let mut program = Vec::<u16>::new();
+    for (line_index, line) in contents.lines().enumerate() {
+        let word = line.trim();
+        if word.is_empty() {
+            continue;
+        }
+        if word.len() != 16 || !word.chars().all(|ch| ch == '0' || ch == '1') {
+            return Err(format!(
+                "Invalid instruction at line {}: {:?}",
+                line_index + 1,
+                word
+            )
+            .into());
+        }
+        program.push(u16::from_str_radix(word, 2)?);
+    }
+    if program.len() > ROM_SIZE {
+        return Err(format!(
+            "Program doesn't fit in ROM: {} instructions, max is {}",
+            program.len(),
+            ROM_SIZE
+        )
+        .into());
+    }
+    Ok(program)
+}
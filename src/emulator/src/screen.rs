@@ -0,0 +1,257 @@
+//! Renders the memory mapped screen to PNG, PPM or PBM images, and compares screens.
+
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+use crate::cpu::{Cpu, MemoryMap};
+
+pub const WIDTH: usize = 512;
+pub const HEIGHT: usize = 256;
+/// Each word holds 16 pixels of a row, the lowest bit is the leftmost one.
+pub const WORDS_PER_ROW: usize = WIDTH / 16;
+pub const SCREEN_WORDS: usize = WORDS_PER_ROW * HEIGHT;
+
+/// Black and white pixels of the 512x256 screen.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Screen {
+    words: Vec<i16>,
+}
+
+/// Pixels which differ between two screens.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScreenDiff {
+    pub pixels: usize,
+    /// Smallest rectangle containing them, as `(x1, y1, x2, y2)` inclusive.
+    pub bounds: (usize, usize, usize, usize),
+}
+
+impl fmt::Display for ScreenDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (x1, y1, x2, y2) = self.bounds;
+        write!(
+            f,
+            "{} pixel(s) differ, within ({}, {})-({}, {})",
+            self.pixels, x1, y1, x2, y2
+        )
+    }
+}
+
+impl Default for Screen {
+    fn default() -> Self {
+        Self {
+            words: vec![0; SCREEN_WORDS],
+        }
+    }
+}
+
+impl Screen {
+    /// Screen of a RAM image, at the `SCREEN` address of the memory map.
+    pub fn from_ram(ram: &[i16]) -> Result<Self, Box<dyn Error>> {
+        let start = MemoryMap::default().screen as usize;
+        let words = ram
+            .get(start..start + SCREEN_WORDS)
+            .ok_or("RAM image doesn't contain the screen")?;
+        Ok(Self {
+            words: words.to_vec(),
+        })
+    }
+
+    pub fn from_cpu(cpu: &Cpu) -> Self {
+        Self {
+            words: cpu.screen()[..SCREEN_WORDS].to_vec(),
+        }
+    }
+
+    pub fn pixel(&self, x: usize, y: usize) -> bool {
+        let word = self.words[y * WORDS_PER_ROW + x / 16];
+        word as u16 >> (x % 16) & 1 == 1
+    }
+
+    pub fn set_pixel(&mut self, x: usize, y: usize, black: bool) {
+        let word = &mut self.words[y * WORDS_PER_ROW + x / 16];
+        let bit = 1 << (x % 16);
+        *word = if black { *word | bit } else { *word & !bit };
+    }
+
+    pub fn diff(&self, other: &Screen) -> Option<ScreenDiff> {
+        let mut diff: Option<ScreenDiff> = None;
+        for y in 0..HEIGHT {
+            for x in (0..WIDTH).filter(|x| self.pixel(*x, y) != other.pixel(*x, y)) {
+                let diff = diff.get_or_insert(ScreenDiff {
+                    pixels: 0,
+                    bounds: (x, y, x, y),
+                });
+                let (x1, y1, x2, y2) = diff.bounds;
+                diff.pixels += 1;
+                diff.bounds = (x1.min(x), y1.min(y), x2.max(x), y2.max(y));
+            }
+        }
+        diff
+    }
+
+    /// Binary PPM (`P6`) image.
+    pub fn to_ppm(&self) -> Vec<u8> {
+        let mut ppm = format!("P6\n{} {}\n255\n", WIDTH, HEIGHT).into_bytes();
+        for y in 0..HEIGHT {
+            for x in 0..WIDTH {
+                let value = if self.pixel(x, y) { 0 } else { 255 };
+                ppm.extend([value; 3]);
+            }
+        }
+        ppm
+    }
+
+    /// Reads a `P6` image of the screen size, dark pixels being black.
+    pub fn from_ppm(ppm: &[u8]) -> Result<Self, Box<dyn Error>> {
+        let start = check_header(ppm, &["P6", &WIDTH.to_string(), &HEIGHT.to_string(), "255"])?;
+        let pixels = ppm
+            .get(start..start + WIDTH * HEIGHT * 3)
+            .ok_or("Truncated PPM image")?;
+        let mut screen = Screen::default();
+        for (i, rgb) in pixels.chunks(3).enumerate() {
+            let brightness: u32 = rgb.iter().map(|c| *c as u32).sum();
+            screen.set_pixel(i % WIDTH, i / WIDTH, brightness < 3 * 128);
+        }
+        Ok(screen)
+    }
+
+    /// Binary PBM (`P4`) image, 1 bit per pixel.
+    pub fn to_pbm(&self) -> Vec<u8> {
+        let mut pbm = format!("P4\n{} {}\n", WIDTH, HEIGHT).into_bytes();
+        for word in &self.words {
+            // PBM has the leftmost pixel in the highest bit, and 1 for black
+            pbm.extend((*word as u16).reverse_bits().to_be_bytes());
+        }
+        pbm
+    }
+
+    /// Reads a `P4` image of the screen size.
+    pub fn from_pbm(pbm: &[u8]) -> Result<Self, Box<dyn Error>> {
+        let start = check_header(pbm, &["P4", &WIDTH.to_string(), &HEIGHT.to_string()])?;
+        let words = pbm
+            .get(start..start + SCREEN_WORDS * 2)
+            .ok_or("Truncated PBM image")?
+            .chunks(2)
+            .map(|bytes| u16::from_be_bytes([bytes[0], bytes[1]]).reverse_bits() as i16)
+            .collect();
+        Ok(Self { words })
+    }
+
+    /// 1 bit grayscale PNG image, stored without compression.
+    pub fn to_png(&self) -> Vec<u8> {
+        let mut data = Vec::with_capacity(HEIGHT * (WIDTH / 8 + 1));
+        for row in self.words.chunks(WORDS_PER_ROW) {
+            // No filter
+            data.push(0);
+            for word in row {
+                // PNG has the leftmost pixel in the highest bit, and 0 for black
+                let bits = !(*word as u16).reverse_bits();
+                data.extend(bits.to_be_bytes());
+            }
+        }
+
+        let mut header = vec![];
+        header.extend((WIDTH as u32).to_be_bytes());
+        header.extend((HEIGHT as u32).to_be_bytes());
+        // Bit depth 1, grayscale, deflate, no filter, no interlace
+        header.extend([1, 0, 0, 0, 0]);
+
+        let mut png = b"\x89PNG\r\n\x1a\n".to_vec();
+        write_chunk(&mut png, b"IHDR", &header);
+        write_chunk(&mut png, b"IDAT", &zlib_stored(&data));
+        write_chunk(&mut png, b"IEND", &[]);
+        png
+    }
+
+    /// Writes a PNG image, or a PPM or PBM one when the file name ends with
+    /// `.ppm` or `.pbm`.
+    pub fn save(&self, path: &Path) -> Result<(), Box<dyn Error>> {
+        let image = match path.extension().and_then(|ext| ext.to_str()) {
+            Some("ppm") => self.to_ppm(),
+            Some("pbm") => self.to_pbm(),
+            _ => self.to_png(),
+        };
+        fs::write(path, image)?;
+        Ok(())
+    }
+}
+
+/// Checks the header fields of a binary PPM or PBM image, returning where
+/// its pixels start.
+fn check_header(image: &[u8], expected: &[&str]) -> Result<usize, Box<dyn Error>> {
+    // Header fields are separated by whitespace, followed by one whitespace byte
+    let mut fields = vec![];
+    let mut start = 0;
+    while fields.len() < expected.len() {
+        let rest = image.get(start..).ok_or("Truncated image header")?;
+        let skip = rest.iter().take_while(|b| b.is_ascii_whitespace()).count();
+        let len = rest[skip..]
+            .iter()
+            .take_while(|b| !b.is_ascii_whitespace())
+            .count();
+        fields.push(String::from_utf8_lossy(&rest[skip..skip + len]).into_owned());
+        start += skip + len;
+    }
+    if fields != expected {
+        return Err(format!(
+            "Expected a {}x{} {} image: {:?}",
+            WIDTH, HEIGHT, expected[0], fields
+        )
+        .into());
+    }
+    Ok(start + 1)
+}
+
+fn write_chunk(png: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    png.extend((data.len() as u32).to_be_bytes());
+    let start = png.len();
+    png.extend(kind);
+    png.extend(data);
+    let crc = crc32(&png[start..]);
+    png.extend(crc.to_be_bytes());
+}
+
+/// zlib stream made of uncompressed deflate blocks.
+fn zlib_stored(data: &[u8]) -> Vec<u8> {
+    let mut out = vec![0x78, 0x01];
+    let mut blocks = data.chunks(0xffff).peekable();
+    if blocks.peek().is_none() {
+        out.extend([1, 0, 0, 0xff, 0xff]);
+    }
+    while let Some(block) = blocks.next() {
+        let is_final = blocks.peek().is_none();
+        out.push(is_final as u8);
+        let len = block.len() as u16;
+        out.extend(len.to_le_bytes());
+        out.extend((!len).to_le_bytes());
+        out.extend(block);
+    }
+    out.extend(adler32(data).to_be_bytes());
+    out
+}
+
+pub fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for byte in data {
+        crc ^= *byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 == 1 {
+                0xedb8_8320 ^ (crc >> 1)
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
+
+pub fn adler32(data: &[u8]) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+    for byte in data {
+        a = (a + *byte as u32) % 65521;
+        b = (b + a) % 65521;
+    }
+    (b << 16) | a
+}
@@ -0,0 +1,31 @@
+use emulator::config::{Config, DEFAULT_MAX_CYCLES};
+
+fn config(args: &[&str]) -> Result<Config, Box<dyn std::error::Error>> {
+    let args: Vec<String> = ["emulator"]
+        .iter()
+        .chain(args)
+        .map(|s| s.to_string())
+        .collect();
+    Config::new(&args)
+}
+
+#[test]
+fn test_options() {
+    let config = config(&["Pong.hack", "--screen=pong.png", "500", "--dump=ram.txt"]).unwrap();
+    assert_eq!(config.filename, "Pong.hack");
+    assert_eq!(config.max_cycles, 500);
+    assert_eq!(config.screen.as_deref(), Some("pong.png"));
+    assert_eq!(config.dump.as_deref(), Some("ram.txt"));
+
+    let config = self::config(&["Pong.hack"]).unwrap();
+    assert_eq!(config.max_cycles, DEFAULT_MAX_CYCLES);
+    assert_eq!(config.screen, None);
+}
+
+#[test]
+fn test_invalid_options() {
+    assert!(config(&[]).is_err());
+    assert!(config(&["Pong.hack", "lots"]).is_err());
+    let err = config(&["Pong.hack", "--screen", "pong.png"]).unwrap_err();
+    assert_eq!(err.to_string(), "unknown option: --screen");
+}
@@ -0,0 +1,120 @@
+use emulator::{cpu::Cpu, rom};
+use hasm::{code, parser};
+
+fn assemble(asm: &str) -> Cpu {
+    let hack = code::generate_code(parser::parse(asm.to_string()).unwrap());
+    Cpu::new(&rom::parse_hack(&hack).unwrap())
+}
+
+#[test]
+fn test_add() {
+    let mut cpu = assemble(
+        "
+        @R0
+        D=M
+        @R1
+        D=D+M
+        @R2
+        M=D
+        (END)
+        @END
+        0;JMP
+        ",
+    );
+    cpu.poke(0, 1234);
+    cpu.poke(1, -34);
+    cpu.run(100);
+    assert_eq!(cpu.peek(2), 1200);
+    assert_eq!(cpu.pc, 6);
+}
+
+#[test]
+fn test_loop_and_variables() {
+    // sum = 1 + 2 + ... + 100
+    let mut cpu = assemble(
+        "
+        @i
+        M=1
+        @sum
+        M=0
+        (LOOP)
+        @i
+        D=M
+        @100
+        D=D-A
+        @STOP
+        D;JGT
+        @i
+        D=M
+        @sum
+        M=D+M
+        @i
+        M=M+1
+        @LOOP
+        0;JMP
+        (STOP)
+        @sum
+        D=M
+        @R0
+        M=D
+        (END)
+        @END
+        0;JMP
+        ",
+    );
+    cpu.run(10_000);
+    assert_eq!(cpu.peek(0), 5050);
+    assert_eq!(cpu.peek(16), 101);
+}
+
+#[test]
+fn test_memory_map() {
+    let mut cpu = assemble(
+        "
+        @KBD
+        D=M
+        @SCREEN
+        M=D
+        @SCREEN
+        A=A+1
+        M=-1
+        ",
+    );
+    cpu.set_key(65);
+    cpu.run(7);
+    assert_eq!(cpu.screen()[0], 65);
+    assert_eq!(cpu.screen()[1], -1);
+    assert_eq!(cpu.screen().len(), 8192);
+}
+
+#[test]
+fn test_invalid_hack() {
+    assert!(rom::parse_hack("0000000000000001\n000000000000002\n").is_err());
+}
+
+#[test]
+fn test_self_jump() {
+    let mut cpu = assemble(
+        "
+        @3
+        D=A
+        (LOOP)
+        D=D-1
+        @LOOP
+        D;JGT
+        (END)
+        @END
+        0;JMP
+        ",
+    );
+    // The backward jump of the countdown isn't one
+    while cpu.pc != 5 {
+        assert!(!cpu.is_self_jump());
+        cpu.step();
+    }
+    for _ in 0..4 {
+        assert!(cpu.is_self_jump());
+        cpu.step();
+    }
+    assert_eq!(cpu.pc, 5);
+}
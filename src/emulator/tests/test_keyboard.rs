@@ -0,0 +1,89 @@
+use emulator::{
+    cpu::Cpu,
+    keyboard::{key_code, KeyPress, KeyScript},
+    rom,
+};
+use hasm::{code, parser};
+
+#[test]
+fn test_key_codes() {
+    assert_eq!(key_code("a"), Some(97));
+    assert_eq!(key_code("Q"), Some(81));
+    assert_eq!(key_code("7"), Some(55));
+    assert_eq!(key_code("55"), Some(55));
+    assert_eq!(key_code("enter"), Some(128));
+    assert_eq!(key_code("NEWLINE"), Some(128));
+    assert_eq!(key_code("UP"), Some(131));
+    assert_eq!(key_code("ESC"), Some(140));
+    assert_eq!(key_code("F1"), Some(141));
+    assert_eq!(key_code("F12"), Some(152));
+    assert_eq!(key_code("F13"), None);
+    assert_eq!(key_code("SHIFT"), None);
+}
+
+#[test]
+fn test_key_script() {
+    let script = KeyScript::parse(
+        "
+        // arrows
+        10-20 RIGHT
+        20-25 DOWN // right after
+        100- q
+        ",
+    )
+    .unwrap();
+    assert_eq!(
+        script.presses[2],
+        KeyPress {
+            key: 113,
+            from: 100,
+            to: None
+        }
+    );
+    let keys: Vec<i16> = [0, 9, 10, 19, 20, 24, 25, 99, 100, 1_000_000]
+        .iter()
+        .map(|time| script.key_at(*time))
+        .collect();
+    assert_eq!(keys, [0, 0, 132, 132, 133, 133, 0, 0, 113, 113]);
+    assert_eq!(script.end(), 100);
+    assert_eq!(KeyScript::default().end(), 0);
+}
+
+#[test]
+fn test_invalid_key_script() {
+    let error = |script: &str| KeyScript::parse(script).unwrap_err().to_string();
+    assert_eq!(error("10-20 SHIFT"), "Unknown key at line 1: SHIFT");
+    assert_eq!(error("\n20-10 a"), "Empty key press at line 2: 20-10 a");
+    assert_eq!(error("10 a"), "Invalid key script line 1: 10 a");
+    assert_eq!(error("10-x a"), "Invalid key script line 1: 10-x a");
+    assert_eq!(
+        error("15- b\n10-20 a"),
+        "Key presses overlap at 15: 97 and 98"
+    );
+}
+
+#[test]
+fn test_run_with_keys() {
+    // Waits for a key and stores it in R0
+    let asm = "
+        (WAIT)
+        @KBD
+        D=M
+        @WAIT
+        D;JEQ
+        @R0
+        M=D
+        (END)
+        @END
+        0;JMP
+        ";
+    let hack = code::generate_code(parser::parse(asm.to_string()).unwrap());
+    let mut cpu = Cpu::new(&rom::parse_hack(&hack).unwrap());
+    let script = KeyScript::parse("30-40 LEFT").unwrap();
+    cpu.run_with_keys(&script, 30);
+    assert_eq!(cpu.peek(0), 0);
+    cpu.run_with_keys(&script, 10);
+    assert_eq!(cpu.peek(0), 130);
+    cpu.run_with_keys(&script, 10);
+    assert_eq!(cpu.peek(cpu.memory_map.keyboard), 0);
+}
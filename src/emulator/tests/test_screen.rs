@@ -0,0 +1,164 @@
+use emulator::{
+    cpu::Cpu,
+    dump, rom,
+    screen::{self, Screen, ScreenDiff, HEIGHT, WIDTH},
+};
+use hasm::{code, parser};
+
+fn run(asm: &str) -> Cpu {
+    let hack = code::generate_code(parser::parse(asm.to_string()).unwrap());
+    let mut cpu = Cpu::new(&rom::parse_hack(&hack).unwrap());
+    cpu.run(1000);
+    cpu
+}
+
+/// Sets the leftmost pixel of the first row, and the rightmost one of the last row.
+fn draw_corners() -> Screen {
+    let cpu = run("
+        @SCREEN
+        M=1
+        @32767
+        D=!A
+        @24575
+        M=D
+        ");
+    Screen::from_cpu(&cpu)
+}
+
+/// Chunks of a PNG file, checking their CRC.
+fn png_chunks(png: &[u8]) -> Vec<(String, Vec<u8>)> {
+    assert_eq!(&png[..8], b"\x89PNG\r\n\x1a\n");
+    let mut chunks = vec![];
+    let mut rest = &png[8..];
+    while !rest.is_empty() {
+        let len = u32::from_be_bytes(rest[..4].try_into().unwrap()) as usize;
+        let crc = u32::from_be_bytes(rest[8 + len..12 + len].try_into().unwrap());
+        assert_eq!(screen::crc32(&rest[4..8 + len]), crc);
+        let kind = String::from_utf8(rest[4..8].to_vec()).unwrap();
+        chunks.push((kind, rest[8..8 + len].to_vec()));
+        rest = &rest[12 + len..];
+    }
+    chunks
+}
+
+/// Contents of a zlib stream made of stored blocks.
+fn inflate_stored(zlib: &[u8]) -> Vec<u8> {
+    let mut data = vec![];
+    let mut rest = &zlib[2..];
+    loop {
+        let is_final = rest[0] & 1 == 1;
+        assert_eq!(rest[0] >> 1, 0, "Not a stored block");
+        let len = u16::from_le_bytes([rest[1], rest[2]]) as usize;
+        data.extend(&rest[5..5 + len]);
+        rest = &rest[5 + len..];
+        if is_final {
+            break;
+        }
+    }
+    assert_eq!(rest, screen::adler32(&data).to_be_bytes());
+    data
+}
+
+#[test]
+fn test_pixels() {
+    let screen = draw_corners();
+    assert!(screen.pixel(0, 0));
+    assert!(!screen.pixel(1, 0));
+    assert!(screen.pixel(WIDTH - 1, HEIGHT - 1));
+    assert!(!screen.pixel(WIDTH - 2, HEIGHT - 1));
+    assert!(!screen.pixel(WIDTH - 1, HEIGHT - 2));
+}
+
+#[test]
+fn test_png() {
+    let png = draw_corners().to_png();
+    let chunks = png_chunks(&png);
+    let kinds = chunks
+        .iter()
+        .map(|(kind, _)| kind.as_str())
+        .collect::<Vec<_>>();
+    assert_eq!(kinds, ["IHDR", "IDAT", "IEND"]);
+    assert_eq!(
+        chunks[0].1,
+        [0, 0, 2, 0, 0, 0, 1, 0, 1, 0, 0, 0, 0],
+        "512x256, 1 bit grayscale"
+    );
+
+    let rows = inflate_stored(&chunks[1].1);
+    let row_len = 1 + WIDTH / 8;
+    assert_eq!(rows.len(), HEIGHT * row_len);
+    let first = &rows[..row_len];
+    assert_eq!(first[0], 0, "No filter");
+    assert_eq!(first[1], 0x7f, "Leftmost pixel is black");
+    assert!(first[2..].iter().all(|b| *b == 0xff));
+    let last = &rows[rows.len() - row_len..];
+    assert_eq!(last[row_len - 1], 0xfe, "Rightmost pixel is black");
+}
+
+#[test]
+fn test_ppm() {
+    let screen = draw_corners();
+    let ppm = screen.to_ppm();
+    assert!(ppm.starts_with(b"P6\n512 256\n255\n"));
+    assert_eq!(Screen::from_ppm(&ppm).unwrap(), screen);
+
+    assert!(Screen::from_ppm(b"P6\n16 16\n255\n").is_err());
+    assert!(Screen::from_ppm(&ppm[..ppm.len() - 1]).is_err());
+}
+
+#[test]
+fn test_pbm() {
+    let screen = draw_corners();
+    let pbm = screen.to_pbm();
+    assert_eq!(pbm.len(), 11 + 512 * 256 / 8);
+    assert!(pbm.starts_with(b"P4\n512 256\n\x80"));
+    assert!(pbm.ends_with(b"\x01"));
+    assert_eq!(Screen::from_pbm(&pbm).unwrap(), screen);
+
+    assert!(Screen::from_pbm(&screen.to_ppm()).is_err());
+    assert!(Screen::from_pbm(&pbm[..pbm.len() - 1]).is_err());
+}
+
+#[test]
+fn test_diff() {
+    let screen = draw_corners();
+    assert_eq!(screen.diff(&screen.clone()), None);
+
+    let mut other = screen.clone();
+    other.set_pixel(10, 20, true);
+    other.set_pixel(WIDTH - 1, HEIGHT - 1, false);
+    let diff = screen.diff(&other).unwrap();
+    assert_eq!(
+        diff,
+        ScreenDiff {
+            pixels: 2,
+            bounds: (10, 20, WIDTH - 1, HEIGHT - 1)
+        }
+    );
+    assert_eq!(
+        diff.to_string(),
+        "2 pixel(s) differ, within (10, 20)-(511, 255)"
+    );
+}
+
+#[test]
+fn test_dump() {
+    let cpu = run("
+        @1234
+        D=A
+        @R3
+        M=D
+        @SCREEN
+        M=-1
+        ");
+    let text = dump::write_dump(cpu.ram());
+    assert_eq!(text, "3: 1234\n16384: -1\n");
+    let ram = dump::parse_dump(&text).unwrap();
+    assert_eq!(ram, cpu.ram());
+    assert_eq!(Screen::from_ram(&ram).unwrap(), Screen::from_cpu(&cpu));
+
+    let ram = dump::parse_dump("1\n2 // R1\n\n5: -3\n4\n").unwrap();
+    assert_eq!(ram[..7], [1, 2, 0, 0, 0, -3, 4]);
+    assert!(dump::parse_dump("1: x").is_err());
+    assert!(dump::parse_dump("40000: 1").is_err());
+}
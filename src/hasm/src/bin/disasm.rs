@@ -0,0 +1,20 @@
+use std::env;
+
+use hasm::config::{Config, DISASM_USAGE};
+
+fn main() {
+    let args: Vec<String> = env::args().collect();
+    let config = Config::new(&args).unwrap_or_else(|err| {
+        println!("Problem parsing arguments: {}\n\n{}", err, DISASM_USAGE);
+        std::process::exit(1);
+    });
+    if config.help {
+        print!("{}", DISASM_USAGE);
+        return;
+    }
+
+    if let Err(e) = hasm::run_disassembler(config) {
+        println!("Application error: {}", e);
+        std::process::exit(1);
+    }
+}
@@ -1,31 +1,62 @@
 use crate::instruction::*;
 use crate::parser::ParseResult;
+use crate::source_map::{Location, SourceMap};
 
 pub fn generate_code(parse_result: ParseResult) -> String {
     parse_result
         .commands
-        .into_iter()
-        .filter_map(|com| generate(com.inst))
+        .iter()
+        .map(|com| generate(&com.inst) + "\n")
         .collect()
 }
 
-fn generate(inst: Instruction) -> Option<String> {
-    // println!("generate: {:?}", inst);
-    Some(
-        match inst {
-            Instruction::AInstruction {
-                address: AInstAddress::Address(address),
-            } => format!("0{:015b}", address),
-            Instruction::CInstruction { comp, dest, jump } => format!(
-                "111{comp}{dest:03b}{jump:03b}",
-                comp = comp.fmt_binary(),
-                dest = dest,
-                jump = jump
-            ),
-            _ => {
-                println!("Unknown code instruction: {:?}", inst);
-                return None;
-            }
-        } + "\n",
-    )
+/// Listing with the ROM address, binary word and source of every instruction,
+/// labels on their own lines before the instruction they point to.
+pub fn generate_listing(parse_result: &ParseResult) -> String {
+    let mut listing = String::new();
+    let words: Vec<_> = parse_result
+        .commands
+        .iter()
+        .map(|com| (generate(&com.inst), &com.raw))
+        .collect();
+    let list_labels = |listing: &mut String, address: usize| {
+        for label in parse_result.symbols.labels_at(address as u16) {
+            *listing += &format!("{:>5}  {:16}  ({})\n", "", "", label);
+        }
+    };
+    for (address, (word, raw)) in words.iter().enumerate() {
+        list_labels(&mut listing, address);
+        listing += &format!("{:>5}  {}  {}\n", address, word, raw);
+    }
+    list_labels(&mut listing, words.len());
+    listing
+}
+
+/// Source line of every ROM address, `file` naming the main source file.
+pub fn generate_source_map(parse_result: &ParseResult, file: &str) -> SourceMap {
+    let mut map = SourceMap::new();
+    for (address, command) in parse_result.commands.iter().enumerate() {
+        let location = Location {
+            file: command.file.as_deref().unwrap_or(file).to_string(),
+            line: command.line,
+        };
+        map.insert(address + 1, location);
+    }
+    map
+}
+
+fn generate(inst: &Instruction) -> String {
+    match inst {
+        Instruction::AInstruction {
+            address: AInstAddress::Address(address),
+        } => format!("0{:015b}", address),
+        Instruction::CInstruction { comp, dest, jump } => format!(
+            "111{comp}{dest:03b}{jump:03b}",
+            comp = comp.fmt_binary(),
+            dest = dest,
+            jump = jump
+        ),
+        // `Parser::parse_input` resolves every symbol and leaves out labels
+        _ => unreachable!("unresolved instruction: {:?}", inst),
+    }
 }
@@ -1,14 +1,116 @@
+use std::error::Error;
+
+/// Path standing for stdin as the input, or stdout as the output.
+pub const STDIO: &str = "-";
+
+pub const USAGE: &str = "\
+Usage: hasm [options] <file.asm | ->
+
+Assembles Hack assembly into <file>.hack. Reading from stdin (`-`) writes to stdout.
+
+Options:
+  -o, --output <path>  Output file, `-` for stdout
+      --emit-symbols   Also write the labels and variables to <output>.sym
+      --listing        Also write a listing of addresses, words and source to <output>.lst
+      --source-map     Also write the source line of every address to <output>.map
+      --check          Only validate the input, don't write anything
+  -v, --verbose        Report what was written
+  -q, --quiet          Only report errors
+  -h, --help           Print this help
+";
+
+pub const DISASM_USAGE: &str = "\
+Usage: disasm [options] <file.hack | ->
+
+Disassembles Hack machine code, writing to stdout.
+
+Options:
+  -o, --output <path>   Output file
+  -s, --symbols <path>  Symbol map written by the assembler, to restore names
+      --check           Only validate the input, don't write anything
+  -h, --help            Print this help
+";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default)]
+pub enum Verbosity {
+    Quiet,
+    #[default]
+    Normal,
+    Verbose,
+}
+
+#[derive(Debug, Default)]
 pub struct Config {
+    /// Source file, `STDIO` for stdin.
     pub filename: String,
+    /// Output file, `STDIO` for stdout. Defaults to the source with a `.hack` extension.
+    pub output: Option<String>,
+    /// Symbol map for the disassembler.
+    pub symbols: Option<String>,
+    pub emit_symbols: bool,
+    pub listing: bool,
+    pub source_map: bool,
+    pub check: bool,
+    pub verbosity: Verbosity,
+    pub help: bool,
 }
 
 impl Config {
-    pub fn new(args: &[String]) -> Result<Self, &'static str> {
-        if args.len() < 2 {
-            return Err("not enough arguments");
+    pub fn new(args: &[String]) -> Result<Self, Box<dyn Error>> {
+        let mut config = Config::default();
+        let mut filename = None;
+        let mut args = args.iter().skip(1);
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "-h" | "--help" => config.help = true,
+                "-o" | "--output" => {
+                    config.output = Some(args.next().ok_or("missing output path")?.clone())
+                }
+                "-s" | "--symbols" => {
+                    config.symbols = Some(args.next().ok_or("missing symbol map path")?.clone())
+                }
+                "--emit-symbols" => config.emit_symbols = true,
+                "--listing" => config.listing = true,
+                "--source-map" => config.source_map = true,
+                "--check" => config.check = true,
+                "-v" | "--verbose" => config.verbosity = Verbosity::Verbose,
+                "-q" | "--quiet" => config.verbosity = Verbosity::Quiet,
+                _ if arg.starts_with('-') && arg != STDIO => {
+                    return Err(format!("unknown option: {}", arg).into())
+                }
+                _ if filename.is_none() => filename = Some(arg.clone()),
+                _ => return Err(format!("unexpected argument: {}", arg).into()),
+            }
         }
+        if !config.help {
+            config.filename = filename.ok_or("not enough arguments")?;
+        }
+        Ok(config)
+    }
 
-        let filename = args[1].clone();
-        Ok(Config { filename })
+    /// Path of an extra output file, next to the output or the source when
+    /// writing to stdout.
+    pub fn side_target(&self, extension: &str) -> Result<String, Box<dyn Error>> {
+        let base = match self.target() {
+            target if target != STDIO => target,
+            _ if self.filename != STDIO => self.filename.clone(),
+            _ => return Err(format!("writing a .{} file needs an output path", extension).into()),
+        };
+        let mut target = std::path::PathBuf::from(base);
+        target.set_extension(extension);
+        Ok(target.to_string_lossy().into_owned())
+    }
+
+    /// Where to write the output, `STDIO` for stdout.
+    pub fn target(&self) -> String {
+        match &self.output {
+            Some(output) => output.clone(),
+            None if self.filename == STDIO => STDIO.into(),
+            None => {
+                let mut target = std::path::PathBuf::from(&self.filename);
+                target.set_extension("hack");
+                target.to_string_lossy().into_owned()
+            }
+        }
     }
 }
@@ -0,0 +1,97 @@
+use crate::error::{AsmError, AsmErrors, ErrorKind};
+use crate::instruction::*;
+use crate::symbols::SymbolMap;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Word {
+    A(u16),
+    C(Comp, Dest, Jump),
+}
+
+fn decode(word: u16) -> Option<Word> {
+    if word & 0x8000 == 0 {
+        return Some(Word::A(word));
+    }
+    if word & 0xe000 != 0xe000 {
+        return None;
+    }
+    Some(Word::C(
+        Comp::from_bits(word >> 6)?,
+        Dest::from_bits(word >> 3),
+        Jump::from_bits(word),
+    ))
+}
+
+fn format_c_instruction(comp: &Comp, dest: &Dest, jump: &Jump) -> String {
+    let mut asm = String::new();
+    if *dest != Dest::Null {
+        asm += dest.mnemonic();
+        asm += "=";
+    }
+    asm += comp.mnemonic();
+    if *jump != Jump::Null {
+        asm += ";";
+        asm += jump.mnemonic();
+    }
+    asm
+}
+
+/// Name for the value of an A-instruction: a label when the next instruction
+/// jumps, a variable otherwise, falling back to the other kind.
+fn symbol_for(value: u16, next: Option<&Word>, symbols: &SymbolMap) -> Option<String> {
+    let jumps = matches!(next, Some(Word::C(_, _, jump)) if *jump != Jump::Null);
+    let (label, variable) = (symbols.label_at(value), symbols.variable_at(value));
+    let name = if jumps {
+        label.or(variable)
+    } else {
+        variable.or(label)
+    };
+    name.map(String::from)
+}
+
+/// Decodes `.hack` words, one per line, back to assembly. With a symbol map,
+/// labels are declared again and addresses replaced by their names.
+pub fn disassemble(hack: &str, symbols: Option<&SymbolMap>) -> Result<String, AsmErrors> {
+    let mut words = vec![];
+    let mut errors = vec![];
+    for (i, line) in hack.lines().enumerate() {
+        let text = line.trim();
+        if text.is_empty() {
+            continue;
+        }
+        let word = match u16::from_str_radix(text, 2) {
+            Ok(word) if text.len() == 16 => decode(word),
+            _ => None,
+        };
+        match word {
+            Some(word) => words.push(word),
+            None => errors.push(AsmError::new(i + 1, text, ErrorKind::InvalidWord)),
+        }
+    }
+    if !errors.is_empty() {
+        return Err(AsmErrors(errors));
+    }
+
+    let mut asm = String::new();
+    let declare_labels = |asm: &mut String, address: usize| {
+        if let Some(symbols) = symbols {
+            for label in symbols.labels_at(address as u16) {
+                asm.push_str(&format!("({})\n", label));
+            }
+        }
+    };
+    for (address, word) in words.iter().enumerate() {
+        declare_labels(&mut asm, address);
+        let line = match word {
+            Word::A(value) => {
+                let symbol = symbols.and_then(|s| symbol_for(*value, words.get(address + 1), s));
+                format!("@{}", symbol.unwrap_or_else(|| value.to_string()))
+            }
+            Word::C(comp, dest, jump) => format_c_instruction(comp, dest, jump),
+        };
+        asm.push_str(&line);
+        asm.push('\n');
+    }
+    declare_labels(&mut asm, words.len());
+    Ok(asm)
+}
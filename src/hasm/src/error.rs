@@ -0,0 +1,151 @@
+use std::{error::Error, fmt};
+
+use crate::parser::MAX_ADDRESS;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ErrorKind {
+    UnknownComp(String),
+    UnknownDest(String),
+    UnknownJump(String),
+    /// Constant which doesn't fit in the 15 bits of an A-instruction.
+    AddressOutOfRange(String),
+    InvalidSymbol(String),
+    MalformedLabel,
+    DuplicateLabel(String),
+    /// First instruction past the end of the ROM.
+    RomOverflow,
+    /// `.hack` line which isn't a valid 16-bit instruction.
+    InvalidWord,
+    UnknownDirective(String),
+    /// Directive with missing or invalid operands, with the expected form.
+    InvalidDirective(String),
+    UnterminatedMacro(String),
+    UnmatchedEndm,
+    MacroArguments {
+        name: String,
+        expected: usize,
+        got: usize,
+    },
+    /// Macro which expands itself, directly or through other macros.
+    MacroRecursion(String),
+    Include(String),
+}
+
+impl fmt::Display for ErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ErrorKind::UnknownComp(comp) => write!(f, "Unknown computation: {:?}", comp),
+            ErrorKind::UnknownDest(dest) => write!(f, "Unknown destination: {:?}", dest),
+            ErrorKind::UnknownJump(jump) => write!(f, "Unknown jump: {:?}", jump),
+            ErrorKind::AddressOutOfRange(address) => {
+                write!(f, "Address out of range 0..=32767: {}", address)
+            }
+            ErrorKind::InvalidSymbol(symbol) => write!(f, "Invalid symbol: {:?}", symbol),
+            ErrorKind::MalformedLabel => f.write_str("Malformed label, expected (SYMBOL)"),
+            ErrorKind::DuplicateLabel(label) => write!(f, "Duplicate label: {}", label),
+            ErrorKind::RomOverflow => write!(
+                f,
+                "Program doesn't fit in the {} word ROM",
+                MAX_ADDRESS as usize + 1
+            ),
+            ErrorKind::InvalidWord => f.write_str("Invalid instruction word"),
+            ErrorKind::UnknownDirective(directive) => {
+                write!(f, "Unknown directive: {}", directive)
+            }
+            ErrorKind::InvalidDirective(usage) => {
+                write!(f, "Invalid directive, expected {}", usage)
+            }
+            ErrorKind::UnterminatedMacro(name) => {
+                write!(f, "Macro {} is missing its .endm", name)
+            }
+            ErrorKind::UnmatchedEndm => f.write_str(".endm outside of a macro"),
+            ErrorKind::MacroArguments {
+                name,
+                expected,
+                got,
+            } => write!(
+                f,
+                "Macro {} expects {} argument(s), got {}",
+                name, expected, got
+            ),
+            ErrorKind::MacroRecursion(name) => write!(f, "Macro {} expands itself", name),
+            ErrorKind::Include(message) => write!(f, "Can't include {}", message),
+        }
+    }
+}
+
+/// Macro expansion a line came from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Expansion {
+    pub macro_name: String,
+    /// File of the call site, `None` for the main file.
+    pub file: Option<String>,
+    /// 1-based line number of the call site.
+    pub line: usize,
+}
+
+/// Invalid assembly line.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AsmError {
+    /// 1-based line number.
+    pub line: usize,
+    /// Statement on the line, without comments.
+    pub text: String,
+    pub kind: ErrorKind,
+    /// Included file the line is in, `None` for the main file.
+    pub file: Option<String>,
+    /// Macro expansions the line is part of, innermost first. The line number
+    /// is then the one in the macro definition.
+    pub expansions: Vec<Expansion>,
+}
+
+impl AsmError {
+    pub fn new<S: Into<String>>(line: usize, text: S, kind: ErrorKind) -> Self {
+        AsmError {
+            line,
+            text: text.into(),
+            kind,
+            file: None,
+            expansions: vec![],
+        }
+    }
+}
+
+fn fmt_location(f: &mut fmt::Formatter<'_>, file: &Option<String>, line: usize) -> fmt::Result {
+    match file {
+        Some(file) => write!(f, "{}:{}", file, line),
+        None => write!(f, "line {}", line),
+    }
+}
+
+impl fmt::Display for AsmError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt_location(f, &self.file, self.line)?;
+        write!(f, ": {}\n  | {}", self.kind, self.text)?;
+        for expansion in &self.expansions {
+            write!(f, "\n  in macro {} expanded at ", expansion.macro_name)?;
+            fmt_location(f, &expansion.file, expansion.line)?;
+        }
+        Ok(())
+    }
+}
+
+impl Error for AsmError {}
+
+/// Every invalid line of a program.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AsmErrors(pub Vec<AsmError>);
+
+impl fmt::Display for AsmErrors {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, error) in self.0.iter().enumerate() {
+            if i > 0 {
+                f.write_str("\n")?;
+            }
+            write!(f, "{}", error)?;
+        }
+        Ok(())
+    }
+}
+
+impl Error for AsmErrors {}
@@ -4,7 +4,7 @@ macro_rules! enum_str {
     ($q:ident enum $name:ident {
         $($variant:ident = $val:expr),*,
     }) => {
-        #[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
         $q enum $name {
             $($variant = $val),*
         }
@@ -15,6 +15,26 @@ macro_rules! enum_str {
                     $($name::$variant => $val),*
                 }
             }
+
+            /// Variant encoded by the 3 lowest bits.
+            pub fn from_bits(bits: u16) -> Self {
+                match (bits & 0b111) as u8 {
+                    $(x if x == $val => $name::$variant,)*
+                    _ => unreachable!(),
+                }
+            }
+
+            /// Assembly mnemonic, empty for `Null`.
+            pub fn mnemonic(&self) -> &'static str {
+                let name = match self {
+                    $($name::$variant => stringify!($variant)),*
+                };
+                if name == "Null" {
+                    ""
+                } else {
+                    name
+                }
+            }
         }
     };
 }
@@ -49,57 +69,110 @@ pub enum Jump {
     JMP = 0b111,
 }}
 
+impl Dest {
+    pub fn parse(s: &str) -> Option<Self> {
+        Some(match s {
+            "M" => Dest::M,
+            "D" => Dest::D,
+            "MD" => Dest::MD,
+            "A" => Dest::A,
+            "AM" => Dest::AM,
+            "AD" => Dest::AD,
+            "AMD" => Dest::AMD,
+            _ => return None,
+        })
+    }
+}
+
+impl Jump {
+    pub fn parse(s: &str) -> Option<Self> {
+        Some(match s {
+            "JGT" => Jump::JGT,
+            "JEQ" => Jump::JEQ,
+            "JGE" => Jump::JGE,
+            "JLT" => Jump::JLT,
+            "JNE" => Jump::JNE,
+            "JLE" => Jump::JLE,
+            "JMP" => Jump::JMP,
+            _ => return None,
+        })
+    }
+}
+
 impl std::fmt::Binary for Jump {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         std::fmt::Binary::fmt(&self.name(), f)
     }
 }
 
-#[derive(Debug)]
-pub struct Comp(String);
+/// Computation of a C-instruction, as its `a c1..c6` bits.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Comp(&'static str);
+
+/// Computations by mnemonic, the bits of the first one are canonical.
+pub const COMPS: [(&str, &str); 28] = [
+    ("0", "0101010"),
+    ("1", "0111111"),
+    ("-1", "0111010"),
+    ("D", "0001100"),
+    ("A", "0110000"),
+    ("!D", "0001101"),
+    ("!A", "0110001"),
+    ("-D", "0001111"),
+    ("-A", "0110011"),
+    ("D+1", "0011111"),
+    ("A+1", "0110111"),
+    ("D-1", "0001110"),
+    ("A-1", "0110010"),
+    ("D+A", "0000010"),
+    ("D-A", "0010011"),
+    ("A-D", "0000111"),
+    ("D&A", "0000000"),
+    ("D|A", "0010101"),
+    ("M", "1110000"),
+    ("!M", "1110001"),
+    ("-M", "1110011"),
+    ("M+1", "1110111"),
+    ("M-1", "1110010"),
+    ("D+M", "1000010"),
+    ("D-M", "1010011"),
+    ("M-D", "1000111"),
+    ("D&M", "1000000"),
+    ("D|M", "1010101"),
+];
 
 impl Comp {
-    pub fn fmt_binary(&self) -> &'static str {
-        let Self(s) = self;
-        match s as &str {
-            "0" => "0101010",
-            "1" => "0111111",
-            "-1" => "0111010",
-
-            "D" => "0001100",
-            "A" => "0110000",
-            "!D" => "0001111",
-            "!A" => "0110011",
-            "-D" => "0001111",
-            "-A" => "0110011",
-            "D+1" => "0011111",
-            "A+1" => "0110111",
-            "D-1" => "0001110",
-            "A-1" => "0110010",
-            "D+A" => "0000010",
-            "D-A" => "0010011",
-            "A-D" => "0000111",
-            "D&A" => "0000000",
-            "D|A" => "0010101",
-
-            "M" => "1110000",
-            "!M" => "1110001",
-            "-M" => "1110011",
-            "M+1" => "1110111",
-            "M-1" => "1110010",
-            "D+M" => "1000010",
-            "D-M" => "1010011",
-            "M-D" => "1000111",
-            "D&M" => "1000000",
-            "D|M" => "1010101",
-
-            _ => "-------",
-        }
+    /// Parses a mnemonic, accepting the operands of `+`, `&` and `|` in either order.
+    pub fn parse(s: &str) -> Option<Self> {
+        let swapped = match s.as_bytes() {
+            [a, op @ (b'+' | b'&' | b'|'), b] => {
+                Some(format!("{}{}{}", *b as char, *op as char, *a as char))
+            }
+            _ => None,
+        };
+        COMPS
+            .iter()
+            .find(|(name, _)| *name == s || Some(*name) == swapped.as_deref())
+            .map(|(_, bits)| Comp(bits))
     }
-}
 
-pub fn make_comp(s: String) -> Comp {
-    Comp(s)
+    /// Decodes the `a c1..c6` bits, `None` for a combination without mnemonic.
+    pub fn from_bits(bits: u16) -> Option<Self> {
+        let bits = format!("{:07b}", bits & 0b111_1111);
+        COMPS.iter().find(|(_, b)| *b == bits).map(|(_, b)| Comp(b))
+    }
+
+    pub fn mnemonic(&self) -> &'static str {
+        COMPS
+            .iter()
+            .find(|(_, bits)| *bits == self.0)
+            .map(|(name, _)| *name)
+            .unwrap_or_default()
+    }
+
+    pub fn fmt_binary(&self) -> &'static str {
+        self.0
+    }
 }
 
 #[derive(Debug)]
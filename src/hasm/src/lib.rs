@@ -1,19 +1,87 @@
 pub mod code;
 pub mod config;
+pub mod disassembler;
+pub mod error;
 pub mod instruction;
 pub mod parser;
+pub mod preprocessor;
+pub mod source_map;
+pub mod symbols;
 
 use std::error::Error;
 use std::fs;
-use std::path;
+use std::io::{self, Read, Write};
+use std::path::Path;
 
-use config::Config;
+use config::{Config, Verbosity, STDIO};
+
+fn read_input(filename: &str) -> Result<String, Box<dyn Error>> {
+    if filename == STDIO {
+        let mut contents = String::new();
+        io::stdin().read_to_string(&mut contents)?;
+        Ok(contents)
+    } else {
+        Ok(fs::read_to_string(filename)?)
+    }
+}
+
+fn write_output(target: &str, contents: &str) -> Result<(), Box<dyn Error>> {
+    if target == STDIO {
+        io::stdout().write_all(contents.as_bytes())?;
+    } else {
+        fs::write(target, contents)?;
+    }
+    Ok(())
+}
 
 pub fn run(config: Config) -> Result<(), Box<dyn Error>> {
-    let contents = fs::read_to_string(&config.filename)?;
-    let parse_result = parser::parse(contents);
-    let mut target_filename = path::PathBuf::from(&config.filename);
-    target_filename.set_extension("hack");
-    fs::write(target_filename, code::generate_code(parse_result))?;
+    let path = Some(Path::new(&config.filename)).filter(|_| config.filename != STDIO);
+    let parse_result = parser::parse_with_macros(&read_input(&config.filename)?, path)?;
+    let mut outputs = vec![];
+    if config.emit_symbols {
+        outputs.push((config.side_target("sym")?, parse_result.symbols.to_string()));
+    }
+    if config.listing {
+        outputs.push((
+            config.side_target("lst")?,
+            code::generate_listing(&parse_result),
+        ));
+    }
+    if config.source_map {
+        let map = code::generate_source_map(&parse_result, &config.filename);
+        outputs.push((config.side_target("map")?, map.to_string()));
+    }
+    let hack = code::generate_code(parse_result);
+    if config.check {
+        if config.verbosity >= Verbosity::Verbose {
+            eprintln!("{}: OK", config.filename);
+        }
+        return Ok(());
+    }
+
+    let target = config.target();
+    write_output(&target, &hack)?;
+    if target != STDIO && config.verbosity >= Verbosity::Verbose {
+        eprintln!("Wrote {} instructions to: {}", hack.lines().count(), target);
+    }
+    for (path, contents) in outputs {
+        fs::write(&path, contents)?;
+        if config.verbosity >= Verbosity::Verbose {
+            eprintln!("Wrote to: {}", path);
+        }
+    }
     Ok(())
 }
+
+/// Disassembles `config.filename`, writing to stdout unless an output is given.
+pub fn run_disassembler(config: Config) -> Result<(), Box<dyn Error>> {
+    let symbols = match &config.symbols {
+        Some(path) => Some(symbols::SymbolMap::parse(&fs::read_to_string(path)?)?),
+        None => None,
+    };
+    let asm = disassembler::disassemble(&read_input(&config.filename)?, symbols.as_ref())?;
+    if config.check {
+        return Ok(());
+    }
+    write_output(config.output.as_deref().unwrap_or(STDIO), &asm)
+}
@@ -1,13 +1,17 @@
 use std::env;
 
-use hasm::config::Config;
+use hasm::config::{Config, USAGE};
 
 fn main() {
     let args: Vec<String> = env::args().collect();
     let config = Config::new(&args).unwrap_or_else(|err| {
-        println!("Problem parsing arguments: {}", err);
+        println!("Problem parsing arguments: {}\n\n{}", err, USAGE);
         std::process::exit(1);
     });
+    if config.help {
+        print!("{}", USAGE);
+        return;
+    }
 
     if let Err(e) = hasm::run(config) {
         println!("Application error: {}", e);
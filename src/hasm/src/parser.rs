@@ -1,15 +1,28 @@
+use std::collections::BTreeMap;
+
+use std::path::Path;
+
+use crate::error::{AsmError, AsmErrors, ErrorKind};
 use crate::instruction::*;
+use crate::preprocessor;
+use crate::symbols::SymbolMap;
 
 #[derive(Debug)]
 pub struct Command {
     pub inst: Instruction,
     pub raw: String,
+    /// Line of the source, counted from 1.
+    pub line: usize,
+    /// Included file the line is in, `None` for the main file.
+    pub file: Option<String>,
 }
 
 #[derive(Debug)]
 pub struct ParseResult {
     pub commands: Vec<Command>,
     pub inst_counter: u16,
+    /// Labels and variables of the program, without the predefined symbols.
+    pub symbols: SymbolMap,
 }
 
 pub struct Parser<'a> {
@@ -17,6 +30,9 @@ pub struct Parser<'a> {
     var_addr_next: u16,
 }
 
+/// Largest address an A-instruction can load.
+pub const MAX_ADDRESS: u16 = 0x7fff;
+
 impl<'a> Parser<'a> {
     pub fn new(input: &'a str) -> Self {
         Parser {
@@ -25,132 +41,178 @@ impl<'a> Parser<'a> {
         }
     }
 
-    pub fn parse(mut self) -> ParseResult {
+    pub fn parse(mut self) -> Result<ParseResult, AsmErrors> {
+        let labels = self.build_labels().map_err(AsmErrors)?;
         let mut symbols = self.build_symbols();
-        self.parse_input(&mut symbols, true); // Build symbols table
-                                              // println!("symbols: {:?}", symbols);
-
-        // println!("symbols final: {:?}", symbols);
-        self.parse_input(&mut symbols, false)
+        symbols.extend(labels.clone());
+        let mut result = self.parse_input(&mut symbols);
+        result.symbols.labels = labels.into_iter().collect();
+        Ok(result)
     }
 
-    pub fn parse_input(&mut self, symbols: &mut SymbolTable, first_pass: bool) -> ParseResult {
+    /// First pass: collects the labels, or the invalid lines.
+    fn build_labels(&self) -> Result<SymbolTable, Vec<AsmError>> {
+        let predefined = self.build_symbols();
         let mut inst_counter = 0u16;
-        let mut commands = Vec::<Command>::new();
-        for line in self.input.lines() {
-            if let Some(command) = Self::parse_line(line, symbols) {
-                match &command.inst {
-                    Instruction::LInstruction { label } => {
-                        // dbg!(symbols.get(label));
-                        symbols.insert(label.into(), inst_counter);
-                    }
-                    Instruction::AInstruction {
-                        address: AInstAddress::Label(label),
-                    } if !first_pass => {
-                        let addr = self.var_addr_next;
-                        symbols.insert(label.into(), addr);
-                        inst_counter += 1;
-                        commands.push(Command {
-                            inst: Instruction::AInstruction {
-                                address: AInstAddress::Address(addr),
-                            },
-                            ..command
-                        });
-                        self.var_addr_next += 1;
+        let mut errors = vec![];
+        let mut labels = SymbolTable::new();
+        for (i, line) in self.input.lines().enumerate() {
+            let error = |kind| AsmError::new(i + 1, statement(line), kind);
+            match Self::parse_line(line, &labels) {
+                Ok(Some(Command {
+                    inst: Instruction::LInstruction { label },
+                    ..
+                })) => {
+                    if labels.contains_key(&label) || predefined.contains_key(&label) {
+                        errors.push(error(ErrorKind::DuplicateLabel(label)));
+                    } else {
+                        labels.insert(label, inst_counter);
                     }
-                    _ => {
-                        inst_counter += 1;
-                        commands.push(command);
+                }
+                Ok(Some(_)) => {
+                    if inst_counter == MAX_ADDRESS + 1 {
+                        errors.push(error(ErrorKind::RomOverflow));
                     }
-                };
-            } else {
-                // println!("Unable to parse line: {}", line);
+                    inst_counter = inst_counter.wrapping_add(1);
+                }
+                Ok(None) => {}
+                Err(kind) => errors.push(error(kind)),
+            }
+        }
+        if errors.is_empty() {
+            Ok(labels)
+        } else {
+            Err(errors)
+        }
+    }
+
+    /// Second pass: resolves symbols, allocating variables. Expects valid input.
+    pub fn parse_input(&mut self, symbols: &mut SymbolTable) -> ParseResult {
+        let mut inst_counter = 0u16;
+        let mut commands = Vec::<Command>::new();
+        let mut variables = BTreeMap::new();
+        for (i, line) in self.input.lines().enumerate() {
+            match Self::parse_line(line, symbols).map(|c| c.map(|c| Command { line: i + 1, ..c })) {
+                Ok(Some(Command {
+                    inst: Instruction::LInstruction { .. },
+                    ..
+                }))
+                | Ok(None)
+                | Err(_) => {}
+                Ok(Some(Command {
+                    inst:
+                        Instruction::AInstruction {
+                            address: AInstAddress::Label(label),
+                        },
+                    raw,
+                    line,
+                    file,
+                })) => {
+                    let addr = self.var_addr_next;
+                    symbols.insert(label.clone(), addr);
+                    variables.insert(label, addr);
+                    inst_counter += 1;
+                    commands.push(Command {
+                        inst: Instruction::AInstruction {
+                            address: AInstAddress::Address(addr),
+                        },
+                        raw,
+                        line,
+                        file,
+                    });
+                    self.var_addr_next += 1;
+                }
+                Ok(Some(command)) => {
+                    inst_counter += 1;
+                    commands.push(command);
+                }
             };
         }
         ParseResult {
             commands,
             inst_counter,
+            symbols: SymbolMap {
+                variables,
+                ..SymbolMap::new()
+            },
         }
     }
 
-    fn parse_line(line: &str, symbols: &mut SymbolTable) -> Option<Command> {
-        let stmt = line.split("//").next().unwrap_or_default().trim();
-        // println!("parse_line: {:?}, {:?}", &line, &stmt);
-        match stmt {
-            "" => None,
+    fn parse_line(line: &str, symbols: &SymbolTable) -> Result<Option<Command>, ErrorKind> {
+        let stmt = statement(line);
+        let inst = match stmt {
+            "" => return Ok(None),
             x if x.starts_with('@') => {
-                let label: String = x.chars().skip(1).collect();
-                let address = str::parse::<u16>(&label).ok();
-                Some(Command {
-                    raw: x.into(),
-                    inst: match address {
-                        Some(address) => Instruction::AInstruction {
-                            address: AInstAddress::Address(address),
-                        },
-                        _ => {
-                            let addr = symbols.get(&label);
-                            Instruction::AInstruction {
-                                address: match addr {
-                                    Some(addr) => AInstAddress::Address(*addr),
-                                    _ => AInstAddress::Label(label),
-                                },
-                            }
+                let label = &x[1..];
+                let address = if label.starts_with(|c: char| c.is_ascii_digit()) {
+                    match str::parse::<u16>(label) {
+                        Ok(address) if address <= MAX_ADDRESS => AInstAddress::Address(address),
+                        _ if label.chars().all(|c| c.is_ascii_digit()) => {
+                            return Err(ErrorKind::AddressOutOfRange(label.into()))
                         }
-                    },
-                })
+                        _ => return Err(ErrorKind::InvalidSymbol(label.into())),
+                    }
+                } else if !is_symbol(label) {
+                    return Err(ErrorKind::InvalidSymbol(label.into()));
+                } else {
+                    match symbols.get(label) {
+                        Some(addr) => AInstAddress::Address(*addr),
+                        _ => AInstAddress::Label(label.into()),
+                    }
+                };
+                Instruction::AInstruction { address }
             }
             x if x.starts_with('(') => {
-                let label = x[1..x.len() - 1].into();
-                Some(Command {
-                    raw: x.into(),
-                    inst: Instruction::LInstruction { label },
-                })
+                let label = x
+                    .strip_prefix('(')
+                    .and_then(|x| x.strip_suffix(')'))
+                    .ok_or(ErrorKind::MalformedLabel)?;
+                if !is_symbol(label) {
+                    return Err(ErrorKind::InvalidSymbol(label.into()));
+                }
+                Instruction::LInstruction {
+                    label: label.into(),
+                }
             }
             x => {
-                let dest = x.split('=').next();
-                let jump = x.split(';').nth(1);
-                let comp = x
-                    .replace(&format!("{}{}", dest.unwrap_or_default(), "="), "")
-                    .replace(&format!("{}{}", ";", jump.unwrap_or_default()), "");
-                Some(Command {
-                    raw: x.into(),
-                    inst: Instruction::CInstruction {
-                        comp: Self::parse_comp(comp),
-                        dest: Self::parse_dest(dest),
-                        jump: Self::parse_jump(jump),
-                    },
-                })
+                let (dest, rest) = match x.split_once('=') {
+                    Some((dest, rest)) => (Some(dest), rest),
+                    None => (None, x),
+                };
+                let (comp, jump) = match rest.split_once(';') {
+                    Some((comp, jump)) => (comp, Some(jump)),
+                    None => (rest, None),
+                };
+                Instruction::CInstruction {
+                    comp: Self::parse_comp(comp)?,
+                    dest: Self::parse_dest(dest)?,
+                    jump: Self::parse_jump(jump)?,
+                }
             }
-        }
+        };
+        Ok(Some(Command {
+            raw: stmt.into(),
+            inst,
+            line: 0,
+            file: None,
+        }))
     }
 
-    fn parse_comp(s: String) -> Comp {
-        make_comp(s)
+    fn parse_comp(s: &str) -> Result<Comp, ErrorKind> {
+        Comp::parse(s).ok_or_else(|| ErrorKind::UnknownComp(s.into()))
     }
 
-    fn parse_dest(s: Option<&str>) -> Dest {
+    fn parse_dest(s: Option<&str>) -> Result<Dest, ErrorKind> {
         match s {
-            Some("M") => Dest::M,
-            Some("D") => Dest::D,
-            Some("MD") => Dest::MD,
-            Some("A") => Dest::A,
-            Some("AM") => Dest::AM,
-            Some("AD") => Dest::AD,
-            Some("AMD") => Dest::AMD,
-            _ => Dest::Null,
+            None => Ok(Dest::Null),
+            Some(s) => Dest::parse(s).ok_or_else(|| ErrorKind::UnknownDest(s.into())),
         }
     }
 
-    fn parse_jump(s: Option<&str>) -> Jump {
+    fn parse_jump(s: Option<&str>) -> Result<Jump, ErrorKind> {
         match s {
-            Some("JGT") => Jump::JGT,
-            Some("JEQ") => Jump::JEQ,
-            Some("JGE") => Jump::JGE,
-            Some("JLT") => Jump::JLT,
-            Some("JNE") => Jump::JNE,
-            Some("JLE") => Jump::JLE,
-            Some("JMP") => Jump::JMP,
-            _ => Jump::Null,
+            None => Ok(Jump::Null),
+            Some(s) => Jump::parse(s).ok_or_else(|| ErrorKind::UnknownJump(s.into())),
         }
     }
 
@@ -159,7 +221,48 @@ impl<'a> Parser<'a> {
     }
 }
 
-pub fn parse(content: String) -> ParseResult {
+/// Line without its comment and surrounding whitespace.
+fn statement(line: &str) -> &str {
+    line.split("//").next().unwrap_or_default().trim()
+}
+
+/// Symbols are letters, digits, `_`, `.`, `$` and `:`, not starting with a digit.
+fn is_symbol(s: &str) -> bool {
+    !s.is_empty()
+        && !s.starts_with(|c: char| c.is_ascii_digit())
+        && s.chars()
+            .all(|c| c.is_ascii_alphanumeric() || "_.$:".contains(c))
+}
+
+pub fn parse(content: String) -> Result<ParseResult, AsmErrors> {
     let parser = Parser::new(&content);
     parser.parse()
 }
+
+/// Expands the `.macro`, `.include` and `.equ` directives before parsing.
+/// Errors point at the line of the original source they come from, commands
+/// at the line of the outermost macro call; `path` is the file of `content`,
+/// used to resolve includes.
+pub fn parse_with_macros(content: &str, path: Option<&Path>) -> Result<ParseResult, AsmErrors> {
+    let preprocessed = preprocessor::preprocess(content, path)?;
+    let mut result = parse(preprocessed.source).map_err(|AsmErrors(errors)| {
+        let locate = |error: AsmError| match error
+            .line
+            .checked_sub(1)
+            .and_then(|i| preprocessed.origins.get(i))
+        {
+            Some(origin) => origin.locate(error),
+            None => error,
+        };
+        AsmErrors(errors.into_iter().map(locate).collect())
+    })?;
+    for command in result.commands.iter_mut() {
+        if let Some(origin) = preprocessed.origins.get(command.line - 1) {
+            (command.file, command.line) = match origin.expansions.last() {
+                Some(call) => (call.file.clone(), call.line),
+                None => (origin.file.clone(), origin.line),
+            };
+        }
+    }
+    Ok(result)
+}
@@ -0,0 +1,279 @@
+//! Expands the assembler directives into plain Hack assembly:
+//!
+//! ```text
+//! .include "macros.asm"        // Reads a file relative to the current one
+//! .equ BUFFER 1024             // `@BUFFER` loads 1024
+//! .macro JUMP_IF_ZERO address  // Parameters are replaced in the body
+//!     @.skip                   // Labels starting with `.` are local to
+//!     D;JNE                    // each expansion
+//!     @address
+//!     0;JMP
+//! (.skip)
+//! .endm
+//!     JUMP_IF_ZERO END         // Expands the macro
+//! ```
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::error::{AsmError, AsmErrors, ErrorKind, Expansion};
+
+/// Expansions nested deeper than this are reported as recursion.
+const MAX_EXPANSION_DEPTH: usize = 64;
+
+/// Where an expanded line comes from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Origin {
+    pub file: Option<String>,
+    pub line: usize,
+    pub expansions: Vec<Expansion>,
+}
+
+impl Origin {
+    /// Moves an error on the expanded source to this line.
+    pub fn locate(&self, error: AsmError) -> AsmError {
+        AsmError {
+            line: self.line,
+            file: self.file.clone(),
+            expansions: self.expansions.clone(),
+            ..error
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Preprocessed {
+    pub source: String,
+    /// Origin of every line of `source`.
+    pub origins: Vec<Origin>,
+}
+
+#[derive(Debug, Clone)]
+struct Macro {
+    params: Vec<String>,
+    /// Body lines with their origin.
+    body: Vec<(String, Origin)>,
+}
+
+#[derive(Default)]
+struct Preprocessor {
+    macros: HashMap<String, Macro>,
+    constants: HashMap<String, String>,
+    includes: Vec<PathBuf>,
+    expansion_count: usize,
+    lines: Vec<String>,
+    origins: Vec<Origin>,
+    errors: Vec<AsmError>,
+}
+
+/// Expands the directives of `source`, `path` locates included files.
+pub fn preprocess(source: &str, path: Option<&Path>) -> Result<Preprocessed, AsmErrors> {
+    let mut preprocessor = Preprocessor::default();
+    if let Some(path) = path {
+        preprocessor.includes.push(path.to_path_buf());
+    }
+    preprocessor.process_source(source, None, path.and_then(Path::parent));
+    if !preprocessor.errors.is_empty() {
+        return Err(AsmErrors(preprocessor.errors));
+    }
+    Ok(Preprocessed {
+        source: preprocessor.lines.join("\n"),
+        origins: preprocessor.origins,
+    })
+}
+
+/// Line without its comment and surrounding whitespace.
+fn statement(line: &str) -> &str {
+    line.split("//").next().unwrap_or_default().trim()
+}
+
+fn is_symbol_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || "_.$:".contains(c)
+}
+
+/// Replaces the symbols in `line` for which `replace` returns a value.
+fn replace_symbols(line: &str, replace: impl Fn(&str) -> Option<String>) -> String {
+    let mut out = String::new();
+    let mut rest = line;
+    while let Some(start) = rest.find(is_symbol_char) {
+        out += &rest[..start];
+        rest = &rest[start..];
+        let end = rest.find(|c| !is_symbol_char(c)).unwrap_or(rest.len());
+        let symbol = &rest[..end];
+        out += &replace(symbol).unwrap_or_else(|| symbol.to_string());
+        rest = &rest[end..];
+    }
+    out + rest
+}
+
+impl Preprocessor {
+    fn error(&mut self, origin: &Origin, text: &str, kind: ErrorKind) {
+        let error = AsmError::new(origin.line, text, kind);
+        self.errors.push(origin.locate(error));
+    }
+
+    fn process_source(&mut self, source: &str, file: Option<String>, dir: Option<&Path>) {
+        let mut definition: Option<(String, Macro, Origin)> = None;
+        for (i, line) in source.lines().enumerate() {
+            let origin = Origin {
+                file: file.clone(),
+                line: i + 1,
+                expansions: vec![],
+            };
+            let stmt = statement(line);
+            let mut words = stmt.split_whitespace();
+            match (words.next(), &mut definition) {
+                (Some(".endm"), Some(_)) => {
+                    let (name, definition, _) = definition.take().unwrap();
+                    self.macros.insert(name, definition);
+                }
+                (_, Some((_, definition, _))) => definition.body.push((line.to_string(), origin)),
+                (Some(".macro"), None) => {
+                    let params: Vec<String> = stmt[".macro".len()..]
+                        .split(|c: char| c == ',' || c.is_whitespace())
+                        .filter(|param| !param.is_empty())
+                        .map(String::from)
+                        .collect();
+                    match params.split_first() {
+                        Some((name, params)) if params.iter().all(|p| !p.starts_with('.')) => {
+                            let params = params.to_vec();
+                            let body = vec![];
+                            definition = Some((name.clone(), Macro { params, body }, origin));
+                        }
+                        _ => self.error(
+                            &origin,
+                            stmt,
+                            ErrorKind::InvalidDirective(".macro NAME [param, ...]".into()),
+                        ),
+                    }
+                }
+                (Some(".include"), None) => {
+                    let included = stmt[".include".len()..].trim();
+                    match included.strip_prefix('"').and_then(|s| s.strip_suffix('"')) {
+                        Some(included) => self.include(included, dir, &origin, stmt),
+                        None => self.error(
+                            &origin,
+                            stmt,
+                            ErrorKind::InvalidDirective(".include \"file.asm\"".into()),
+                        ),
+                    }
+                }
+                _ => self.process_line(line, &origin),
+            }
+        }
+        if let Some((name, _, origin)) = definition {
+            self.error(&origin, &name, ErrorKind::UnterminatedMacro(name.clone()));
+        }
+    }
+
+    fn include(&mut self, included: &str, dir: Option<&Path>, origin: &Origin, stmt: &str) {
+        let path = dir.unwrap_or_else(|| Path::new("")).join(included);
+        let canonical = path.canonicalize().unwrap_or_else(|_| path.clone());
+        if self
+            .includes
+            .iter()
+            .any(|p| p.canonicalize().ok().as_ref() == Some(&canonical))
+        {
+            let message = format!("{}: it includes itself", included);
+            self.error(origin, stmt, ErrorKind::Include(message));
+            return;
+        }
+        match fs::read_to_string(&path) {
+            Ok(source) => {
+                self.includes.push(path.clone());
+                let file = Some(path.to_string_lossy().into_owned());
+                self.process_source(&source, file, path.parent());
+                self.includes.pop();
+            }
+            Err(e) => {
+                let message = format!("{}: {}", included, e);
+                self.error(origin, stmt, ErrorKind::Include(message));
+            }
+        }
+    }
+
+    /// Handles a line outside of macro definitions: a macro call, `.equ` or
+    /// an instruction.
+    fn process_line(&mut self, line: &str, origin: &Origin) {
+        let stmt = statement(line);
+        let (first, rest) = stmt
+            .split_once(char::is_whitespace)
+            .map_or((stmt, ""), |(first, rest)| (first, rest.trim()));
+        if let Some(definition) = self.macros.get(first).cloned() {
+            let args: Vec<&str> = if rest.is_empty() {
+                vec![]
+            } else {
+                rest.split(',').map(str::trim).collect()
+            };
+            self.expand(first, &definition, &args, origin, stmt);
+        } else if first == ".equ" {
+            let operands: Vec<&str> = rest.split_whitespace().collect();
+            match operands[..] {
+                [name, value] if !name.starts_with(|c: char| c.is_ascii_digit()) => {
+                    self.constants.insert(name.into(), value.into());
+                }
+                _ => self.error(
+                    origin,
+                    stmt,
+                    ErrorKind::InvalidDirective(".equ NAME value".into()),
+                ),
+            }
+        } else if first == ".endm" {
+            self.error(origin, stmt, ErrorKind::UnmatchedEndm);
+        } else if first.starts_with('.') {
+            self.error(origin, stmt, ErrorKind::UnknownDirective(first.into()));
+        } else {
+            let line = match stmt.strip_prefix('@') {
+                Some(symbol) => match self.constants.get(symbol) {
+                    Some(value) => format!("@{}", value),
+                    None => line.to_string(),
+                },
+                None => line.to_string(),
+            };
+            self.lines.push(line);
+            self.origins.push(origin.clone());
+        }
+    }
+
+    fn expand(&mut self, name: &str, definition: &Macro, args: &[&str], call: &Origin, stmt: &str) {
+        if args.len() != definition.params.len() {
+            let kind = ErrorKind::MacroArguments {
+                name: name.into(),
+                expected: definition.params.len(),
+                got: args.len(),
+            };
+            self.error(call, stmt, kind);
+            return;
+        }
+        if call.expansions.len() >= MAX_EXPANSION_DEPTH {
+            self.error(call, stmt, ErrorKind::MacroRecursion(name.into()));
+            return;
+        }
+        self.expansion_count += 1;
+        let local_prefix = format!("{}.{}", name, self.expansion_count);
+        let mut expansions = vec![Expansion {
+            macro_name: name.into(),
+            file: call.file.clone(),
+            line: call.line,
+        }];
+        expansions.extend(call.expansions.iter().cloned());
+
+        for (line, body_origin) in &definition.body {
+            let line = replace_symbols(line, |symbol| {
+                if let Some(i) = definition.params.iter().position(|p| p == symbol) {
+                    Some(args[i].to_string())
+                } else if symbol.starts_with('.') && symbol.len() > 1 {
+                    Some(format!("{}{}", local_prefix, symbol))
+                } else {
+                    None
+                }
+            });
+            let origin = Origin {
+                expansions: expansions.clone(),
+                ..body_origin.clone()
+            };
+            self.process_line(&line, &origin);
+        }
+    }
+}
@@ -0,0 +1,100 @@
+use std::{collections::BTreeMap, error::Error, fmt};
+
+/// Line of a source file, counted from 1.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Location {
+    pub file: String,
+    pub line: usize,
+}
+
+impl fmt::Display for Location {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.file, self.line)
+    }
+}
+
+/// Source location of the lines of a generated file, one per line of a
+/// `.map` file:
+///
+/// ```text
+/// 12 Main.vm:4
+/// ```
+///
+/// Lines are counted from 1, so in a map of a `.hack` file the line of the
+/// instruction at ROM address `n` is `n + 1`. Lines without a source, like
+/// the bootstrap code, aren't mapped.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct SourceMap {
+    pub lines: BTreeMap<usize, Location>,
+}
+
+impl SourceMap {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub fn insert(&mut self, line: usize, location: Location) {
+        self.lines.insert(line, location);
+    }
+
+    pub fn get(&self, line: usize) -> Option<&Location> {
+        self.lines.get(&line)
+    }
+
+    /// Location of the instruction at a ROM address, in a map of a `.hack` file.
+    pub fn at_address(&self, address: u16) -> Option<&Location> {
+        self.get(address as usize + 1)
+    }
+
+    /// Follows the locations pointing into a file which has a map in
+    /// `sources`, e.g. from the assembly to the VM code it was translated
+    /// from. Lines missing from those maps are dropped, locations in other
+    /// files are kept.
+    pub fn compose(&self, sources: &BTreeMap<String, SourceMap>) -> SourceMap {
+        let lines = self
+            .lines
+            .iter()
+            .filter_map(|(line, location)| {
+                let location = match sources.get(&location.file) {
+                    Some(source) => source.get(location.line)?.clone(),
+                    None => location.clone(),
+                };
+                Some((*line, location))
+            })
+            .collect();
+        SourceMap { lines }
+    }
+
+    pub fn parse(contents: &str) -> Result<Self, Box<dyn Error>> {
+        let mut map = Self::new();
+        for (i, line) in contents.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() {
+                continue;
+            }
+            let invalid = || format!("Invalid source map line {}: {}", i + 1, line);
+            let (generated, location) = line.split_once(' ').ok_or_else(invalid)?;
+            let (file, source_line) = location.rsplit_once(':').ok_or_else(invalid)?;
+            let generated = generated.parse().map_err(|_| invalid())?;
+            let source_line = source_line.parse().map_err(|_| invalid())?;
+            map.insert(
+                generated,
+                Location {
+                    file: file.to_string(),
+                    line: source_line,
+                },
+            );
+        }
+        Ok(map)
+    }
+}
+
+/// Writes the `.map` format.
+impl fmt::Display for SourceMap {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (line, location) in &self.lines {
+            writeln!(f, "{} {}", line, location)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,77 @@
+use std::{collections::BTreeMap, error::Error, fmt};
+
+/// Symbols resolved by the assembler, one per line of a `.sym` file:
+///
+/// ```text
+/// label LOOP 4
+/// variable sum 16
+/// ```
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct SymbolMap {
+    /// Label names by ROM address.
+    pub labels: BTreeMap<String, u16>,
+    /// Variable names by RAM address.
+    pub variables: BTreeMap<String, u16>,
+}
+
+impl SymbolMap {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub fn parse(contents: &str) -> Result<Self, Box<dyn Error>> {
+        let mut symbols = Self::new();
+        for (i, line) in contents.lines().enumerate() {
+            let line = line.split("//").next().unwrap_or_default().trim();
+            if line.is_empty() {
+                continue;
+            }
+            let invalid = || format!("Invalid symbol map line {}: {}", i + 1, line);
+            let parts: Vec<&str> = line.split_whitespace().collect();
+            let (kind, name, address) = match parts[..] {
+                [kind, name, address] => (kind, name, address),
+                _ => return Err(invalid().into()),
+            };
+            let address = address.parse::<u16>().map_err(|_| invalid())?;
+            let table = match kind {
+                "label" => &mut symbols.labels,
+                "variable" => &mut symbols.variables,
+                _ => return Err(invalid().into()),
+            };
+            table.insert(name.to_string(), address);
+        }
+        Ok(symbols)
+    }
+
+    pub fn labels_at(&self, address: u16) -> impl Iterator<Item = &str> {
+        self.labels
+            .iter()
+            .filter(move |(_, a)| **a == address)
+            .map(|(name, _)| name.as_str())
+    }
+
+    pub fn label_at(&self, address: u16) -> Option<&str> {
+        self.labels_at(address).next()
+    }
+
+    pub fn variable_at(&self, address: u16) -> Option<&str> {
+        self.variables
+            .iter()
+            .find(|(_, a)| **a == address)
+            .map(|(name, _)| name.as_str())
+    }
+}
+
+/// Writes the `.sym` format, ordered by address.
+impl fmt::Display for SymbolMap {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (kind, table) in [("label", &self.labels), ("variable", &self.variables)] {
+            let mut entries: Vec<_> = table.iter().collect();
+            entries.sort_by_key(|(name, address)| (**address, *name));
+            for (name, address) in entries {
+                writeln!(f, "{} {} {}", kind, name, address)?;
+            }
+        }
+        Ok(())
+    }
+}
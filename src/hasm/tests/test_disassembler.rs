@@ -0,0 +1,105 @@
+use hasm::{
+    code, disassembler::disassemble, error::ErrorKind, instruction::COMPS, parser,
+    symbols::SymbolMap,
+};
+
+const PROGRAM: &str = "\
+@R0
+D=M
+@sum
+M=0
+(LOOP)
+@i
+D=M
+@R0
+D=D-M
+@END
+D;JGE
+@i
+D=M
+@sum
+M=D+M
+@i
+M=M+1
+@LOOP
+0;JMP
+(END)
+@END
+0;JMP
+";
+
+fn assemble(asm: &str) -> String {
+    code::generate_code(parser::parse(asm.to_string()).unwrap())
+}
+
+#[test]
+fn test_round_trip() {
+    let hack = assemble(PROGRAM);
+    let asm = disassemble(&hack, None).unwrap();
+    assert!(asm.starts_with("@0\nD=M\n@16\nM=0\n@17\n"));
+    assert_eq!(assemble(&asm), hack);
+}
+
+#[test]
+fn test_round_trip_every_comp() {
+    let asm: String = COMPS
+        .iter()
+        .map(|(comp, _)| format!("AMD={};JMP\n", comp))
+        .collect();
+    assert_eq!(disassemble(&assemble(&asm), None).unwrap(), asm);
+}
+
+#[test]
+fn test_symbols() {
+    let symbols = SymbolMap::parse(
+        "\
+label LOOP 4
+label END 18
+// Variables
+variable sum 16
+variable i 17
+",
+    )
+    .unwrap();
+    let asm = disassemble(&assemble(PROGRAM), Some(&symbols)).unwrap();
+    let expected = PROGRAM.replace("@R0", "@0");
+    assert_eq!(asm, expected);
+}
+
+#[test]
+fn test_symbol_map_format() {
+    let mut symbols = SymbolMap::new();
+    symbols.labels.insert("LOOP".into(), 4);
+    symbols.labels.insert("END".into(), 2);
+    symbols.variables.insert("x".into(), 16);
+    let text = symbols.to_string();
+    assert_eq!(text, "label END 2\nlabel LOOP 4\nvariable x 16\n");
+    assert_eq!(SymbolMap::parse(&text).unwrap(), symbols);
+    assert!(SymbolMap::parse("label LOOP").is_err());
+    assert!(SymbolMap::parse("constant X 1").is_err());
+}
+
+#[test]
+fn test_invalid_words() {
+    let hack = "\
+0000000000000001
+1010101010101010
+111011111100100
+1110111111001000
+2110111111001000
+1111111111001000
+";
+    let errors = disassemble(hack, None).unwrap_err().0;
+    let lines: Vec<usize> = errors.iter().map(|e| e.line).collect();
+    assert_eq!(lines, vec![2, 3, 5, 6]);
+    assert!(errors.iter().all(|e| e.kind == ErrorKind::InvalidWord));
+}
+
+#[test]
+fn test_round_trip_with_emitted_symbols() {
+    let parse_result = parser::parse(PROGRAM.to_string()).unwrap();
+    let symbols = parse_result.symbols.clone();
+    let hack = code::generate_code(parse_result);
+    let asm = disassemble(&hack, Some(&symbols)).unwrap();
+    assert_eq!(asm, PROGRAM.replace("@R0", "@0"));
+}
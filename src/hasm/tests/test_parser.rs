@@ -0,0 +1,121 @@
+use hasm::{
+    code,
+    error::{AsmError, ErrorKind},
+    parser,
+};
+
+fn assemble(asm: &str) -> Result<String, Vec<AsmError>> {
+    parser::parse(asm.to_string())
+        .map(code::generate_code)
+        .map_err(|errors| errors.0)
+}
+
+#[test]
+fn test_assemble() {
+    let asm = "
+        // Adds R0 and R1
+        @R0
+        D=M
+        @R1
+        D=D+M
+        @sum // variable
+        M=D
+    (END)
+        @END
+        0;JMP
+    ";
+    let expected = [
+        "0000000000000000",
+        "1111110000010000",
+        "0000000000000001",
+        "1111000010010000",
+        "0000000000010000",
+        "1110001100001000",
+        "0000000000000110",
+        "1110101010000111",
+    ];
+    assert_eq!(assemble(asm).unwrap(), expected.join("\n") + "\n");
+}
+
+#[test]
+fn test_comp_bits() {
+    let comp = |asm: &str| assemble(asm).unwrap()[3..10].to_string();
+    assert_eq!(comp("D=!D"), "0001101");
+    assert_eq!(comp("D=!A"), "0110001");
+    assert_eq!(comp("D=-D"), "0001111");
+    assert_eq!(comp("D=-A"), "0110011");
+    // Commutative operators accept either operand order
+    assert_eq!(comp("D=M+D"), comp("D=D+M"));
+    assert_eq!(comp("D=A&D"), comp("D=D&A"));
+    assert_eq!(comp("M=M|D"), comp("M=D|M"));
+}
+
+#[test]
+fn test_errors() {
+    let asm = "\
+@40000
+(LOOP
+D=Q
+X=D
+D;JXX
+@1abc
+(END)
+(END)
+@a-b
+@32767
+0;JMP
+";
+    let errors = assemble(asm).unwrap_err();
+    let kinds: Vec<(usize, ErrorKind)> = errors.iter().map(|e| (e.line, e.kind.clone())).collect();
+    assert_eq!(
+        kinds,
+        vec![
+            (1, ErrorKind::AddressOutOfRange("40000".into())),
+            (2, ErrorKind::MalformedLabel),
+            (3, ErrorKind::UnknownComp("Q".into())),
+            (4, ErrorKind::UnknownDest("X".into())),
+            (5, ErrorKind::UnknownJump("JXX".into())),
+            (6, ErrorKind::InvalidSymbol("1abc".into())),
+            (8, ErrorKind::DuplicateLabel("END".into())),
+            (9, ErrorKind::InvalidSymbol("a-b".into())),
+        ]
+    );
+    assert_eq!(errors[1].text, "(LOOP");
+    assert_eq!(
+        errors[2].to_string(),
+        "line 3: Unknown computation: \"Q\"\n  | D=Q"
+    );
+}
+
+#[test]
+fn test_rom_overflow() {
+    let asm = "D=0\n".repeat(32768);
+    assert!(assemble(&asm).is_ok());
+    let errors = assemble(&(asm + "@END\n(END)\n")).unwrap_err();
+    assert_eq!(errors.len(), 1);
+    assert_eq!(
+        (errors[0].line, &errors[0].kind),
+        (32769, &ErrorKind::RomOverflow)
+    );
+}
+
+#[test]
+fn test_symbols_and_listing() {
+    let asm = "@i\nM=1\n(LOOP)\n@i\nM=M+1\n@LOOP\n0;JMP\n(END)";
+    let parse_result = parser::parse(asm.to_string()).unwrap();
+    assert_eq!(
+        parse_result.symbols.to_string(),
+        "label LOOP 2\nlabel END 6\nvariable i 16\n"
+    );
+    let listing = code::generate_listing(&parse_result);
+    let expected = "    0  0000000000010000  @i
+    1  1110111111001000  M=1
+                         (LOOP)
+    2  0000000000010000  @i
+    3  1111110111001000  M=M+1
+    4  0000000000000010  @LOOP
+    5  1110101010000111  0;JMP
+                         (END)
+";
+    assert_eq!(listing, expected);
+}
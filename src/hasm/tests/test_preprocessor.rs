@@ -0,0 +1,229 @@
+use std::fs;
+
+use hasm::{
+    code,
+    error::{AsmError, ErrorKind, Expansion},
+    parser,
+};
+
+fn assemble(asm: &str) -> Result<String, Vec<AsmError>> {
+    parser::parse_with_macros(asm, None)
+        .map(code::generate_code)
+        .map_err(|errors| errors.0)
+}
+
+fn assert_same_code(with_macros: &str, plain: &str) {
+    let expected = code::generate_code(parser::parse(plain.to_string()).unwrap());
+    assert_eq!(assemble(with_macros).unwrap(), expected);
+}
+
+#[test]
+fn test_macro_expansion() {
+    let asm = "
+    .macro COPY from, to
+        @from
+        D=M
+        @to
+        M=D
+    .endm
+        COPY R0, R1
+        COPY R1, R2
+    ";
+    let plain = "
+        @R0
+        D=M
+        @R1
+        M=D
+        @R1
+        D=M
+        @R2
+        M=D
+    ";
+    assert_same_code(asm, plain);
+}
+
+#[test]
+fn test_local_labels() {
+    let asm = "
+    .macro ABS register
+        @register
+        D=M
+        @.positive
+        D;JGE
+        @register
+        M=-M
+    (.positive)
+    .endm
+        ABS R0
+        ABS R1
+    ";
+    let plain = "
+        @R0
+        D=M
+        @ABS.1.positive
+        D;JGE
+        @R0
+        M=-M
+    (ABS.1.positive)
+        @R1
+        D=M
+        @ABS.2.positive
+        D;JGE
+        @R1
+        M=-M
+    (ABS.2.positive)
+    ";
+    assert_same_code(asm, plain);
+}
+
+#[test]
+fn test_equ() {
+    let asm = "
+    .equ BUFFER 1024
+    .macro CLEAR address
+        @address
+        M=0
+    .endm
+        @BUFFER
+        D=A
+        CLEAR BUFFER
+    ";
+    let plain = "
+        @1024
+        D=A
+        @1024
+        M=0
+    ";
+    assert_same_code(asm, plain);
+}
+
+#[test]
+fn test_nested_macros() {
+    let asm = "
+    .macro SET register, value
+        @value
+        D=A
+        @register
+        M=D
+    .endm
+    .macro RESET
+        SET R0, 0
+        SET R1, 1
+    .endm
+        RESET
+    ";
+    let plain = "
+        @0
+        D=A
+        @R0
+        M=D
+        @1
+        D=A
+        @R1
+        M=D
+    ";
+    assert_same_code(asm, plain);
+}
+
+#[test]
+fn test_include() {
+    let dir = std::env::temp_dir().join(format!("hasm_include_{}", std::process::id()));
+    fs::create_dir_all(dir.join("lib")).unwrap();
+    fs::write(
+        dir.join("lib/macros.asm"),
+        ".macro INC register\n    @register\n    M=M+1\n.endm\n",
+    )
+    .unwrap();
+    let main = dir.join("Main.asm");
+    let asm = ".include \"lib/macros.asm\"\n    INC R3\n    D;JLT\n";
+    fs::write(&main, asm).unwrap();
+
+    let result = parser::parse_with_macros(asm, Some(&main)).map(code::generate_code);
+    let plain = "@R3\nM=M+1\nD;JLT";
+    let expected = code::generate_code(parser::parse(plain.to_string()).unwrap());
+    assert_eq!(result.unwrap(), expected);
+
+    fs::write(dir.join("lib/macros.asm"), ".include \"../Main.asm\"\n").unwrap();
+    let errors = parser::parse_with_macros(asm, Some(&main)).unwrap_err().0;
+    assert!(matches!(errors[0].kind, ErrorKind::Include(_)));
+    assert_eq!(errors[0].line, 1);
+    assert!(errors[0].file.as_ref().unwrap().ends_with("macros.asm"));
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_error_in_macro_body() {
+    let asm = "\
+.macro BAD register
+    @register
+    D=Q
+.endm
+    @R0
+    BAD R1";
+    let errors = assemble(asm).unwrap_err();
+    assert_eq!(errors.len(), 1);
+    assert_eq!(errors[0].line, 3);
+    assert_eq!(errors[0].kind, ErrorKind::UnknownComp("Q".into()));
+    assert_eq!(
+        errors[0].expansions,
+        vec![Expansion {
+            macro_name: "BAD".into(),
+            file: None,
+            line: 6,
+        }]
+    );
+    assert_eq!(
+        errors[0].to_string(),
+        "line 3: Unknown computation: \"Q\"\n  | D=Q\n  in macro BAD expanded at line 6"
+    );
+}
+
+#[test]
+fn test_directive_errors() {
+    let kinds = |asm: &str| -> Vec<(usize, ErrorKind)> {
+        assemble(asm)
+            .unwrap_err()
+            .into_iter()
+            .map(|error| (error.line, error.kind))
+            .collect()
+    };
+    assert_eq!(
+        kinds(".macro M a\n@a\n.endm\nM\nM 1, 2"),
+        vec![
+            (
+                4,
+                ErrorKind::MacroArguments {
+                    name: "M".into(),
+                    expected: 1,
+                    got: 0
+                }
+            ),
+            (
+                5,
+                ErrorKind::MacroArguments {
+                    name: "M".into(),
+                    expected: 1,
+                    got: 2
+                }
+            ),
+        ]
+    );
+    assert_eq!(
+        kinds("@0\n.macro M\n@0"),
+        vec![(2, ErrorKind::UnterminatedMacro("M".into()))]
+    );
+    assert_eq!(kinds(".endm"), vec![(1, ErrorKind::UnmatchedEndm)]);
+    assert_eq!(
+        kinds(".org 100"),
+        vec![(1, ErrorKind::UnknownDirective(".org".into()))]
+    );
+    assert_eq!(
+        kinds(".equ X"),
+        vec![(1, ErrorKind::InvalidDirective(".equ NAME value".into()))]
+    );
+    assert!(matches!(
+        kinds(".macro LOOP\nLOOP\n.endm\nLOOP")[0],
+        (2, ErrorKind::MacroRecursion(_))
+    ));
+}
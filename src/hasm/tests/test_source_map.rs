@@ -0,0 +1,57 @@
+use std::collections::BTreeMap;
+
+use hasm::{
+    code, parser,
+    source_map::{Location, SourceMap},
+};
+
+fn location(file: &str, line: usize) -> Location {
+    Location {
+        file: file.into(),
+        line,
+    }
+}
+
+#[test]
+fn test_format() {
+    let contents = "1 Main.vm:3\n2 Main.vm:3\n5 dir/Sys.vm:10\n";
+    let map = SourceMap::parse(contents).unwrap();
+    assert_eq!(map.get(2), Some(&location("Main.vm", 3)));
+    assert_eq!(map.at_address(4), Some(&location("dir/Sys.vm", 10)));
+    assert_eq!(map.get(3), None);
+    assert_eq!(map.to_string(), contents);
+
+    assert!(SourceMap::parse("1 Main.vm").is_err());
+    assert!(SourceMap::parse("x Main.vm:1").is_err());
+}
+
+#[test]
+fn test_compose() {
+    let hack = SourceMap::parse("1 Prog.asm:2\n2 Prog.asm:3\n3 Prog.asm:9\n4 Other.asm:1").unwrap();
+    let asm = SourceMap::parse("2 Main.vm:1\n3 Main.vm:4").unwrap();
+    let composed = hack.compose(&BTreeMap::from([("Prog.asm".to_string(), asm)]));
+    assert_eq!(
+        composed.to_string(),
+        "1 Main.vm:1\n2 Main.vm:4\n4 Other.asm:1\n"
+    );
+}
+
+#[test]
+fn test_assembler_map() {
+    let asm = "\
+.macro INC register
+    @register
+    M=M+1
+.endm
+(LOOP)
+    INC R0
+    @LOOP
+    0;JMP";
+    let parse_result = parser::parse_with_macros(asm, None).unwrap();
+    let map = code::generate_source_map(&parse_result, "Prog.asm");
+    let lines: Vec<usize> = (0..4)
+        .map(|address| map.at_address(address).unwrap().line)
+        .collect();
+    // Expanded instructions point at the macro call
+    assert_eq!(lines, vec![6, 6, 7, 8]);
+}
@@ -0,0 +1,52 @@
+use std::env;
+use std::error::Error;
+use std::fs;
+
+use n2t::config::{HeapConfig, HEAP_USAGE};
+use vm::heap::{self, HeapReport};
+
+fn read_heap(path: &str) -> Result<HeapReport, Box<dyn Error>> {
+    let contents =
+        fs::read_to_string(path).map_err(|e| format!("Couldn't read {}: {}", path, e))?;
+    let ram = emulator::dump::parse_dump(&contents)?;
+    Ok(heap::inspect(&ram))
+}
+
+/// Prints the report, returns whether the heap is fine.
+fn run(config: &HeapConfig) -> Result<bool, Box<dyn Error>> {
+    let report = read_heap(&config.dump)?;
+    print!("{}", report);
+    let mut ok = report.is_ok();
+    if let Some(since) = &config.since {
+        let leaked = report.allocated_since(&read_heap(since)?);
+        for block in &leaked {
+            println!(
+                "Not freed: block {} of {} words",
+                block.address, block.length
+            );
+        }
+        ok &= leaked.is_empty();
+    }
+    Ok(ok)
+}
+
+fn main() {
+    let args: Vec<String> = env::args().collect();
+    if args.iter().any(|arg| arg == "-h" || arg == "--help") {
+        print!("{}", HEAP_USAGE);
+        return;
+    }
+    let config = HeapConfig::new(&args).unwrap_or_else(|err| {
+        println!("Problem parsing arguments: {}\n\n{}", err, HEAP_USAGE);
+        std::process::exit(1);
+    });
+
+    match run(&config) {
+        Ok(true) => {}
+        Ok(false) => std::process::exit(1),
+        Err(e) => {
+            println!("Application error: {}", e);
+            std::process::exit(1);
+        }
+    }
+}
@@ -0,0 +1,20 @@
+use std::env;
+
+use n2t::config::{RunConfig, RUN_USAGE};
+
+fn main() {
+    let args: Vec<String> = env::args().collect();
+    if args.iter().any(|arg| arg == "-h" || arg == "--help") {
+        print!("{}", RUN_USAGE);
+        return;
+    }
+    let config = RunConfig::new(&args).unwrap_or_else(|err| {
+        println!("Problem parsing arguments: {}\n\n{}", err, RUN_USAGE);
+        std::process::exit(1);
+    });
+
+    if let Err(e) = n2t::headless::run(&config) {
+        println!("Application error: {}", e);
+        std::process::exit(1);
+    }
+}
@@ -0,0 +1,47 @@
+use std::env;
+use std::io::{self, BufRead, Write};
+
+use n2t::config::{Config, DEBUG_USAGE};
+use vm::debugger::Debugger;
+
+fn main() {
+    let args: Vec<String> = env::args().collect();
+    if args.iter().any(|arg| arg == "-h" || arg == "--help") {
+        print!("{}", DEBUG_USAGE);
+        return;
+    }
+    let config = Config::new(&args).unwrap_or_else(|err| {
+        println!("Problem parsing arguments: {}\n\n{}", err, DEBUG_USAGE);
+        std::process::exit(1);
+    });
+
+    let mut debugger = n2t::load_program(&config)
+        .and_then(|(program, info)| {
+            let mut debugger = Debugger::new(program, info);
+            debugger.bootstrap()?;
+            Ok(debugger)
+        })
+        .unwrap_or_else(|e| {
+            println!("Application error: {}", e);
+            std::process::exit(1);
+        });
+    println!("{}", debugger.run_command("where").unwrap_or_default());
+
+    let stdin = io::stdin();
+    let mut lines = stdin.lock().lines();
+    loop {
+        print!("(vmdbg) ");
+        io::stdout().flush().ok();
+        let Some(Ok(line)) = lines.next() else {
+            break;
+        };
+        if matches!(line.trim(), "q" | "quit") {
+            break;
+        }
+        match debugger.run_command(&line) {
+            Ok(output) if output.is_empty() => {}
+            Ok(output) => println!("{}", output),
+            Err(e) => println!("Error: {}", e),
+        }
+    }
+}
@@ -0,0 +1,285 @@
+use compiler::config::CompileOptions;
+use vm::config::TranslateOptions;
+use vm::debugger::Breakpoint;
+
+pub const N2T_USAGE: &str = "\
+Usage: n2t <directory | file.jack> [options] [compiler options]
+
+Builds a Jack program down to Hack machine code, writing `<program>.hack` next
+to the sources. The classes are compiled to VM code, which is translated to
+assembly and assembled.
+
+Options:
+  --os                  Compile the OS classes from src/os along with the
+                        program, a program class with the same name replaces
+                        the OS one
+  --stop-after=STAGE    Last stage to run, `vm`, `asm` or `hack` (default)
+  --emit=STAGE,...      Also write the artifacts of earlier stages
+  --compact             Use shared compare/call/return routines for a smaller ROM
+  --source-map          Write the Jack line of every ROM address to
+                        `<program>.map`
+  -h, --help            Print this help
+
+Any other option, e.g. -O2 or -Wall, is passed to the compiler.
+";
+
+pub const DEBUG_USAGE: &str = "\
+Usage: vmdbg <directory | file.jack | file.vm> [--os] [compiler options]
+
+Runs a program at the VM level under an interactive debugger. Jack sources are
+compiled first, so breakpoints and variables can use their lines and names.
+
+Options:
+  --os        Compile the OS classes from src/os along with the program, else
+              OS functions the program doesn't define are built in
+  -h, --help  Print this help
+";
+
+pub const RUN_USAGE: &str = "\
+Usage: jackrun <directory | file.jack | file.vm> [options] [--os] [compiler options]
+
+Runs a program without a display, on the VM interpreter or the CPU emulator,
+feeding the keyboard from a key script. Times in the script count VM steps, or
+CPU cycles with --hack.
+
+Options:
+  --keys=FILE     Key script, one `from-to key` press per line, e.g. `100-2000 RIGHT`
+  --steps=N       Stop after N VM steps or CPU cycles (default 10000000)
+  --idle=N        Stop when the RAM is the same N steps or cycles apart, after
+                  the last key press. Loops which come back to the same state,
+                  like Sys.halt, always stop
+  --hack          Run the assembled program on the CPU emulator
+  --screen=IMAGE  Write the screen after the run, as PPM or PBM if IMAGE ends
+                  with .ppm or .pbm, else PNG
+  --dump=FILE     Write the non-zero RAM words after the run
+  --report        Print the call stack, with each frame's arguments and locals,
+                  if the program calls Sys.error, hits a breakpoint or doesn't
+                  halt within the step limit (VM only)
+  --break=TARGET  Stop with a report on a function (Main.main), VM line
+                  (Main.vm:12) or Jack line (Main.jack:5), implies --report
+  --os            Compile the OS classes from src/os along with the program, else
+                  OS functions the program doesn't define are built in (VM only)
+  -h, --help      Print this help
+";
+
+pub const HEAP_USAGE: &str = "\
+Usage: heapcheck <DUMP> [--since=DUMP]
+
+Checks the heap of a RAM dump, as written by `jackrun --dump` or `emulator --dump`,
+and prints its free and allocated blocks. Exits with an error when the free list
+is broken or blocks overlap.
+
+Options:
+  --since=DUMP  Also list the blocks allocated since an earlier dump, e.g. taken
+                when the program started, and fail if there are any
+  -h, --help    Print this help
+";
+
+pub const DEFAULT_RUN_STEPS: u64 = 10_000_000;
+
+/// Pipeline stages, in order, named after the artifact each one produces.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Stage {
+    /// Jack to `.vm`
+    Compile,
+    /// VM code to `.asm`
+    Translate,
+    /// Assembly to `.hack`
+    Assemble,
+}
+
+impl Stage {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "compile" | "vm" => Some(Stage::Compile),
+            "translate" | "asm" => Some(Stage::Translate),
+            "assemble" | "hack" => Some(Stage::Assemble),
+            _ => None,
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Stage::Compile => "vm",
+            Stage::Translate => "asm",
+            Stage::Assemble => "hack",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Config {
+    pub source_path: String,
+    /// Compiles the OS classes from `src/os` along with the program, a
+    /// program class with the same name replaces the OS one.
+    pub link_os: bool,
+    /// Last stage to run, its artifacts are always written.
+    pub stop_after: Stage,
+    /// Artifacts of the earlier stages to write as well.
+    pub emit: Vec<Stage>,
+    /// Writes the Jack line of every ROM address to `<program>.map`.
+    pub source_map: bool,
+    pub translate_options: TranslateOptions,
+    pub options: CompileOptions,
+}
+
+impl Config {
+    /// `n2t <source> [--os] [--stop-after=<stage>] [--emit=vm,asm,hack] [--compact]
+    /// [--source-map]`, any other option is passed to the compiler.
+    pub fn new(args: &[String]) -> Result<Self, Box<dyn std::error::Error>> {
+        if args.len() < 2 {
+            return Err("not enough arguments".into());
+        }
+
+        let source_path = args[1].clone();
+        let mut link_os = false;
+        let mut stop_after = Stage::Assemble;
+        let mut emit = vec![];
+        let mut source_map = false;
+        let mut translate_options = TranslateOptions::default();
+        let mut compiler_args = vec![];
+        for arg in &args[2..] {
+            let stage = |name: &str| {
+                Stage::from_name(name).ok_or_else(|| format!("unknown stage: {}", name))
+            };
+            if arg == "--os" {
+                link_os = true;
+            } else if arg == "--compact" {
+                translate_options.compact = true;
+            } else if arg == "--source-map" {
+                source_map = true;
+            } else if let Some(name) = arg.strip_prefix("--stop-after=") {
+                stop_after = stage(name)?;
+            } else if let Some(names) = arg.strip_prefix("--emit=") {
+                for name in names.split(',') {
+                    emit.push(stage(name)?);
+                }
+            } else {
+                compiler_args.push(arg.clone());
+            }
+        }
+        Ok(Config {
+            source_path,
+            link_os,
+            stop_after,
+            emit,
+            source_map,
+            translate_options,
+            options: CompileOptions::from_args(&compiler_args)?,
+        })
+    }
+
+    /// Whether the artifacts of `stage` should be written.
+    pub fn writes(&self, stage: Stage) -> bool {
+        stage == self.stop_after || (stage < self.stop_after && self.emit.contains(&stage))
+    }
+}
+
+pub struct RunConfig {
+    /// Build of the program, only compiled unless `hack` is set.
+    pub build: Config,
+    pub keys: Option<String>,
+    /// VM steps, or CPU cycles with `hack`.
+    pub max_steps: u64,
+    /// See `vm::halt::HaltDetector`.
+    pub idle_steps: Option<u64>,
+    pub hack: bool,
+    pub screen: Option<String>,
+    pub dump: Option<String>,
+    /// Report crashes, see `vm::crash`.
+    pub report: bool,
+    pub breakpoints: Vec<Breakpoint>,
+}
+
+impl RunConfig {
+    /// `jackrun <source> [--keys=<file>] [--steps=<n>] [--idle=<n>] [--hack] [--screen=<image>]
+    /// [--dump=<file>] [--report] [--break=<target>]...`, other arguments are
+    /// handled by `Config::new`.
+    pub fn new(args: &[String]) -> Result<Self, Box<dyn std::error::Error>> {
+        let mut keys = None;
+        let mut max_steps = DEFAULT_RUN_STEPS;
+        let mut idle_steps = None;
+        let mut hack = false;
+        let mut screen = None;
+        let mut dump = None;
+        let mut report = false;
+        let mut breakpoints = vec![];
+        let mut build_args = vec![];
+        for arg in args {
+            if arg == "--hack" {
+                hack = true;
+            } else if let Some(file) = arg.strip_prefix("--keys=") {
+                keys = Some(file.to_string());
+            } else if let Some(n) = arg.strip_prefix("--steps=") {
+                max_steps = n
+                    .parse()
+                    .map_err(|_| format!("invalid step limit: {}", n))?;
+            } else if let Some(n) = arg.strip_prefix("--idle=") {
+                idle_steps = Some(
+                    n.parse()
+                        .ok()
+                        .filter(|n| *n > 0)
+                        .ok_or_else(|| format!("invalid idle steps: {}", n))?,
+                );
+            } else if let Some(file) = arg.strip_prefix("--screen=") {
+                screen = Some(file.to_string());
+            } else if let Some(file) = arg.strip_prefix("--dump=") {
+                dump = Some(file.to_string());
+            } else if arg == "--report" {
+                report = true;
+            } else if let Some(target) = arg.strip_prefix("--break=") {
+                breakpoints.push(
+                    Breakpoint::parse(target)
+                        .ok_or_else(|| format!("invalid breakpoint: {}", target))?,
+                );
+                report = true;
+            } else {
+                build_args.push(arg.clone());
+            }
+        }
+        if hack && report {
+            return Err("crash reports need the VM interpreter, not --hack".into());
+        }
+        Ok(RunConfig {
+            build: Config::new(&build_args)?,
+            keys,
+            max_steps,
+            idle_steps,
+            hack,
+            screen,
+            dump,
+            report,
+            breakpoints,
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct HeapConfig {
+    pub dump: String,
+    /// Earlier dump, to list the blocks allocated since.
+    pub since: Option<String>,
+}
+
+impl HeapConfig {
+    /// `heapcheck <dump> [--since=<dump>]`
+    pub fn new(args: &[String]) -> Result<Self, Box<dyn std::error::Error>> {
+        let mut dump = None;
+        let mut since = None;
+        for arg in &args[1..] {
+            if let Some(file) = arg.strip_prefix("--since=") {
+                since = Some(file.to_string());
+            } else if arg.starts_with("--") {
+                return Err(format!("unknown option: {}", arg).into());
+            } else if dump.is_none() {
+                dump = Some(arg.clone());
+            } else {
+                return Err(format!("unexpected argument: {}", arg).into());
+            }
+        }
+        Ok(HeapConfig {
+            dump: dump.ok_or("no dump file specified")?,
+            since,
+        })
+    }
+}
@@ -0,0 +1,149 @@
+//! Runs programs without a display or a user, feeding `KBD` from a key script.
+
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+
+use emulator::cpu::{Cpu, MemoryMap, ROM_SIZE};
+use emulator::keyboard::KeyScript;
+use emulator::screen::Screen;
+use emulator::{dump, rom};
+use vm::debugger::Debugger;
+use vm::halt::{HaltDetector, HaltReason};
+use vm::interpreter::Interpreter;
+
+use crate::config::RunConfig;
+
+/// Runs until the program halts, loops forever, stays idle for `idle_steps`
+/// commands after the last key press if given, or `max_steps` commands were
+/// executed, with `KBD` holding the key of the script at the current step
+/// before each command.
+pub fn run_vm(
+    interpreter: &mut Interpreter,
+    keys: &KeyScript,
+    max_steps: u64,
+    idle_steps: Option<u64>,
+) -> Result<HaltReason, Box<dyn Error>> {
+    let keyboard = MemoryMap::default().keyboard as usize;
+    let mut detector = HaltDetector::new(idle_steps);
+    detector.input_until = keys.end();
+    for _ in 0..max_steps {
+        interpreter.poke(keyboard, keys.key_at(interpreter.steps));
+        if let Some(reason) = detector.check(interpreter) {
+            return Ok(reason);
+        }
+        interpreter.step()?;
+    }
+    Ok(detector
+        .check(interpreter)
+        .unwrap_or(HaltReason::StepLimit(max_steps)))
+}
+
+/// Same as `run_vm` on the CPU emulator, counting cycles. Endless loops are
+/// the `@X` `0;JMP` ones at address `X`, found at once, and the ones which
+/// come back to the same state, see `HaltDetector::check_repeat`.
+pub fn run_cpu(
+    cpu: &mut Cpu,
+    keys: &KeyScript,
+    max_cycles: u64,
+    idle_cycles: Option<u64>,
+) -> HaltReason {
+    let mut detector = HaltDetector::new(idle_cycles);
+    detector.input_until = keys.end();
+    for _ in 0..max_cycles {
+        cpu.set_key(keys.key_at(cpu.cycles));
+        if cpu.is_self_jump() {
+            return HaltReason::Loop {
+                pc: cpu.pc as usize,
+            };
+        }
+        if let Some(reason) = detector.check_idle(cpu.cycles, cpu.ram()) {
+            return reason;
+        }
+        let pc = cpu.pc;
+        cpu.step();
+        if cpu.pc <= pc {
+            let registers = [cpu.a, cpu.d];
+            if let Some(reason) =
+                detector.check_repeat(cpu.cycles, cpu.pc as usize, &registers, cpu.ram())
+            {
+                return reason;
+            }
+        }
+    }
+    HaltReason::StepLimit(max_cycles)
+}
+
+/// Builds and runs a program on the VM interpreter, or on the CPU emulator
+/// with `config.hack`, then writes the requested screen image and RAM dump.
+/// With `config.report`, a crash is reported and fails the run.
+pub fn run(config: &RunConfig) -> Result<(), Box<dyn Error>> {
+    let keys = match &config.keys {
+        Some(path) => KeyScript::parse(&fs::read_to_string(path)?)?,
+        None => KeyScript::default(),
+    };
+    let mut crash = None;
+    let ram = if config.hack {
+        let build = crate::build(&config.build)?;
+        for warning in &build.warnings {
+            eprintln!("{}\n", warning);
+        }
+        let hack = build.hack.ok_or("No Hack code was generated")?;
+        let program = rom::parse_hack(&hack)?;
+        if program.len() > ROM_SIZE {
+            return Err(format!(
+                "{} instructions don't fit in the {} word ROM, try --compact",
+                program.len(),
+                ROM_SIZE
+            )
+            .into());
+        }
+        let mut cpu = Cpu::new(&program);
+        let reason = run_cpu(&mut cpu, &keys, config.max_steps, config.idle_steps);
+        println!("Cycles: {}", cpu.cycles);
+        println!("Stopped: {}", reason);
+        cpu.ram().to_vec()
+    } else if config.report {
+        let (program, info) = crate::load_program(&config.build)?;
+        let mut debugger = Debugger::new(program, info);
+        debugger.breakpoints = config.breakpoints.clone();
+        debugger.bootstrap()?;
+        let keyboard = MemoryMap::default().keyboard as usize;
+        let report = debugger.run_to_crash(config.max_steps, |interpreter| {
+            interpreter.poke(keyboard, keys.key_at(interpreter.steps));
+        })?;
+        println!("Steps: {}", debugger.interpreter.steps);
+        if let Some(report) = &report {
+            print!("{}", report);
+        }
+        crash = report.map(|report| report.reason);
+        debugger.interpreter.ram
+    } else {
+        let (program, _) = crate::load_program(&config.build)?;
+        let mut interpreter = Interpreter::new(program);
+        interpreter.bootstrap()?;
+        let reason = run_vm(&mut interpreter, &keys, config.max_steps, config.idle_steps)?;
+        println!("Steps: {}", interpreter.steps);
+        match reason {
+            HaltReason::Loop { pc } => {
+                let command = &interpreter.program.commands[pc];
+                println!(
+                    "Stopped: {} ({}.vm:{})",
+                    reason, command.module, command.line
+                );
+            }
+            _ => println!("Stopped: {}", reason),
+        }
+        interpreter.ram
+    };
+    if let Some(path) = &config.screen {
+        Screen::from_ram(&ram)?.save(Path::new(path))?;
+    }
+    if let Some(path) = &config.dump {
+        fs::write(path, dump::write_dump(&ram))?;
+    }
+    match crash {
+        Some(reason) => Err(reason.to_string().into()),
+        None => Ok(()),
+    }
+}
@@ -0,0 +1,222 @@
+pub mod config;
+pub mod headless;
+
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use compiler::{compiler_cli, diagnostic::Diagnostic, input, os};
+use config::{Config, Stage};
+use hasm::source_map::SourceMap;
+use vm::debugger::{DebugInfo, Variable};
+use vm::interpreter::Program;
+
+/// Artifacts of a build, stages after `Config::stop_after` are `None`.
+#[derive(Debug)]
+pub struct Build {
+    /// Program name, used for the bootstrap code and the output files.
+    pub name: String,
+    /// VM code of every class, by class name.
+    pub vm: Vec<(String, String)>,
+    /// Jack lines and variables of the VM code.
+    pub debug_info: DebugInfo,
+    pub asm: Option<String>,
+    pub hack: Option<String>,
+    /// Jack line of every ROM address of `hack`.
+    pub source_map: Option<SourceMap>,
+    pub warnings: Vec<Diagnostic>,
+}
+
+fn file_stem(path: &Path) -> Result<String, Box<dyn Error>> {
+    Ok(path
+        .file_stem()
+        .and_then(|s| s.to_str())
+        .ok_or("Invalid filename")?
+        .to_string())
+}
+
+/// Files with their contents.
+type Sources = Vec<(PathBuf, String)>;
+
+/// Sources of the program, followed by the OS classes it doesn't define if
+/// `config.link_os` is set, and the files of those OS classes.
+fn read_sources(config: &Config) -> Result<(Sources, Vec<PathBuf>), Box<dyn Error>> {
+    let mut sources = input::read_sources(&config.source_path)?;
+    let mut os_files = vec![];
+    if config.link_os {
+        let program_classes = sources
+            .iter()
+            .map(|(file, _)| file_stem(file))
+            .collect::<Result<Vec<_>, _>>()?;
+        for (name, source) in os::SOURCES.iter() {
+            if !program_classes.iter().any(|class| class == name) {
+                let path = PathBuf::from(format!("src/os/{}.jack", name));
+                os_files.push(path.clone());
+                sources.push((path, source.to_string()));
+            }
+        }
+    }
+    Ok((sources, os_files))
+}
+
+/// The debugger's view of a variable of the compiler.
+fn debug_variable(variable: compiler::compiler::Variable) -> Variable {
+    Variable {
+        name: variable.name,
+        typ: variable.typ,
+        segment: variable.segment,
+        index: variable.index,
+    }
+}
+
+/// Runs the pipeline in memory, up to `config.stop_after`.
+pub fn build(config: &Config) -> Result<Build, Box<dyn Error>> {
+    let name = vm::config::program_name(Path::new(&config.source_path));
+    let (sources, os_files) = read_sources(config)?;
+    let mut output = compiler_cli::compile_sources(sources, &config.options)?;
+    // Warnings about the linked OS classes aren't the program's to fix
+    output.warnings.retain(|warning| {
+        warning
+            .file
+            .as_ref()
+            .is_none_or(|file| !os_files.contains(file))
+    });
+    let mut vm = vec![];
+    let mut debug_info = DebugInfo::default();
+    for (file, result) in output.files {
+        let class = file_stem(&file)?;
+        debug_info
+            .source_maps
+            .insert(class.clone(), result.source_map);
+        for (function, variables) in result.variables {
+            let variables = variables.into_iter().map(debug_variable).collect();
+            debug_info.variables.insert(function, variables);
+        }
+        vm.push((class, result.vm_code));
+    }
+
+    let translated = if config.stop_after >= Stage::Translate {
+        Some(vm::translate_with_map(
+            &vm,
+            &name,
+            &config.translate_options,
+        ))
+    } else {
+        None
+    };
+    let (hack, source_map) = match &translated {
+        Some((asm, asm_map)) if config.stop_after >= Stage::Assemble => {
+            let parse_result = hasm::parser::parse(asm.clone())?;
+            let asm_file = format!("{}.asm", name);
+            let hack_map = hasm::code::generate_source_map(&parse_result, &asm_file);
+            let vm_maps = debug_info
+                .source_maps
+                .iter()
+                .map(|(class, map)| (format!("{}.vm", class), map.clone()))
+                .collect();
+            let source_map = hack_map
+                .compose(&BTreeMap::from([(asm_file, asm_map.clone())]))
+                .compose(&vm_maps);
+            let hack = hasm::code::generate_code(parse_result);
+            (Some(hack), Some(source_map))
+        }
+        _ => (None, None),
+    };
+    Ok(Build {
+        name,
+        vm,
+        debug_info,
+        asm: translated.map(|(asm, _)| asm),
+        hack,
+        source_map,
+        warnings: output.warnings,
+    })
+}
+
+fn has_jack_sources(source_path: &Path) -> bool {
+    match source_path.read_dir() {
+        Ok(entries) => entries
+            .filter_map(Result::ok)
+            .any(|entry| entry.path().extension().is_some_and(|ext| ext == "jack")),
+        Err(_) => source_path.extension().is_some_and(|ext| ext == "jack"),
+    }
+}
+
+/// Compiles Jack sources for the VM interpreter, keeping their debug info,
+/// or loads `.vm` files as is. OS functions the program doesn't define are
+/// built in.
+pub fn load_program(config: &Config) -> Result<(Program, DebugInfo), Box<dyn Error>> {
+    if !has_jack_sources(Path::new(&config.source_path)) {
+        let mut program = Program::from_path(Path::new(&config.source_path))?;
+        program.link_builtins();
+        return Ok((program, DebugInfo::default()));
+    }
+    let config = Config {
+        stop_after: Stage::Compile,
+        ..config.clone()
+    };
+    let build = build(&config)?;
+    for warning in &build.warnings {
+        eprintln!("{}\n", warning);
+    }
+    let modules: Vec<(&str, &str)> = build
+        .vm
+        .iter()
+        .map(|(class, vm_code)| (class.as_str(), vm_code.as_str()))
+        .collect();
+    let mut program = Program::from_modules(&modules)?;
+    program.link_builtins();
+    Ok((program, build.debug_info))
+}
+
+/// Directory for the output files: the source directory, or the directory
+/// of a single source file.
+fn output_dir(source_path: &Path) -> PathBuf {
+    if source_path.is_dir() {
+        source_path.to_path_buf()
+    } else {
+        source_path
+            .parent()
+            .map(Path::to_path_buf)
+            .unwrap_or_default()
+    }
+}
+
+pub fn run(config: &Config) -> Result<(), Box<dyn Error>> {
+    let build = build(config)?;
+    for warning in &build.warnings {
+        eprintln!("{}\n", warning);
+    }
+
+    let dir = output_dir(Path::new(&config.source_path));
+    let program_path = |stage: Stage| dir.join(format!("{}.{}", build.name, stage.extension()));
+    if config.writes(Stage::Compile) {
+        for (class, vm_code) in &build.vm {
+            fs::write(dir.join(format!("{}.vm", class)), vm_code)?;
+        }
+    }
+    if let Some(asm) = &build.asm {
+        if config.writes(Stage::Translate) {
+            fs::write(program_path(Stage::Translate), asm)?;
+        }
+        let size = vm::rom_size(asm);
+        if size > vm::ROM_SIZE {
+            println!(
+                "Warning: {} instructions don't fit in the {} word ROM",
+                size,
+                vm::ROM_SIZE
+            );
+        }
+    }
+    if let Some(hack) = &build.hack {
+        fs::write(program_path(Stage::Assemble), hack)?;
+    }
+    if let Some(source_map) = build.source_map.filter(|_| config.source_map) {
+        fs::write(
+            dir.join(format!("{}.map", build.name)),
+            source_map.to_string(),
+        )?;
+    }
+    Ok(())
+}
@@ -0,0 +1,21 @@
+use std::env;
+
+use n2t::config::{self, N2T_USAGE};
+use n2t::run;
+
+fn main() {
+    let args: Vec<String> = env::args().collect();
+    if args.iter().any(|arg| arg == "-h" || arg == "--help") {
+        print!("{}", N2T_USAGE);
+        return;
+    }
+    let config = config::Config::new(&args).unwrap_or_else(|err| {
+        println!("Problem parsing arguments: {}\n\n{}", err, N2T_USAGE);
+        std::process::exit(1);
+    });
+
+    if let Err(e) = run(&config) {
+        println!("Application error: {}", e);
+        std::process::exit(1);
+    }
+}
@@ -0,0 +1,158 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use emulator::{cpu::Cpu, dump, keyboard::KeyScript, rom, screen::Screen};
+use n2t::{config::RunConfig, headless, load_program};
+use vm::halt::HaltReason;
+use vm::interpreter::Interpreter;
+
+const READ_INT: &str = "
+class Main {
+    function void main() {
+        var int n;
+        let n = Keyboard.readInt(\"n? \");
+        do Memory.poke(8000, n + 1);
+        return;
+    }
+}
+";
+
+fn program_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(name);
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(dir.join("Main.jack"), READ_INT).unwrap();
+    dir
+}
+
+fn run_config(dir: &Path, args: &[&str]) -> RunConfig {
+    let args: Vec<String> = ["jackrun", dir.to_str().unwrap()]
+        .iter()
+        .chain(args)
+        .map(|arg| arg.to_string())
+        .collect();
+    RunConfig::new(&args).unwrap()
+}
+
+#[test]
+fn test_read_int() {
+    let dir = program_dir("n2t_headless_read_int");
+    let keys = KeyScript::parse(
+        "
+        // types 12, erases the 2 and types 3
+        1000000-1100000 1
+        1200000-1300000 2
+        1400000-1500000 BACKSPACE
+        1600000-1700000 3
+        1800000-1900000 ENTER
+        ",
+    )
+    .unwrap();
+    // With the Jack OS, and with the built-in one
+    for args in [["--os"].as_slice(), &[]] {
+        let (program, _) = load_program(&run_config(&dir, args).build).unwrap();
+        let mut interpreter = Interpreter::new(program);
+        interpreter.bootstrap().unwrap();
+        let reason = headless::run_vm(&mut interpreter, &keys, 900_000, Some(1000)).unwrap();
+        assert_eq!(reason, HaltReason::StepLimit(900_000));
+        assert_eq!(interpreter.peek(8000), 0, "Keys aren't pressed yet");
+        let reason = headless::run_vm(&mut interpreter, &keys, 2_000_000, None).unwrap();
+        assert_eq!(interpreter.peek(8000), 14);
+        // The Jack OS `Sys.halt` loops, the built-in one halts
+        match args {
+            ["--os"] => assert!(matches!(reason, HaltReason::Loop { .. })),
+            _ => assert_eq!(reason, HaltReason::Halted),
+        }
+        assert!(interpreter.steps < 2_900_000);
+    }
+}
+
+#[test]
+fn test_run_on_cpu() {
+    let dir = program_dir("n2t_headless_cpu");
+    let keys = dir.join("keys.txt");
+    fs::write(&keys, "10000000-11000000 4\n12000000-13000000 NEWLINE\n").unwrap();
+    let dump = dir.join("ram.txt");
+    let screen = dir.join("screen.ppm");
+    let config = run_config(
+        &dir,
+        &[
+            "--os",
+            "--compact",
+            "--hack",
+            "--steps=20000000",
+            &format!("--keys={}", keys.display()),
+            &format!("--dump={}", dump.display()),
+            &format!("--screen={}", screen.display()),
+        ],
+    );
+    headless::run(&config).unwrap();
+    let ram = dump::parse_dump(&fs::read_to_string(dump).unwrap()).unwrap();
+    assert_eq!(ram[8000], 5);
+    let screen = Screen::from_ppm(&fs::read(screen).unwrap()).unwrap();
+    assert_eq!(Screen::from_ram(&ram).unwrap(), screen);
+}
+
+#[test]
+fn test_os_halt_on_cpu() {
+    let dir = std::env::temp_dir().join(format!("n2t_headless_halt_{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(
+        dir.join("Main.jack"),
+        "class Main { function void main() { do Memory.poke(8000, 1); return; } }",
+    )
+    .unwrap();
+    // `Sys.halt` of the Jack OS loops on a constant condition at every level
+    for level in ["-O0", "-O1", "-O2"] {
+        let config = run_config(&dir, &["--os", "--compact", "--hack", level]);
+        let build = n2t::build(&config.build).unwrap();
+        let program = rom::parse_hack(&build.hack.unwrap()).unwrap();
+        let mut cpu = Cpu::new(&program);
+        let reason = headless::run_cpu(&mut cpu, &KeyScript::default(), 30_000_000, None);
+        assert!(
+            matches!(reason, HaltReason::Loop { .. }),
+            "{}: {}",
+            level,
+            reason
+        );
+        assert_eq!(cpu.ram()[8000], 1);
+        assert!(cpu.cycles < 10_000_000, "{}: {} cycles", level, cpu.cycles);
+    }
+    fs::remove_dir_all(dir).unwrap();
+}
+
+const CRASH: &str = "
+class Main {
+    function void main() {
+        do Main.check(3);
+        return;
+    }
+
+    function void check(int n) {
+        var int doubled;
+        let doubled = n + n;
+        do Sys.error(doubled);
+        return;
+    }
+}
+";
+
+#[test]
+fn test_crash_report() {
+    let dir = std::env::temp_dir().join("n2t_headless_crash");
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(dir.join("Main.jack"), CRASH).unwrap();
+    for args in [["--report", "--os"].as_slice(), &["--report"]] {
+        let err = headless::run(&run_config(&dir, args)).unwrap_err();
+        assert_eq!(err.to_string(), "Sys.error called with code 6");
+    }
+    let err = headless::run(&run_config(&dir, &["--break=Main.jack:10"])).unwrap_err();
+    assert_eq!(err.to_string(), "Breakpoint Main.jack:10 hit");
+
+    let args: Vec<String> = ["jackrun", dir.to_str().unwrap(), "--hack", "--report"]
+        .iter()
+        .map(|arg| arg.to_string())
+        .collect();
+    assert!(RunConfig::new(&args).is_err());
+}
@@ -0,0 +1,117 @@
+use emulator::{cpu::Cpu, rom};
+use n2t::{
+    build,
+    config::{Config, Stage},
+};
+
+fn config(args: &[&str]) -> Config {
+    let math_test = concat!(env!("CARGO_MANIFEST_DIR"), "/../os/MathTest");
+    let args: Vec<String> = ["n2t", math_test]
+        .iter()
+        .chain(args)
+        .map(|arg| arg.to_string())
+        .collect();
+    Config::new(&args).unwrap()
+}
+
+#[test]
+fn test_build_runs_on_emulator() {
+    let build = build(&config(&["--os", "--compact", "-O2"])).unwrap();
+    let hack = build.hack.unwrap();
+    let mut cpu = Cpu::new(&rom::parse_hack(&hack).unwrap());
+    cpu.run(3_000_000);
+    let results: Vec<i16> = (8000..8014).map(|addr| cpu.peek(addr)).collect();
+    assert_eq!(
+        results,
+        vec![6, -180, -18000, -18000, 0, 3, -3000, 0, 3, 181, 123, 123, 27, 32767]
+    );
+}
+
+#[test]
+fn test_stop_after_compile() {
+    let build = build(&config(&["--os", "--stop-after=compile"])).unwrap();
+    assert!(build.asm.is_none() && build.hack.is_none());
+    let classes: Vec<&str> = build.vm.iter().map(|(name, _)| name.as_str()).collect();
+    assert_eq!(classes[0], "Main");
+    assert!(classes.contains(&"Math") && classes.contains(&"Sys"));
+}
+
+#[test]
+fn test_os_isnt_linted() {
+    let build = build(&config(&["--os", "-Wall", "--stop-after=compile"])).unwrap();
+    let files: Vec<_> = build
+        .warnings
+        .iter()
+        .filter_map(|w| w.file.as_ref())
+        .collect();
+    assert!(files
+        .iter()
+        .all(|file| file.ends_with("MathTest/Main.jack")));
+}
+
+#[test]
+fn test_without_os() {
+    let build = build(&config(&["--stop-after=translate"])).unwrap();
+    assert_eq!(build.name, "MathTest");
+    assert_eq!(build.vm.len(), 1);
+    assert!(build.asm.is_some() && build.hack.is_none());
+}
+
+#[test]
+fn test_written_artifacts() {
+    let config = config(&["--stop-after=translate", "--emit=vm,hack"]);
+    assert!(config.writes(Stage::Compile));
+    assert!(config.writes(Stage::Translate));
+    assert!(!config.writes(Stage::Assemble));
+
+    let args = ["n2t".to_string(), "Main".into(), "--stop-after=link".into()];
+    assert!(Config::new(&args).is_err());
+}
+
+#[test]
+fn test_source_map() {
+    let build = build(&config(&["--os", "--compact", "-O2"])).unwrap();
+    let map = build.source_map.unwrap();
+    let hack_lines = build.hack.unwrap().lines().count();
+    // The bootstrap code isn't mapped, all the rest comes from Jack
+    assert!(map.at_address(0).is_none());
+    assert!(map.lines.len() > hack_lines * 9 / 10);
+    let files: std::collections::BTreeSet<&str> = map
+        .lines
+        .values()
+        .map(|location| location.file.as_str())
+        .collect();
+    assert!(files
+        .iter()
+        .any(|file| file.ends_with("MathTest/Main.jack")));
+    assert!(files.contains("src/os/Math.jack"));
+    // `let r = 8000;` is the first statement of `Main.main`
+    let main = map
+        .lines
+        .values()
+        .find(|location| location.file.ends_with("Main.jack") && location.line != 10)
+        .unwrap();
+    assert_eq!(main.line, 13);
+}
+
+#[test]
+fn test_debug_info() {
+    let build = build(&config(&["--os", "--stop-after=compile"])).unwrap();
+    let modules: Vec<(&str, &str)> = build
+        .vm
+        .iter()
+        .map(|(class, vm_code)| (class.as_str(), vm_code.as_str()))
+        .collect();
+    let program = vm::interpreter::Program::from_modules(&modules).unwrap();
+    let mut debugger = vm::debugger::Debugger::new(program, build.debug_info);
+    debugger.bootstrap().unwrap();
+
+    debugger.run_command("break Main.jack:15").unwrap();
+    let stop = debugger.run_command("continue").unwrap();
+    assert!(stop.starts_with("Breakpoint 1: Main.jack:15\n"), "{}", stop);
+    assert!(stop.ends_with("MathTest/Main.jack:15)"), "{}", stop);
+    assert_eq!(debugger.run_command("print r").unwrap(), "r: Array = 8000");
+    let backtrace = debugger.run_command("backtrace").unwrap();
+    assert!(backtrace.starts_with("#0 Main.main at Main.vm:"));
+    assert!(backtrace.contains("   local r: Array = 8000\n#1 Sys.init"));
+}
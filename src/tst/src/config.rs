@@ -0,0 +1,14 @@
+pub struct Config {
+    pub script_path: String,
+}
+
+impl Config {
+    pub fn new(args: &[String]) -> Result<Self, &'static str> {
+        if args.len() < 2 {
+            return Err("not enough arguments");
+        }
+
+        let script_path = args[1].clone();
+        Ok(Config { script_path })
+    }
+}
@@ -0,0 +1,14 @@
+pub mod config;
+pub mod runner;
+pub mod script;
+
+use std::error::Error;
+use std::path;
+
+use config::Config;
+
+pub fn run(config: &Config) -> Result<(), Box<dyn Error>> {
+    let runner = runner::run_file(path::Path::new(&config.script_path), None)?;
+    println!("End of script - {} output lines", runner.output.len());
+    Ok(())
+}
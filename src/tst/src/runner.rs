@@ -0,0 +1,328 @@
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use emulator::{cpu::Cpu, rom};
+use vm::interpreter::{self, Interpreter, Program};
+
+use crate::script::{Command, OutputColumn, Variable};
+
+type Res<T = ()> = Result<T, Box<dyn Error>>;
+
+pub enum Machine {
+    Vm(Box<Interpreter>),
+    Cpu(Box<Cpu>),
+}
+
+impl Machine {
+    fn address(&self, variable: &Variable) -> Option<usize> {
+        let index = variable.index.map(|index| index as usize);
+        match (self, variable.name.as_str(), index) {
+            (_, "RAM", Some(index)) => Some(index),
+            (Machine::Vm(vm), name, Some(index)) => {
+                let base = |pointer: usize| vm.ram[pointer] as u16 as usize;
+                Some(match name {
+                    "local" => base(interpreter::LCL) + index,
+                    "argument" => base(interpreter::ARG) + index,
+                    "this" => base(interpreter::THIS) + index,
+                    "that" => base(interpreter::THAT) + index,
+                    "temp" => interpreter::TEMP_BASE + index,
+                    "pointer" => interpreter::THIS + index,
+                    _ => return None,
+                })
+            }
+            (Machine::Vm(_), name, None) => Some(match name {
+                "sp" => interpreter::SP,
+                "local" => interpreter::LCL,
+                "argument" => interpreter::ARG,
+                "this" => interpreter::THIS,
+                "that" => interpreter::THAT,
+                _ => return None,
+            }),
+            _ => None,
+        }
+    }
+
+    pub fn get(&self, variable: &Variable) -> Res<i16> {
+        if let Some(address) = self.address(variable) {
+            return Ok(match self {
+                Machine::Vm(vm) => vm.peek(address),
+                Machine::Cpu(cpu) => cpu.peek(address as u16),
+            });
+        }
+        match (self, variable.name.as_str()) {
+            (Machine::Cpu(cpu), "A") => Ok(cpu.a),
+            (Machine::Cpu(cpu), "D") => Ok(cpu.d),
+            (Machine::Cpu(cpu), "PC") => Ok(cpu.pc as i16),
+            (Machine::Cpu(cpu), "time") => Ok(cpu.cycles as i16),
+            _ => Err(format!("Unknown variable: {}", variable).into()),
+        }
+    }
+
+    pub fn set(&mut self, variable: &Variable, value: i16) -> Res {
+        if let Some(address) = self.address(variable) {
+            match self {
+                Machine::Vm(vm) => vm.poke(address, value),
+                Machine::Cpu(cpu) => cpu.poke(address as u16, value),
+            };
+            return Ok(());
+        }
+        match (self, variable.name.as_str()) {
+            (Machine::Cpu(cpu), "A") => cpu.a = value,
+            (Machine::Cpu(cpu), "D") => cpu.d = value,
+            (Machine::Cpu(cpu), "PC") => cpu.pc = value as u16,
+            _ => return Err(format!("Can't set variable: {}", variable).into()),
+        };
+        Ok(())
+    }
+}
+
+/// First line where the output differs from the compare file, both counted from 1.
+#[derive(Debug)]
+pub struct ComparisonFailure {
+    pub line: usize,
+    pub column: usize,
+    pub column_name: String,
+    pub expected: String,
+    pub actual: String,
+}
+
+impl std::fmt::Display for ComparisonFailure {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Comparison failure at line {}, column {} ({}): expected {:?}, got {:?}",
+            self.line,
+            self.column,
+            self.column_name,
+            self.expected,
+            self.actual
+        )
+    }
+}
+
+impl Error for ComparisonFailure {}
+
+fn format_header(column: &OutputColumn) -> String {
+    let width = column.pad_left + column.len + column.pad_right;
+    let name = column.variable.to_string();
+    if name.len() >= width {
+        name[..width].to_string()
+    } else {
+        let left = (width - name.len()) / 2;
+        format!(
+            "{:left$}{}{:right$}",
+            "",
+            name,
+            "",
+            left = left,
+            right = width - name.len() - left
+        )
+    }
+}
+
+fn format_value(column: &OutputColumn, value: i16) -> String {
+    let body = match column.format {
+        'X' => format!("{:04X}", value as u16),
+        'B' => format!("{:016b}", value as u16),
+        _ => value.to_string(),
+    };
+    let body = match column.format {
+        'X' | 'B' if body.len() > column.len => body[body.len() - column.len..].to_string(),
+        _ => body,
+    };
+    format!(
+        "{:left$}{:>len$}{:right$}",
+        "",
+        body,
+        "",
+        left = column.pad_left,
+        len = column.len,
+        right = column.pad_right
+    )
+}
+
+fn format_row(cells: &[String]) -> String {
+    format!("|{}|", cells.join("|"))
+}
+
+pub struct Runner {
+    pub dir: PathBuf,
+    pub machine: Option<Machine>,
+    /// Used by a bare `load` instead of reading `.vm` files from `dir`.
+    pub program: Option<Program>,
+    pub write_output: bool,
+    pub output: Vec<String>,
+    output_list: Vec<OutputColumn>,
+    output_file: Option<PathBuf>,
+    compare_lines: Option<Vec<String>>,
+}
+
+impl Runner {
+    pub fn new(dir: &Path) -> Self {
+        Self {
+            dir: dir.to_owned(),
+            machine: None,
+            program: None,
+            write_output: true,
+            output: vec![],
+            output_list: vec![],
+            output_file: None,
+            compare_lines: None,
+        }
+    }
+
+    /// Runs the script and writes the output file, if one was requested.
+    pub fn run(&mut self, commands: &[Command]) -> Res {
+        let result = self.run_commands(commands);
+        if let (true, Some(output_file)) = (self.write_output, &self.output_file) {
+            let mut contents = self.output.join("\n");
+            contents.push('\n');
+            fs::write(output_file, contents)?;
+        }
+        result
+    }
+
+    fn machine(&mut self) -> Res<&mut Machine> {
+        self.machine
+            .as_mut()
+            .ok_or_else(|| "No program loaded".into())
+    }
+
+    fn run_commands(&mut self, commands: &[Command]) -> Res {
+        for command in commands {
+            self.run_command(command)?;
+        }
+        Ok(())
+    }
+
+    fn run_command(&mut self, command: &Command) -> Res {
+        match command {
+            Command::Load(file) => self.load(file.as_deref())?,
+            Command::OutputFile(file) => self.output_file = Some(self.dir.join(file)),
+            Command::CompareTo(file) => {
+                let contents = fs::read_to_string(self.dir.join(file))?;
+                self.compare_lines = Some(contents.lines().map(String::from).collect());
+            }
+            Command::OutputList(columns) => {
+                self.output_list = columns.clone();
+                let header = format_row(&columns.iter().map(format_header).collect::<Vec<_>>());
+                self.write_line(header)?;
+            }
+            Command::Set(variable, value) => self.machine()?.set(variable, *value)?,
+            Command::Repeat(count, commands) => {
+                for _ in 0..*count {
+                    self.run_commands(commands)?;
+                }
+            }
+            Command::VmStep => match self.machine()? {
+                Machine::Vm(vm) => {
+                    if !vm.is_halted() {
+                        vm.step()?;
+                    }
+                }
+                Machine::Cpu(..) => return Err("vmstep requires a VM program".into()),
+            },
+            Command::TickTock => match self.machine()? {
+                Machine::Cpu(cpu) => cpu.step(),
+                Machine::Vm(..) => return Err("ticktock requires a Hack program".into()),
+            },
+            Command::Output => {
+                let machine = self.machine.as_ref().ok_or("No program loaded")?;
+                let cells = self
+                    .output_list
+                    .iter()
+                    .map(|column| Ok(format_value(column, machine.get(&column.variable)?)))
+                    .collect::<Res<Vec<_>>>()?;
+                self.write_line(format_row(&cells))?;
+            }
+            Command::Echo(text) => println!("{}", text),
+            Command::ClearEcho => {}
+        };
+        Ok(())
+    }
+
+    fn load(&mut self, file: Option<&str>) -> Res {
+        let path = file.map(|f| self.dir.join(f));
+        let extension = path
+            .as_ref()
+            .and_then(|p| p.extension())
+            .and_then(|ext| ext.to_str());
+        let machine = match (extension, &path) {
+            (Some("hack"), Some(path)) => {
+                let program = rom::parse_hack(&fs::read_to_string(path)?)?;
+                Machine::Cpu(Box::new(Cpu::new(&program)))
+            }
+            (Some("asm"), Some(path)) => {
+                let asm = fs::read_to_string(path)?;
+                let hack = hasm::code::generate_code(hasm::parser::parse(asm)?);
+                Machine::Cpu(Box::new(Cpu::new(&rom::parse_hack(&hack)?)))
+            }
+            _ => {
+                let mut program = match (&path, &self.program) {
+                    (None, Some(program)) => program.clone(),
+                    (None, None) => Program::from_path(&self.dir)?,
+                    (Some(path), _) => Program::from_path(path)?,
+                };
+                // OS functions missing from the loaded files are built in, as
+                // on the official VM emulator
+                program.link_builtins();
+                let has_entry_point = program.has_entry_point();
+                let mut vm = Interpreter::new(program);
+                if has_entry_point {
+                    vm.bootstrap()?;
+                }
+                Machine::Vm(Box::new(vm))
+            }
+        };
+        self.machine = Some(machine);
+        Ok(())
+    }
+
+    fn write_line(&mut self, line: String) -> Res {
+        let line_index = self.output.len();
+        if let Some(compare_lines) = &self.compare_lines {
+            let expected = compare_lines
+                .get(line_index)
+                .map(String::as_str)
+                .unwrap_or_default();
+            if expected != line {
+                return Err(Box::new(comparison_failure(
+                    line_index + 1,
+                    expected,
+                    &line,
+                    compare_lines.first().map(String::as_str).unwrap_or_default(),
+                )));
+            }
+        }
+        self.output.push(line);
+        Ok(())
+    }
+}
+
+fn comparison_failure(line: usize, expected: &str, actual: &str, header: &str) -> ComparisonFailure {
+    let expected_cells = expected.split('|').collect::<Vec<_>>();
+    let actual_cells = actual.split('|').collect::<Vec<_>>();
+    let header_cells = header.split('|').collect::<Vec<_>>();
+    let column = (0..expected_cells.len().max(actual_cells.len()))
+        .find(|i| expected_cells.get(*i) != actual_cells.get(*i))
+        .unwrap_or(0);
+    let cell = |cells: &[&str]| cells.get(column).copied().unwrap_or_default().to_string();
+    ComparisonFailure {
+        line,
+        column: column.max(1),
+        column_name: cell(&header_cells).trim().to_string(),
+        expected: cell(&expected_cells),
+        actual: cell(&actual_cells),
+    }
+}
+
+/// Parses and runs a `.tst` file, relative paths in it are resolved against its directory.
+pub fn run_file(script_path: &Path, program: Option<Program>) -> Res<Runner> {
+    let commands = crate::script::parse(&fs::read_to_string(script_path)?)?;
+    let dir = script_path.parent().unwrap_or_else(|| Path::new("."));
+    let mut runner = Runner::new(dir);
+    runner.program = program;
+    runner.run(&commands)?;
+    Ok(runner)
+}
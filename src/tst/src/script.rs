@@ -0,0 +1,278 @@
+use std::error::Error;
+
+type Res<T = ()> = Result<T, Box<dyn Error>>;
+
+/// Script variable, e.g. `RAM[8000]`, `sp`, `local[2]` or `PC`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Variable {
+    pub name: String,
+    pub index: Option<u16>,
+}
+
+impl std::fmt::Display for Variable {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.index {
+            Some(index) => write!(f, "{}[{}]", self.name, index),
+            None => write!(f, "{}", self.name),
+        }
+    }
+}
+
+/// `output-list` entry, e.g. `RAM[8000]%D2.6.1`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OutputColumn {
+    pub variable: Variable,
+    pub format: char,
+    pub pad_left: usize,
+    pub len: usize,
+    pub pad_right: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Command {
+    Load(Option<String>),
+    OutputFile(String),
+    CompareTo(String),
+    OutputList(Vec<OutputColumn>),
+    Set(Variable, i16),
+    Repeat(u64, Vec<Command>),
+    VmStep,
+    TickTock,
+    Output,
+    Echo(String),
+    ClearEcho,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Word(String),
+    Terminator,
+    BlockStart,
+    BlockEnd,
+}
+
+fn tokenize(source: &str) -> Res<Vec<(Token, usize)>> {
+    let mut tokens = vec![];
+    let mut chars = source.chars().peekable();
+    let mut line = 1;
+    while let Some(ch) = chars.next() {
+        match ch {
+            '\n' => line += 1,
+            _ if ch.is_whitespace() => {}
+            '/' if chars.peek() == Some(&'/') => {
+                while chars.peek().is_some_and(|ch| *ch != '\n') {
+                    chars.next();
+                }
+            }
+            '/' if chars.peek() == Some(&'*') => {
+                chars.next();
+                let mut prev = ' ';
+                loop {
+                    match chars.next() {
+                        Some('/') if prev == '*' => break,
+                        Some(ch) => {
+                            if ch == '\n' {
+                                line += 1;
+                            }
+                            prev = ch;
+                        }
+                        None => return Err(format!("Unclosed comment at line {}", line).into()),
+                    }
+                }
+            }
+            ',' | ';' | '!' => tokens.push((Token::Terminator, line)),
+            '{' => tokens.push((Token::BlockStart, line)),
+            '}' => tokens.push((Token::BlockEnd, line)),
+            '"' => {
+                let s: String = chars.by_ref().take_while(|ch| *ch != '"').collect();
+                tokens.push((Token::Word(s), line));
+            }
+            _ => {
+                let mut word = ch.to_string();
+                while let Some(&ch) = chars.peek() {
+                    if ch.is_whitespace() || ",;!{}".contains(ch) {
+                        break;
+                    }
+                    word.push(ch);
+                    chars.next();
+                }
+                tokens.push((Token::Word(word), line));
+            }
+        }
+    }
+    Ok(tokens)
+}
+
+pub fn parse_variable(s: &str) -> Res<Variable> {
+    match s.split_once('[') {
+        Some((name, rest)) => {
+            let index = rest
+                .strip_suffix(']')
+                .and_then(|index| str::parse::<u16>(index).ok())
+                .ok_or_else(|| format!("Invalid variable: {}", s))?;
+            Ok(Variable {
+                name: name.into(),
+                index: Some(index),
+            })
+        }
+        None => Ok(Variable {
+            name: s.into(),
+            index: None,
+        }),
+    }
+}
+
+/// Parses `%D-5`, `%X1F`, `%B101` or plain decimal values.
+pub fn parse_value(s: &str) -> Res<i16> {
+    let (radix, digits) = match s.strip_prefix('%') {
+        Some(rest) if rest.len() > 1 => match &rest[..1] {
+            "D" => (10, &rest[1..]),
+            "X" => (16, &rest[1..]),
+            "B" => (2, &rest[1..]),
+            _ => return Err(format!("Invalid value: {}", s).into()),
+        },
+        Some(_) => return Err(format!("Invalid value: {}", s).into()),
+        None => (10, s),
+    };
+    let value =
+        i32::from_str_radix(digits, radix).map_err(|_| format!("Invalid value: {}", s))?;
+    if !(i16::MIN as i32..=u16::MAX as i32).contains(&value) {
+        return Err(format!("Value out of range: {}", s).into());
+    }
+    Ok(value as i16)
+}
+
+fn parse_output_column(s: &str) -> Res<OutputColumn> {
+    let invalid = || format!("Invalid output column: {}", s);
+    let (variable, format) = match s.split_once('%') {
+        Some((variable, format)) => (variable, format),
+        None => (s, "D1.6.1"),
+    };
+    let mut format_chars = format.chars();
+    let format_type = format_chars.next().ok_or_else(invalid)?;
+    if !"DXBS".contains(format_type) {
+        return Err(invalid().into());
+    }
+    let widths = format_chars
+        .as_str()
+        .split('.')
+        .map(str::parse::<usize>)
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|_| invalid())?;
+    match widths[..] {
+        [pad_left, len, pad_right] => Ok(OutputColumn {
+            variable: parse_variable(variable)?,
+            format: format_type,
+            pad_left,
+            len,
+            pad_right,
+        }),
+        _ => Err(invalid().into()),
+    }
+}
+
+struct Parser {
+    tokens: Vec<(Token, usize)>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos).map(|(token, _)| token)
+    }
+
+    fn line(&self) -> usize {
+        self.tokens
+            .get(self.pos)
+            .or_else(|| self.tokens.last())
+            .map_or(0, |(_, line)| *line)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.peek().cloned();
+        self.pos += 1;
+        token
+    }
+
+    fn error<T>(&self, message: &str) -> Res<T> {
+        Err(format!("Script error at line {}: {}", self.line(), message).into())
+    }
+
+    fn parse_block(&mut self, is_nested: bool) -> Res<Vec<Command>> {
+        let mut commands = vec![];
+        loop {
+            match self.peek() {
+                None if is_nested => return self.error("expected '}'"),
+                None => return Ok(commands),
+                Some(Token::BlockEnd) if is_nested => {
+                    self.next();
+                    return Ok(commands);
+                }
+                Some(Token::Word(..)) => commands.push(self.parse_command()?),
+                Some(token) => return self.error(&format!("unexpected {:?}", token)),
+            }
+        }
+    }
+
+    fn parse_command(&mut self) -> Res<Command> {
+        let line = self.line();
+        let name = match self.next() {
+            Some(Token::Word(name)) => name,
+            _ => unreachable!("Commands start with a word"),
+        };
+        let mut args = vec![];
+        while let Some(Token::Word(arg)) = self.peek() {
+            args.push(arg.clone());
+            self.next();
+        }
+        if name == "repeat" {
+            let count = match &args[..] {
+                [count] => str::parse::<u64>(count).or_else(|_| self.error("invalid count"))?,
+                _ => return self.error("repeat expects a count"),
+            };
+            if self.next() != Some(Token::BlockStart) {
+                return self.error("expected '{'");
+            }
+            return Ok(Command::Repeat(count, self.parse_block(true)?));
+        }
+        if self.next() != Some(Token::Terminator) {
+            return Err(format!("Script error at line {}: missing terminator", line).into());
+        }
+        let command = match (name.as_str(), &args[..]) {
+            ("load", []) => Command::Load(None),
+            ("load", [file]) => Command::Load(Some(file.clone())),
+            ("output-file", [file]) => Command::OutputFile(file.clone()),
+            ("compare-to", [file]) => Command::CompareTo(file.clone()),
+            ("output-list", columns) => Command::OutputList(
+                columns
+                    .iter()
+                    .map(|column| parse_output_column(column))
+                    .collect::<Res<Vec<_>>>()?,
+            ),
+            ("set", [variable, value]) => Command::Set(parse_variable(variable)?, parse_value(value)?),
+            ("vmstep", []) => Command::VmStep,
+            ("ticktock", []) => Command::TickTock,
+            ("output", []) => Command::Output,
+            ("echo", [text]) => Command::Echo(text.clone()),
+            ("clear-echo", []) => Command::ClearEcho,
+            _ => {
+                return Err(format!(
+                    "Script error at line {}: unsupported command: {} {}",
+                    line,
+                    name,
+                    args.join(" ")
+                )
+                .into())
+            }
+        };
+        Ok(command)
+    }
+}
+
+pub fn parse(source: &str) -> Res<Vec<Command>> {
+    let mut parser = Parser {
+        tokens: tokenize(source)?,
+        pos: 0,
+    };
+    parser.parse_block(false)
+}
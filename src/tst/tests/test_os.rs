@@ -0,0 +1,303 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use compiler::{
+    compiler::compile_program,
+    compiler_cli::{self, CompileResultSuccess},
+    optimizer, os, parser,
+};
+use emulator::{cpu::MemoryMap, keyboard::KeyScript, screen::Screen};
+use tst::runner::{self, ComparisonFailure};
+use vm::halt::HaltDetector;
+use vm::heap::{self, HeapReport};
+use vm::interpreter::{Interpreter, Program};
+
+/// Compiles the OS classes in `src/os` together with the test's own `Main.jack`.
+fn compile_os_test(test_dir: &Path, opt_level: u8) -> Program {
+    compile_os_test_with(test_dir, opt_level, None)
+}
+
+/// Compiles the test's own `Main.jack` with the given OS classes, or all of
+/// them, the program using the built-in versions of the other ones.
+fn compile_os_test_with(test_dir: &Path, opt_level: u8, os_classes: Option<&[&str]>) -> Program {
+    let os_dir = test_dir.parent().unwrap();
+    let is_linked = |file: &Path| {
+        let class = file.file_stem().unwrap().to_str().unwrap();
+        os_classes.is_none_or(|classes| classes.contains(&class))
+    };
+    let mut files = fs::read_dir(os_dir)
+        .unwrap()
+        .filter_map(Result::ok)
+        .map(|f| f.path())
+        .filter(|f| f.extension().is_some_and(|ext| ext == "jack") && is_linked(f))
+        .collect::<Vec<_>>();
+    files.sort();
+    files.push(test_dir.join("Main.jack"));
+    let modules = files
+        .iter()
+        .map(|file| {
+            let CompileResultSuccess { vm_code, .. } = compiler_cli::compile_file(file).unwrap();
+            let module = file.file_stem().unwrap().to_str().unwrap().to_string();
+            (module, optimizer::optimize(&vm_code, opt_level))
+        })
+        .collect::<Vec<_>>();
+    let modules = modules
+        .iter()
+        .map(|(module, vm_code)| (module.as_str(), vm_code.as_str()))
+        .collect::<Vec<_>>();
+    Program::from_modules(&modules).unwrap()
+}
+
+fn os_test_dir(name: &str) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("../os")
+        .join(name)
+}
+
+fn run_os_test(name: &str, opt_level: u8) {
+    run_os_test_with(name, compile_os_test(&os_test_dir(name), opt_level));
+}
+
+fn run_os_test_with(name: &str, program: Program) {
+    let test_dir = os_test_dir(name);
+    let script = test_dir.join(format!("{}.tst", name));
+    let commands = tst::script::parse(&fs::read_to_string(script).unwrap()).unwrap();
+    let mut runner = runner::Runner::new(&test_dir);
+    runner.program = Some(program);
+    runner.write_output = false;
+    if let Err(e) = runner.run(&commands) {
+        panic!("{} failed: {}", name, e);
+    }
+    assert_eq!(runner.output.len(), 2);
+}
+
+#[test]
+fn test_os_math() {
+    run_os_test("MathTest", 0);
+}
+
+#[test]
+fn test_os_array() {
+    run_os_test("ArrayTest", 0);
+}
+
+#[test]
+fn test_os_memory() {
+    run_os_test("MemoryTest", 0);
+}
+
+#[test]
+fn test_os_optimized() {
+    run_os_test("MathTest", 2);
+    run_os_test("ArrayTest", 2);
+    run_os_test("MemoryTest", 2);
+}
+
+#[test]
+fn test_os_builtins() {
+    for name in ["MathTest", "ArrayTest", "MemoryTest"] {
+        let program = compile_os_test_with(&os_test_dir(name), 0, Some(&[]));
+        run_os_test_with(name, program);
+    }
+}
+
+/// Tests one Jack OS class at a time, with the built-in versions of the others.
+#[test]
+fn test_os_class_with_builtins() {
+    for (name, classes) in [
+        ("MathTest", ["Math"].as_slice()),
+        ("ArrayTest", &["Array"]),
+        ("MemoryTest", &["Memory"]),
+        ("MemoryTest", &["Memory", "Array"]),
+    ] {
+        let program = compile_os_test_with(&os_test_dir(name), 0, Some(classes));
+        run_os_test_with(name, program);
+    }
+}
+
+/// Runs until the program halts, or loops forever like the Jack OS `Sys.halt`.
+fn run_until_halt(interpreter: &mut Interpreter) {
+    run_with_keys(interpreter, &KeyScript::default());
+}
+
+/// Same as `run_until_halt`, with `KBD` holding the key of the script at the
+/// current step, failing after `MAX_STEPS` steps.
+fn run_with_keys(interpreter: &mut Interpreter, keys: &KeyScript) {
+    const MAX_STEPS: u64 = 100_000_000;
+    let keyboard = MemoryMap::default().keyboard as usize;
+    let mut detector = HaltDetector::default();
+    detector.input_until = keys.end();
+    loop {
+        interpreter.poke(keyboard, keys.key_at(interpreter.steps));
+        if detector.check(interpreter).is_some() {
+            break;
+        }
+        assert!(interpreter.steps < MAX_STEPS, "didn't halt");
+        interpreter.step().unwrap();
+    }
+}
+
+/// Allocates and frees a block many more times than the heap holds it, then
+/// checks the next block takes the place of the freed one.
+const MEMORY_REUSE_MAIN: &str = "class Main {
+  function void main() {
+    var int i;
+    var Array a, b;
+    let i = 0;
+    while (i < 1000) {
+      let a = Array.new(100);
+      do a.dispose();
+      let i = i + 1;
+    }
+    let b = Array.new(100);
+    do Memory.poke(8000, a = b);
+    return;
+  }
+}
+";
+
+/// `Memory.deAlloc` used to compare the end of the previous block with the
+/// freed object instead of its header, so blocks never merged back, and it
+/// stored them with a length missing the header, too short to ever be reused.
+/// The heap ran out after about 140 allocations of 100 words.
+#[test]
+fn test_os_memory_reuses_freed_blocks() {
+    let mut sources = os::SOURCES.to_vec();
+    sources.push(("Main", MEMORY_REUSE_MAIN));
+    let modules = sources
+        .iter()
+        .map(|(name, source)| {
+            let vm_code = compile_program(parser::parse(source).unwrap()).unwrap();
+            (*name, vm_code)
+        })
+        .collect::<Vec<_>>();
+    let modules = modules
+        .iter()
+        .map(|(name, vm_code)| (*name, vm_code.as_str()))
+        .collect::<Vec<_>>();
+    let mut interpreter = Interpreter::new(Program::from_modules(&modules).unwrap());
+    interpreter.bootstrap().unwrap();
+    run_until_halt(&mut interpreter);
+    assert_eq!(interpreter.peek(8000), -1);
+}
+
+/// Heap snapshots of a run, on entering `Main.main` and once halted.
+fn heap_reports(program: Program) -> (HeapReport, HeapReport) {
+    let mut interpreter = Interpreter::new(program);
+    interpreter.program.link_builtins();
+    interpreter.bootstrap().unwrap();
+    let main = interpreter.program.functions["Main.main"];
+    while interpreter.pc != main {
+        interpreter.step().unwrap();
+    }
+    let start = heap::inspect(&interpreter.ram);
+    run_until_halt(&mut interpreter);
+    (start, heap::inspect(&interpreter.ram))
+}
+
+#[test]
+fn test_os_memory_heap() {
+    let test_dir = os_test_dir("MemoryTest");
+    for program in [
+        compile_os_test(&test_dir, 0),
+        compile_os_test_with(&test_dir, 0, Some(&[])),
+    ] {
+        let (start, end) = heap_reports(program);
+        assert!(start.is_ok(), "{}", start);
+        assert!(end.is_ok(), "{}", end);
+        assert_eq!(end.allocated_since(&start), []);
+        assert_eq!(
+            end.free_words(),
+            start.free_words(),
+            "blocks should merge back:\n{}",
+            end
+        );
+    }
+}
+
+/// Renders the screen of a test without a display, and diffs it with
+/// `<name>Output.pbm`, the picture of `<name>Output.gif` at the screen size.
+fn check_screen_test(name: &str, program: Program, keys: &KeyScript) {
+    let mut interpreter = Interpreter::new(program);
+    interpreter.program.link_builtins();
+    interpreter.bootstrap().unwrap();
+    run_with_keys(&mut interpreter, keys);
+    let screen = Screen::from_ram(&interpreter.ram).unwrap();
+    let reference = os_test_dir(name).join(format!("{}Output.pbm", name));
+    let reference = Screen::from_pbm(&fs::read(reference).unwrap()).unwrap();
+    if let Some(diff) = screen.diff(&reference) {
+        panic!("{}: {}", name, diff);
+    }
+}
+
+/// Answers of `KeyboardTest`, typing and erasing a wrong character in both
+/// lines. Prompts take the Jack OS millions of steps to print.
+const KEYBOARD_TEST_KEYS: &str = "
+    4000000-4300000 PAGEDOWN
+    10000000-10300000 3
+    16000000-16300000 J
+    17000000-17300000 A
+    18000000-18300000 X
+    19000000-19300000 BACKSPACE
+    20000000-20300000 C
+    21000000-21300000 K
+    22000000-22300000 ENTER
+    28000000-28300000 -
+    29000000-29300000 3
+    30000000-30300000 2
+    31000000-31300000 1
+    32000000-32300000 2
+    33000000-33300000 4
+    34000000-34300000 BACKSPACE
+    35000000-35300000 3
+    36000000-36300000 ENTER
+";
+
+#[test]
+fn test_os_screen() {
+    let keys = KeyScript::parse(KEYBOARD_TEST_KEYS).unwrap();
+    for name in ["ScreenTest", "OutputTest", "StringTest", "KeyboardTest"] {
+        let keys = match name {
+            "KeyboardTest" => keys.clone(),
+            _ => KeyScript::default(),
+        };
+        let test_dir = os_test_dir(name);
+        check_screen_test(name, compile_os_test(&test_dir, 2), &keys);
+        check_screen_test(name, compile_os_test_with(&test_dir, 0, Some(&[])), &keys);
+    }
+}
+
+#[test]
+fn test_comparison_failure() {
+    let dir = std::env::temp_dir().join("tst_comparison_failure");
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(
+        dir.join("Test.cmp"),
+        "|RAM[0] |RAM[1] |\n|     7 |     8 |\n|     7 |    -1 |\n",
+    )
+    .unwrap();
+    let commands = tst::script::parse(
+        "
+        load Test.vm,
+        compare-to Test.cmp,
+        output-list RAM[0]%D1.5.1 RAM[1]%D1.5.1;
+        set RAM[0] 7, set RAM[1] %X8,
+        output;
+        repeat 2 { vmstep; }
+        output;
+        ",
+    )
+    .unwrap();
+    fs::write(dir.join("Test.vm"), "push constant 9\npop static 0\n").unwrap();
+    let mut runner = runner::Runner::new(&dir);
+    runner.write_output = false;
+    let err = runner.run(&commands).unwrap_err();
+    let failure = err.downcast_ref::<ComparisonFailure>().unwrap();
+    assert_eq!(failure.line, 3);
+    assert_eq!(failure.column, 2);
+    assert_eq!(failure.column_name, "RAM[1]");
+    assert_eq!(failure.expected, "    -1 ");
+    assert_eq!(failure.actual, "     8 ");
+}
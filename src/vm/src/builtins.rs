@@ -0,0 +1,926 @@
+//! Rust versions of the Jack OS functions, so programs run on the interpreter
+//! without the compiled OS, as on the official VM emulator.
+//!
+//! They keep the RAM layout of the Jack OS in `src/os`: the heap is a free list
+//! starting at `HEAP_BASE` whose blocks hold the address of the next one and
+//! their length, strings are `[length, capacity, chars]` objects, and the
+//! screen and keyboard are memory mapped. Built-ins call the other OS functions
+//! by name, so a loaded module replacing an OS class is used by the built-in
+//! versions of the other classes too.
+//!
+//! Errors are reported with the error codes of the official OS, by calling
+//! `Sys.error` and halting.
+
+use std::collections::HashSet;
+use std::error::Error;
+
+use crate::interpreter::{address, Interpreter, SP};
+
+type Res<T = ()> = Result<T, Box<dyn Error>>;
+
+pub const HEAP_BASE: usize = 2048;
+/// Initial length of the heap's single free block.
+pub const HEAP_LENGTH: i16 = 14334;
+/// Error code for a broken free list, or `Memory.deAlloc` given an object
+/// outside the heap. The official OS has no such check, its codes end at 20.
+pub const HEAP_DAMAGED: i16 = 21;
+pub const SCREEN: usize = 0x4000;
+pub const KBD: usize = 0x6000;
+/// `Sys.wait` waits this many VM steps per millisecond.
+pub const WAIT_STEPS_PER_MS: u64 = 1000;
+/// Steps a VM function called by a built-in may run for before giving up.
+pub const CALL_STEP_LIMIT: u64 = 10_000_000;
+
+const TEXT_ROWS: usize = 23;
+const TEXT_COLUMNS: usize = 64;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Builtin {
+    MathInit,
+    MathAbs,
+    MathMultiply,
+    MathDivide,
+    MathMin,
+    MathMax,
+    MathSqrt,
+    StringNew,
+    StringDispose,
+    StringLength,
+    StringCharAt,
+    StringSetCharAt,
+    StringAppendChar,
+    StringEraseLastChar,
+    StringIntValue,
+    StringSetInt,
+    StringBackSpace,
+    StringDoubleQuote,
+    StringNewLine,
+    ArrayNew,
+    ArrayDispose,
+    OutputInit,
+    OutputMoveCursor,
+    OutputPrintChar,
+    OutputPrintString,
+    OutputPrintInt,
+    OutputPrintln,
+    OutputBackSpace,
+    ScreenInit,
+    ScreenClearScreen,
+    ScreenSetColor,
+    ScreenDrawPixel,
+    ScreenDrawLine,
+    ScreenDrawRectangle,
+    ScreenDrawCircle,
+    KeyboardInit,
+    KeyboardKeyPressed,
+    KeyboardReadChar,
+    KeyboardReadLine,
+    KeyboardReadInt,
+    MemoryInit,
+    MemoryPeek,
+    MemoryPoke,
+    MemoryAlloc,
+    MemoryDeAlloc,
+    SysInit,
+    SysHalt,
+    SysError,
+    SysWait,
+}
+
+/// Function name and number of arguments of the built-ins, counting `this`
+/// for methods.
+const BUILTINS: [(Builtin, &str, usize); 49] = [
+    (Builtin::MathInit, "Math.init", 0),
+    (Builtin::MathAbs, "Math.abs", 1),
+    (Builtin::MathMultiply, "Math.multiply", 2),
+    (Builtin::MathDivide, "Math.divide", 2),
+    (Builtin::MathMin, "Math.min", 2),
+    (Builtin::MathMax, "Math.max", 2),
+    (Builtin::MathSqrt, "Math.sqrt", 1),
+    (Builtin::StringNew, "String.new", 1),
+    (Builtin::StringDispose, "String.dispose", 1),
+    (Builtin::StringLength, "String.length", 1),
+    (Builtin::StringCharAt, "String.charAt", 2),
+    (Builtin::StringSetCharAt, "String.setCharAt", 3),
+    (Builtin::StringAppendChar, "String.appendChar", 2),
+    (Builtin::StringEraseLastChar, "String.eraseLastChar", 1),
+    (Builtin::StringIntValue, "String.intValue", 1),
+    (Builtin::StringSetInt, "String.setInt", 2),
+    (Builtin::StringBackSpace, "String.backSpace", 0),
+    (Builtin::StringDoubleQuote, "String.doubleQuote", 0),
+    (Builtin::StringNewLine, "String.newLine", 0),
+    (Builtin::ArrayNew, "Array.new", 1),
+    (Builtin::ArrayDispose, "Array.dispose", 1),
+    (Builtin::OutputInit, "Output.init", 0),
+    (Builtin::OutputMoveCursor, "Output.moveCursor", 2),
+    (Builtin::OutputPrintChar, "Output.printChar", 1),
+    (Builtin::OutputPrintString, "Output.printString", 1),
+    (Builtin::OutputPrintInt, "Output.printInt", 1),
+    (Builtin::OutputPrintln, "Output.println", 0),
+    (Builtin::OutputBackSpace, "Output.backSpace", 0),
+    (Builtin::ScreenInit, "Screen.init", 0),
+    (Builtin::ScreenClearScreen, "Screen.clearScreen", 0),
+    (Builtin::ScreenSetColor, "Screen.setColor", 1),
+    (Builtin::ScreenDrawPixel, "Screen.drawPixel", 2),
+    (Builtin::ScreenDrawLine, "Screen.drawLine", 4),
+    (Builtin::ScreenDrawRectangle, "Screen.drawRectangle", 4),
+    (Builtin::ScreenDrawCircle, "Screen.drawCircle", 3),
+    (Builtin::KeyboardInit, "Keyboard.init", 0),
+    (Builtin::KeyboardKeyPressed, "Keyboard.keyPressed", 0),
+    (Builtin::KeyboardReadChar, "Keyboard.readChar", 0),
+    (Builtin::KeyboardReadLine, "Keyboard.readLine", 1),
+    (Builtin::KeyboardReadInt, "Keyboard.readInt", 1),
+    (Builtin::MemoryInit, "Memory.init", 0),
+    (Builtin::MemoryPeek, "Memory.peek", 1),
+    (Builtin::MemoryPoke, "Memory.poke", 2),
+    (Builtin::MemoryAlloc, "Memory.alloc", 1),
+    (Builtin::MemoryDeAlloc, "Memory.deAlloc", 1),
+    (Builtin::SysInit, "Sys.init", 0),
+    (Builtin::SysHalt, "Sys.halt", 0),
+    (Builtin::SysError, "Sys.error", 1),
+    (Builtin::SysWait, "Sys.wait", 1),
+];
+
+impl Builtin {
+    pub fn from_name(name: &str) -> Option<Self> {
+        BUILTINS
+            .iter()
+            .find(|(_, builtin_name, _)| *builtin_name == name)
+            .map(|(builtin, _, _)| *builtin)
+    }
+
+    fn entry(&self) -> &'static (Builtin, &'static str, usize) {
+        BUILTINS
+            .iter()
+            .find(|(builtin, _, _)| builtin == self)
+            .unwrap()
+    }
+
+    pub fn name(&self) -> &'static str {
+        self.entry().1
+    }
+
+    pub fn n_args(&self) -> usize {
+        self.entry().2
+    }
+}
+
+/// Keyboard read waiting for a key to be pressed and released.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct KeyRead {
+    /// Key currently held, 0 if none.
+    pub key: i16,
+    /// Characters read by `readLine` or `readInt`.
+    pub line: Vec<i16>,
+}
+
+/// Built-in call which doesn't return until some steps have run.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Waiting {
+    /// `Sys.wait`, until the given step.
+    Steps(u64),
+    Key(KeyRead),
+}
+
+/// State of the built-ins, which the Jack OS keeps in static variables.
+#[derive(Debug, Clone)]
+pub struct OsState {
+    /// `Screen.setColor`, true for black.
+    pub color: bool,
+    pub cursor_row: usize,
+    pub cursor_column: usize,
+    pub waiting: Option<Waiting>,
+    /// Code of the `Sys.error` call which halted the program.
+    pub error: Option<i16>,
+}
+
+impl Default for OsState {
+    fn default() -> Self {
+        Self {
+            color: true,
+            cursor_row: 0,
+            cursor_column: 0,
+            waiting: None,
+            error: None,
+        }
+    }
+}
+
+/// What a built-in call did.
+enum Outcome {
+    Return(i16),
+    /// Not done yet, the call runs again on the next step.
+    Wait,
+    Halt,
+    /// Transferred control to VM code, like `Sys.init` calling `Main.main`.
+    Jump,
+}
+
+/// Integer part of the square root of a non-negative number.
+fn isqrt(x: i32) -> i32 {
+    let mut y = 0;
+    while (y + 1) * (y + 1) <= x {
+        y += 1;
+    }
+    y
+}
+
+impl Interpreter {
+    /// Runs a built-in called by a VM command, its arguments being on the stack.
+    pub(crate) fn builtin(&mut self, builtin: Builtin, n_args: usize) -> Res {
+        let call_pc = self.pc - 1;
+        if n_args != builtin.n_args() {
+            self.pc = call_pc;
+            return Err(format!(
+                "{} expects {} argument(s), got {}",
+                builtin.name(),
+                builtin.n_args(),
+                n_args
+            )
+            .into());
+        }
+        let sp = self.ram[SP];
+        let args: Vec<i16> = (0..n_args)
+            .map(|i| self.peek(address(sp.wrapping_sub((n_args - i) as i16))))
+            .collect();
+        let outcome = match self.run_builtin(builtin, &args) {
+            Ok(outcome) => outcome,
+            // An OS error in a function the built-in called, like the
+            // `Memory.alloc` of `Array.new`, halts the program all the same
+            Err(_) if self.os.error.is_some() => Outcome::Halt,
+            Err(e) => {
+                self.pc = call_pc;
+                return Err(e);
+            }
+        };
+        match outcome {
+            Outcome::Return(value) => {
+                self.ram[SP] = sp.wrapping_sub(n_args as i16);
+                self.push(value);
+            }
+            Outcome::Wait => self.pc = call_pc,
+            Outcome::Halt => self.pc = self.program.commands.len(),
+            Outcome::Jump => {}
+        }
+        Ok(())
+    }
+
+    /// The built-in `Sys.init`, for bootstrapping programs without one.
+    pub(crate) fn sys_init(&mut self) -> Res {
+        self.run_builtin(Builtin::SysInit, &[])?;
+        Ok(())
+    }
+
+    /// Calls an OS function on behalf of a built-in: the loaded one if a
+    /// module defines it, else the built-in one. VM functions run until they
+    /// return, as part of the current step.
+    fn call_function(&mut self, name: &str, args: &[i16]) -> Res<i16> {
+        let Some(&target) = self.program.functions.get(name) else {
+            let builtin =
+                Builtin::from_name(name).ok_or_else(|| format!("Unknown function: {}", name))?;
+            return match self.run_builtin(builtin, args)? {
+                Outcome::Return(value) => Ok(value),
+                Outcome::Halt => Err(format!("{} halted the program", name).into()),
+                Outcome::Wait | Outcome::Jump => {
+                    Err(format!("{} can't be called by a built-in", name).into())
+                }
+            };
+        };
+        let pc = self.pc;
+        let sp = self.ram[SP];
+        let return_address = self.program.commands.len();
+        for arg in args {
+            self.push(*arg);
+        }
+        self.call(target, args.len(), return_address);
+        let mut steps = 0;
+        while self.pc != return_address {
+            if steps == CALL_STEP_LIMIT {
+                return Err(format!("{} didn't return after {} steps", name, steps).into());
+            }
+            self.step()?;
+            steps += 1;
+        }
+        if self.ram[SP] != sp.wrapping_add(1) {
+            return Err(format!("Program halted in {}", name).into());
+        }
+        self.pc = pc;
+        Ok(self.pop())
+    }
+
+    /// Reports an error the way the OS does: calls `Sys.error` and halts.
+    fn os_error(&mut self, code: i16) -> Res<Outcome> {
+        if self.program.functions.contains_key("Sys.error") {
+            self.call_function("Sys.error", &[code])?;
+        } else {
+            self.print_error(code)?;
+        }
+        self.os.error = Some(code);
+        Ok(Outcome::Halt)
+    }
+
+    fn print_error(&mut self, code: i16) -> Res {
+        for c in format!("ERR{}", code).bytes() {
+            self.call_function("Output.printChar", &[c as i16])?;
+        }
+        Ok(())
+    }
+
+    fn run_builtin(&mut self, builtin: Builtin, args: &[i16]) -> Res<Outcome> {
+        let arg = |i: usize| args[i];
+        let value = match builtin {
+            Builtin::MathInit => 0,
+            Builtin::MathAbs => arg(0).wrapping_abs(),
+            Builtin::MathMultiply => arg(0).wrapping_mul(arg(1)),
+            Builtin::MathDivide if arg(1) == 0 => return self.os_error(3),
+            Builtin::MathDivide => arg(0).wrapping_div(arg(1)),
+            Builtin::MathMin => arg(0).min(arg(1)),
+            Builtin::MathMax => arg(0).max(arg(1)),
+            Builtin::MathSqrt if arg(0) < 0 => return self.os_error(4),
+            Builtin::MathSqrt => isqrt(arg(0) as i32) as i16,
+
+            Builtin::StringNew => return self.string_new(arg(0)),
+            Builtin::StringDispose => {
+                let chars = self.field(arg(0), 2);
+                if chars != 0 {
+                    self.call_function("Array.dispose", &[chars])?;
+                }
+                self.call_function("Memory.deAlloc", &[arg(0)])?;
+                0
+            }
+            Builtin::StringLength => self.field(arg(0), 0),
+            Builtin::StringCharAt if !self.in_string(arg(0), arg(1)) => return self.os_error(15),
+            Builtin::StringCharAt => self.field(self.field(arg(0), 2), arg(1)),
+            Builtin::StringSetCharAt if !self.in_string(arg(0), arg(1)) => {
+                return self.os_error(16)
+            }
+            Builtin::StringSetCharAt => {
+                self.set_field(self.field(arg(0), 2), arg(1), arg(2));
+                0
+            }
+            Builtin::StringAppendChar => {
+                let (string, length) = (arg(0), self.field(arg(0), 0));
+                if length >= self.field(string, 1) {
+                    return self.os_error(17);
+                }
+                self.set_field(self.field(string, 2), length, arg(1));
+                self.set_field(string, 0, length + 1);
+                string
+            }
+            Builtin::StringEraseLastChar => {
+                let length = self.field(arg(0), 0);
+                if length <= 0 {
+                    return self.os_error(18);
+                }
+                self.set_field(arg(0), 0, length - 1);
+                0
+            }
+            Builtin::StringIntValue => {
+                let chars = self.field(arg(0), 2);
+                let chars: Vec<i16> = (0..self.field(arg(0), 0))
+                    .map(|i| self.field(chars, i))
+                    .collect();
+                int_value(&chars)
+            }
+            Builtin::StringSetInt => {
+                let (string, digits) = (arg(0), arg(1).to_string().into_bytes());
+                if digits.len() as i16 > self.field(string, 1) {
+                    return self.os_error(19);
+                }
+                let chars = self.field(string, 2);
+                for (i, digit) in digits.iter().enumerate() {
+                    self.set_field(chars, i as i16, *digit as i16);
+                }
+                self.set_field(string, 0, digits.len() as i16);
+                0
+            }
+            Builtin::StringBackSpace => 129,
+            Builtin::StringDoubleQuote => 34,
+            Builtin::StringNewLine => 128,
+
+            Builtin::ArrayNew if arg(0) <= 0 => return self.os_error(2),
+            Builtin::ArrayNew => self.call_function("Memory.alloc", &[arg(0)])?,
+            Builtin::ArrayDispose => self.call_function("Memory.deAlloc", &[arg(0)])?,
+
+            Builtin::OutputInit => {
+                self.os.cursor_row = 0;
+                self.os.cursor_column = 0;
+                0
+            }
+            Builtin::OutputMoveCursor => {
+                let (row, column) = (arg(0) as usize, arg(1) as usize);
+                if arg(0) < 0 || arg(1) < 0 || row >= TEXT_ROWS || column >= TEXT_COLUMNS {
+                    return self.os_error(20);
+                }
+                self.os.cursor_row = row;
+                self.os.cursor_column = column;
+                self.draw_char(b' ' as i16);
+                0
+            }
+            Builtin::OutputPrintChar => {
+                self.print_char(arg(0));
+                0
+            }
+            Builtin::OutputPrintString => {
+                let length = self.call_function("String.length", &[arg(0)])?;
+                for i in 0..length {
+                    let c = self.call_function("String.charAt", &[arg(0), i])?;
+                    self.print_char(c);
+                }
+                0
+            }
+            Builtin::OutputPrintInt => {
+                for c in arg(0).to_string().bytes() {
+                    self.print_char(c as i16);
+                }
+                0
+            }
+            Builtin::OutputPrintln => {
+                self.println();
+                0
+            }
+            Builtin::OutputBackSpace => {
+                self.back_space();
+                0
+            }
+
+            Builtin::ScreenInit => {
+                self.os.color = true;
+                0
+            }
+            Builtin::ScreenClearScreen => {
+                self.ram[SCREEN..KBD].fill(0);
+                0
+            }
+            Builtin::ScreenSetColor => {
+                self.os.color = arg(0) != 0;
+                0
+            }
+            Builtin::ScreenDrawPixel if !on_screen(arg(0), arg(1)) => return self.os_error(7),
+            Builtin::ScreenDrawPixel => {
+                self.draw_pixel(arg(0), arg(1));
+                0
+            }
+            Builtin::ScreenDrawLine if !on_screen(arg(0), arg(1)) || !on_screen(arg(2), arg(3)) => {
+                return self.os_error(8)
+            }
+            Builtin::ScreenDrawLine => {
+                self.draw_line(arg(0), arg(1), arg(2), arg(3));
+                0
+            }
+            Builtin::ScreenDrawRectangle
+                if !on_screen(arg(0), arg(1))
+                    || !on_screen(arg(2), arg(3))
+                    || arg(0) > arg(2)
+                    || arg(1) > arg(3) =>
+            {
+                return self.os_error(9)
+            }
+            Builtin::ScreenDrawRectangle => {
+                for y in arg(1)..=arg(3) {
+                    self.draw_row(arg(0), arg(2), y);
+                }
+                0
+            }
+            Builtin::ScreenDrawCircle if !on_screen(arg(0), arg(1)) => return self.os_error(12),
+            Builtin::ScreenDrawCircle if !(0..=181).contains(&arg(2)) => return self.os_error(13),
+            Builtin::ScreenDrawCircle => {
+                let (x, y, r) = (arg(0) as i32, arg(1) as i32, arg(2) as i32);
+                for dy in -r..=r {
+                    let half = isqrt(r * r - dy * dy);
+                    if (0..256).contains(&(y + dy)) {
+                        let left = (x - half).max(0);
+                        let right = (x + half).min(511);
+                        self.draw_row(left as i16, right as i16, (y + dy) as i16);
+                    }
+                }
+                0
+            }
+
+            Builtin::KeyboardInit => 0,
+            Builtin::KeyboardKeyPressed => self.ram[KBD],
+            Builtin::KeyboardReadChar | Builtin::KeyboardReadLine | Builtin::KeyboardReadInt => {
+                return self.read_keyboard(builtin, args)
+            }
+
+            Builtin::MemoryInit => {
+                self.ram[HEAP_BASE] = 0;
+                self.ram[HEAP_BASE + 1] = HEAP_LENGTH;
+                0
+            }
+            Builtin::MemoryPeek => self.peek(address(arg(0))),
+            Builtin::MemoryPoke => {
+                self.poke(address(arg(0)), arg(1));
+                0
+            }
+            Builtin::MemoryAlloc if arg(0) <= 0 => return self.os_error(5),
+            Builtin::MemoryAlloc => match self.alloc(arg(0)) {
+                Ok(block) => block,
+                Err(code) => return self.os_error(code),
+            },
+            Builtin::MemoryDeAlloc => match self.de_alloc(arg(0)) {
+                Ok(()) => 0,
+                Err(code) => return self.os_error(code),
+            },
+
+            Builtin::SysInit => {
+                for name in [
+                    "Memory.init",
+                    "Math.init",
+                    "Screen.init",
+                    "Output.init",
+                    "Keyboard.init",
+                ] {
+                    self.call_function(name, &[])?;
+                }
+                let main = *self
+                    .program
+                    .functions
+                    .get("Main.main")
+                    .ok_or("Can't start the program, no Main.main function")?;
+                let halt_address = self.program.commands.len();
+                self.call(main, 0, halt_address);
+                return Ok(Outcome::Jump);
+            }
+            Builtin::SysHalt => return Ok(Outcome::Halt),
+            Builtin::SysError => {
+                self.print_error(arg(0))?;
+                self.os.error = Some(arg(0));
+                return Ok(Outcome::Halt);
+            }
+            Builtin::SysWait if arg(0) < 0 => return self.os_error(1),
+            Builtin::SysWait => match self.os.waiting {
+                Some(Waiting::Steps(until)) if self.steps >= until => {
+                    self.os.waiting = None;
+                    0
+                }
+                Some(_) => return Ok(Outcome::Wait),
+                None => {
+                    let until = self.steps + arg(0) as u64 * WAIT_STEPS_PER_MS;
+                    self.os.waiting = Some(Waiting::Steps(until));
+                    return Ok(Outcome::Wait);
+                }
+            },
+        };
+        Ok(Outcome::Return(value))
+    }
+
+    fn field(&self, object: i16, index: i16) -> i16 {
+        self.peek(address(object.wrapping_add(index)))
+    }
+
+    fn set_field(&mut self, object: i16, index: i16, value: i16) {
+        self.poke(address(object.wrapping_add(index)), value);
+    }
+
+    fn in_string(&self, string: i16, index: i16) -> bool {
+        (0..self.field(string, 0)).contains(&index)
+    }
+
+    fn string_new(&mut self, capacity: i16) -> Res<Outcome> {
+        if capacity < 0 {
+            return self.os_error(14);
+        }
+        let string = self.call_function("Memory.alloc", &[3])?;
+        let chars = if capacity > 0 {
+            self.call_function("Array.new", &[capacity])?
+        } else {
+            0
+        };
+        self.set_field(string, 0, 0);
+        self.set_field(string, 1, capacity);
+        self.set_field(string, 2, chars);
+        Ok(Outcome::Return(string))
+    }
+
+    /// Address and length of the free blocks, or `None` when the free list is
+    /// broken: a block reaches out of the heap, or a link goes back to a block
+    /// already visited.
+    fn free_blocks(&self) -> Option<Vec<(usize, i16)>> {
+        let mut blocks = vec![];
+        let mut visited = HashSet::new();
+        let mut node = HEAP_BASE;
+        loop {
+            let length = self.peek(node + 1);
+            if !is_heap_block(node, length) || !visited.insert(node) {
+                return None;
+            }
+            blocks.push((node, length));
+            match self.peek(node) {
+                0 => return Some(blocks),
+                next => node = address(next),
+            }
+        }
+    }
+
+    /// Best fit allocation from the end of a free block, with the block's
+    /// origin and size in the two words before the returned address, as in
+    /// `src/os/Memory.jack`. Fails with the OS error code.
+    fn alloc(&mut self, size: i16) -> Result<i16, i16> {
+        let needed = size as i32 + 2;
+        let mut best: Option<(usize, i32)> = None;
+        for (node, length) in self.free_blocks().ok_or(HEAP_DAMAGED)? {
+            let length = length as i32;
+            // The rest of the block keeps its two header words
+            if length - needed >= 2 && best.is_none_or(|(_, best_length)| length < best_length) {
+                best = Some((node, length));
+            }
+        }
+        let Some((node, length)) = best else {
+            return Err(6);
+        };
+        let rest = length - needed;
+        self.poke(node + 1, rest as i16);
+        let block = node + rest as usize;
+        self.poke(block, node as i16);
+        self.poke(block + 1, size);
+        Ok(block as i16 + 2)
+    }
+
+    /// Gives a block back to the free block it follows, or appends it to the
+    /// free list. Fails with the OS error code.
+    fn de_alloc(&mut self, object: i16) -> Result<(), i16> {
+        let block = address(object.wrapping_sub(2));
+        let length = self.peek(block + 1).wrapping_add(2);
+        if !is_heap_block(block, length) {
+            return Err(HEAP_DAMAGED);
+        }
+        let blocks = self.free_blocks().ok_or(HEAP_DAMAGED)?;
+        for &(node, node_length) in &blocks {
+            if node + node_length as usize == block {
+                self.poke(node + 1, node_length.wrapping_add(length));
+                return Ok(());
+            }
+        }
+        let (last, _) = blocks[blocks.len() - 1];
+        self.poke(last, block as i16);
+        self.poke(block, 0);
+        self.poke(block + 1, length);
+        Ok(())
+    }
+
+    fn draw_pixel(&mut self, x: i16, y: i16) {
+        let word = SCREEN + y as usize * 32 + x as usize / 16;
+        let bit = 1 << (x % 16);
+        if self.os.color {
+            self.ram[word] |= bit;
+        } else {
+            self.ram[word] &= !bit;
+        }
+    }
+
+    fn draw_row(&mut self, x1: i16, x2: i16, y: i16) {
+        for x in x1..=x2 {
+            self.draw_pixel(x, y);
+        }
+    }
+
+    /// Draws a line like the Jack OS, with the algorithm of the book: each
+    /// pixel is one step right, or one step up or down, from the last one.
+    fn draw_line(&mut self, x1: i16, y1: i16, x2: i16, y2: i16) {
+        let (x1, y1, x2, y2) = if x2 < x1 {
+            (x2, y2, x1, y1)
+        } else {
+            (x1, y1, x2, y2)
+        };
+        if y1 == y2 {
+            self.draw_row(x1, x2, y1);
+            return;
+        }
+        let (dx, dy) = (x2 - x1, y2 - y1);
+        let (mut a, mut b, mut diff) = (0, 0i16, 0);
+        while a <= dx && b.abs() <= dy.abs() {
+            self.draw_pixel(x1 + a, y1 + b);
+            if diff < 0 {
+                a += 1;
+                diff += dy.abs();
+            } else {
+                b += dy.signum();
+                diff -= dx;
+            }
+        }
+    }
+
+    /// Draws a character at the cursor, two 8 pixels wide columns per word.
+    fn draw_char(&mut self, c: i16) {
+        let glyph = match c {
+            32..=126 => &FONT[c as usize - 32],
+            _ => &BLACK_SQUARE,
+        };
+        let (row, column) = (self.os.cursor_row, self.os.cursor_column);
+        for (i, bits) in glyph.iter().enumerate() {
+            let word = &mut self.ram[SCREEN + (row * 11 + i) * 32 + column / 2];
+            *word = if column % 2 == 1 {
+                (*word & 0x00ff) | ((*bits as i16) << 8)
+            } else {
+                (*word & !0x00ff) | *bits as i16
+            };
+        }
+    }
+
+    fn print_char(&mut self, c: i16) {
+        match c {
+            128 => self.println(),
+            129 => self.back_space(),
+            _ => {
+                self.draw_char(c);
+                self.os.cursor_column += 1;
+                if self.os.cursor_column == TEXT_COLUMNS {
+                    self.println();
+                }
+            }
+        }
+    }
+
+    fn println(&mut self) {
+        self.os.cursor_column = 0;
+        self.os.cursor_row = (self.os.cursor_row + 1) % TEXT_ROWS;
+    }
+
+    fn back_space(&mut self) {
+        if self.os.cursor_column > 0 {
+            self.os.cursor_column -= 1;
+        } else if self.os.cursor_row > 0 {
+            self.os.cursor_row -= 1;
+            self.os.cursor_column = TEXT_COLUMNS - 1;
+        }
+        self.draw_char(b' ' as i16);
+    }
+
+    /// `readChar`, `readLine` and `readInt`: each call handles the keyboard
+    /// state of one step, echoing a key once it's released.
+    fn read_keyboard(&mut self, builtin: Builtin, args: &[i16]) -> Res<Outcome> {
+        let mut read = match self.os.waiting.take() {
+            Some(Waiting::Key(read)) => read,
+            _ => {
+                if builtin != Builtin::KeyboardReadChar {
+                    self.call_function("Output.printString", &[args[0]])?;
+                }
+                KeyRead::default()
+            }
+        };
+        let pressed = self.ram[KBD];
+        if pressed != 0 || read.key == 0 {
+            read.key = pressed;
+            self.os.waiting = Some(Waiting::Key(read));
+            return Ok(Outcome::Wait);
+        }
+        let key = read.key;
+        read.key = 0;
+        if builtin == Builtin::KeyboardReadChar {
+            self.call_function("Output.printChar", &[key])?;
+            return Ok(Outcome::Return(key));
+        }
+        match key {
+            128 => {
+                self.call_function("Output.println", &[])?;
+                let value = if builtin == Builtin::KeyboardReadInt {
+                    int_value(&read.line)
+                } else {
+                    let string = self.call_function("String.new", &[read.line.len() as i16])?;
+                    for c in &read.line {
+                        self.call_function("String.appendChar", &[string, *c])?;
+                    }
+                    string
+                };
+                return Ok(Outcome::Return(value));
+            }
+            129 => {
+                if read.line.pop().is_some() {
+                    self.call_function("Output.backSpace", &[])?;
+                }
+            }
+            _ => {
+                read.line.push(key);
+                self.call_function("Output.printChar", &[key])?;
+            }
+        }
+        self.os.waiting = Some(Waiting::Key(read));
+        Ok(Outcome::Wait)
+    }
+}
+
+fn on_screen(x: i16, y: i16) -> bool {
+    (0..512).contains(&x) && (0..256).contains(&y)
+}
+
+/// Value of the leading digits, with an optional minus sign, like `String.intValue`.
+fn int_value(chars: &[i16]) -> i16 {
+    let (sign, digits) = match chars.first() {
+        Some(45) => (-1, &chars[1..]),
+        _ => (1, chars),
+    };
+    digits
+        .iter()
+        .take_while(|c| (48..58).contains(*c))
+        .fold(0i16, |value, c| value.wrapping_mul(10).wrapping_add(c - 48))
+        .wrapping_mul(sign)
+}
+
+/// Whether a block with its two header words fits in the heap.
+fn is_heap_block(block: usize, length: i16) -> bool {
+    block >= HEAP_BASE && length >= 2 && block + length as usize <= SCREEN
+}
+
+/// Bitmaps of the characters 32 to 126, one byte per row with the lowest bit
+/// as the leftmost pixel, the font of `src/os/Output.jack`.
+const FONT: [[u8; 11]; 95] = [
+    [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0],           // space
+    [12, 30, 30, 30, 12, 12, 0, 12, 12, 0, 0],   // !
+    [54, 54, 20, 0, 0, 0, 0, 0, 0, 0, 0],        // "
+    [0, 18, 18, 63, 18, 18, 63, 18, 18, 0, 0],   // #
+    [12, 30, 51, 3, 30, 48, 51, 30, 12, 12, 0],  // $
+    [0, 0, 35, 51, 24, 12, 6, 51, 49, 0, 0],     // %
+    [12, 30, 30, 12, 54, 27, 27, 27, 54, 0, 0],  // &
+    [12, 12, 6, 0, 0, 0, 0, 0, 0, 0, 0],         // '
+    [24, 12, 6, 6, 6, 6, 6, 12, 24, 0, 0],       // (
+    [6, 12, 24, 24, 24, 24, 24, 12, 6, 0, 0],    // )
+    [0, 0, 0, 51, 30, 63, 30, 51, 0, 0, 0],      // *
+    [0, 0, 0, 12, 12, 63, 12, 12, 0, 0, 0],      // +
+    [0, 0, 0, 0, 0, 0, 0, 12, 12, 6, 0],         // ,
+    [0, 0, 0, 0, 0, 63, 0, 0, 0, 0, 0],          // -
+    [0, 0, 0, 0, 0, 0, 0, 12, 12, 0, 0],         // .
+    [0, 0, 32, 48, 24, 12, 6, 3, 1, 0, 0],       // /
+    [12, 30, 51, 51, 51, 51, 51, 30, 12, 0, 0],  // 0
+    [12, 14, 15, 12, 12, 12, 12, 12, 63, 0, 0],  // 1
+    [30, 51, 48, 24, 12, 6, 3, 51, 63, 0, 0],    // 2
+    [30, 51, 48, 48, 28, 48, 48, 51, 30, 0, 0],  // 3
+    [16, 24, 28, 26, 25, 63, 24, 24, 60, 0, 0],  // 4
+    [63, 3, 3, 31, 48, 48, 48, 51, 30, 0, 0],    // 5
+    [28, 6, 3, 3, 31, 51, 51, 51, 30, 0, 0],     // 6
+    [63, 49, 48, 48, 24, 12, 12, 12, 12, 0, 0],  // 7
+    [30, 51, 51, 51, 30, 51, 51, 51, 30, 0, 0],  // 8
+    [30, 51, 51, 51, 62, 48, 48, 24, 14, 0, 0],  // 9
+    [0, 0, 12, 12, 0, 0, 12, 12, 0, 0, 0],       // :
+    [0, 0, 12, 12, 0, 0, 12, 12, 6, 0, 0],       // ;
+    [0, 0, 24, 12, 6, 3, 6, 12, 24, 0, 0],       // <
+    [0, 0, 0, 63, 0, 0, 63, 0, 0, 0, 0],         // =
+    [0, 0, 3, 6, 12, 24, 12, 6, 3, 0, 0],        // >
+    [30, 51, 51, 24, 12, 12, 0, 12, 12, 0, 0],   // ?
+    [30, 51, 51, 59, 59, 59, 27, 3, 30, 0, 0],   // @
+    [12, 30, 51, 51, 63, 51, 51, 51, 51, 0, 0],  // A
+    [31, 51, 51, 51, 31, 51, 51, 51, 31, 0, 0],  // B
+    [28, 54, 35, 3, 3, 3, 35, 54, 28, 0, 0],     // C
+    [15, 27, 51, 51, 51, 51, 51, 27, 15, 0, 0],  // D
+    [63, 51, 35, 11, 15, 11, 35, 51, 63, 0, 0],  // E
+    [63, 51, 35, 11, 15, 11, 3, 3, 3, 0, 0],     // F
+    [28, 54, 35, 3, 59, 51, 51, 54, 44, 0, 0],   // G
+    [51, 51, 51, 51, 63, 51, 51, 51, 51, 0, 0],  // H
+    [30, 12, 12, 12, 12, 12, 12, 12, 30, 0, 0],  // I
+    [60, 24, 24, 24, 24, 24, 27, 27, 14, 0, 0],  // J
+    [51, 51, 51, 27, 15, 27, 51, 51, 51, 0, 0],  // K
+    [3, 3, 3, 3, 3, 3, 35, 51, 63, 0, 0],        // L
+    [33, 51, 63, 63, 51, 51, 51, 51, 51, 0, 0],  // M
+    [51, 51, 55, 55, 63, 59, 59, 51, 51, 0, 0],  // N
+    [30, 51, 51, 51, 51, 51, 51, 51, 30, 0, 0],  // O
+    [31, 51, 51, 51, 31, 3, 3, 3, 3, 0, 0],      // P
+    [30, 51, 51, 51, 51, 51, 63, 59, 30, 48, 0], // Q
+    [31, 51, 51, 51, 31, 27, 51, 51, 51, 0, 0],  // R
+    [30, 51, 51, 6, 28, 48, 51, 51, 30, 0, 0],   // S
+    [63, 63, 45, 12, 12, 12, 12, 12, 30, 0, 0],  // T
+    [51, 51, 51, 51, 51, 51, 51, 51, 30, 0, 0],  // U
+    [51, 51, 51, 51, 51, 30, 30, 12, 12, 0, 0],  // V
+    [51, 51, 51, 51, 51, 63, 63, 63, 18, 0, 0],  // W
+    [51, 51, 30, 30, 12, 30, 30, 51, 51, 0, 0],  // X
+    [51, 51, 51, 51, 30, 12, 12, 12, 30, 0, 0],  // Y
+    [63, 51, 49, 24, 12, 6, 35, 51, 63, 0, 0],   // Z
+    [30, 6, 6, 6, 6, 6, 6, 6, 30, 0, 0],         // [
+    [0, 0, 1, 3, 6, 12, 24, 48, 32, 0, 0],       // \
+    [30, 24, 24, 24, 24, 24, 24, 24, 30, 0, 0],  // ]
+    [8, 28, 54, 0, 0, 0, 0, 0, 0, 0, 0],         // ^
+    [0, 0, 0, 0, 0, 0, 0, 0, 0, 63, 0],          // _
+    [6, 12, 24, 0, 0, 0, 0, 0, 0, 0, 0],         // `
+    [0, 0, 0, 14, 24, 30, 27, 27, 54, 0, 0],     // a
+    [3, 3, 3, 15, 27, 51, 51, 51, 30, 0, 0],     // b
+    [0, 0, 0, 30, 51, 3, 3, 51, 30, 0, 0],       // c
+    [48, 48, 48, 60, 54, 51, 51, 51, 30, 0, 0],  // d
+    [0, 0, 0, 30, 51, 63, 3, 51, 30, 0, 0],      // e
+    [28, 54, 38, 6, 15, 6, 6, 6, 15, 0, 0],      // f
+    [0, 0, 30, 51, 51, 51, 62, 48, 51, 30, 0],   // g
+    [3, 3, 3, 27, 55, 51, 51, 51, 51, 0, 0],     // h
+    [12, 12, 0, 14, 12, 12, 12, 12, 30, 0, 0],   // i
+    [48, 48, 0, 56, 48, 48, 48, 48, 51, 30, 0],  // j
+    [3, 3, 3, 51, 27, 15, 15, 27, 51, 0, 0],     // k
+    [14, 12, 12, 12, 12, 12, 12, 12, 30, 0, 0],  // l
+    [0, 0, 0, 29, 63, 43, 43, 43, 43, 0, 0],     // m
+    [0, 0, 0, 29, 51, 51, 51, 51, 51, 0, 0],     // n
+    [0, 0, 0, 30, 51, 51, 51, 51, 30, 0, 0],     // o
+    [0, 0, 0, 30, 51, 51, 51, 31, 3, 3, 0],      // p
+    [0, 0, 0, 30, 51, 51, 51, 62, 48, 48, 0],    // q
+    [0, 0, 0, 29, 55, 51, 3, 3, 7, 0, 0],        // r
+    [0, 0, 0, 30, 51, 6, 24, 51, 30, 0, 0],      // s
+    [4, 6, 6, 15, 6, 6, 6, 54, 28, 0, 0],        // t
+    [0, 0, 0, 27, 27, 27, 27, 27, 54, 0, 0],     // u
+    [0, 0, 0, 51, 51, 51, 51, 30, 12, 0, 0],     // v
+    [0, 0, 0, 51, 51, 51, 63, 63, 18, 0, 0],     // w
+    [0, 0, 0, 51, 30, 12, 12, 30, 51, 0, 0],     // x
+    [0, 0, 0, 51, 51, 51, 62, 48, 24, 15, 0],    // y
+    [0, 0, 0, 63, 27, 12, 6, 51, 63, 0, 0],      // z
+    [56, 12, 12, 12, 7, 12, 12, 12, 56, 0, 0],   // {
+    [12, 12, 12, 12, 12, 12, 12, 12, 12, 0, 0],  // |
+    [7, 12, 12, 12, 56, 12, 12, 12, 7, 0, 0],    // }
+    [38, 45, 25, 0, 0, 0, 0, 0, 0, 0, 0],        // ~
+];
+
+/// Shown for characters without a bitmap.
+const BLACK_SQUARE: [u8; 11] = [63, 63, 63, 63, 63, 63, 63, 63, 63, 0, 0];
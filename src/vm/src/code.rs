@@ -2,19 +2,37 @@ use crate::instruction::{PushPop::*, *};
 use crate::parser::{Command, ParseResult};
 
 pub fn generate_code(parse_result: ParseResult) -> String {
-    let body: String = parse_result
-        .commands
-        .into_iter()
-        .enumerate()
-        .filter_map(|(cmd_index, cmd)| generate(&cmd, cmd_index))
-        .collect();
-    format!(
+    generate_code_mapped(parse_result).0
+}
+
+/// Same as `generate_code`, also giving the VM line of every generated line.
+pub fn generate_code_mapped(parse_result: ParseResult) -> (String, Vec<Option<usize>>) {
+    generate_module(parse_result, generate)
+}
+
+/// Joins the code generated for each command between the module markers,
+/// along with the VM line every line of it comes from.
+pub(crate) fn generate_module(
+    parse_result: ParseResult,
+    generate: impl Fn(&Command, usize) -> Option<String>,
+) -> (String, Vec<Option<usize>>) {
+    let mut body = String::new();
+    let mut lines = vec![None];
+    for (cmd_index, cmd) in parse_result.commands.iter().enumerate() {
+        if let Some(code) = generate(cmd, cmd_index) {
+            lines.extend(code.lines().map(|_| Some(cmd.line)));
+            body += &code;
+        }
+    }
+    lines.push(None);
+    let code = format!(
         "///@module-start '{module}'
 {body}\
 ///@module-end '{module}'",
         module = parse_result.module,
         body = body
-    )
+    );
+    (code, lines)
 }
 
 // Wrap format! to provide some builtins
@@ -43,7 +61,9 @@ fn generate(cmd: &Command, cmd_index: usize) -> Option<String> {
         )),
         Instruction::Return() => Some(generate_inst_return(cmd)),
         Instruction::PushPop(x) => generate_inst_pushpop(x, cmd),
-        Instruction::Arithmetic(cmd_type) => generate_inst_arithmetic(cmd_type, cmd_index),
+        Instruction::Arithmetic(cmd_type) => {
+            generate_inst_arithmetic(cmd_type, cmd.module_name, cmd_index)
+        }
         Instruction::Label(label, func_name) => {
             Some(generate_inst_label(label, cmd, func_name.clone()))
         }
@@ -59,7 +79,7 @@ fn generate(cmd: &Command, cmd_index: usize) -> Option<String> {
     if let Some(code) = asm {
         Some(format!("// {}\n{}\n", cmd.raw, code))
     } else {
-        println!("Unknown instruction: {:?}", cmd);
+        eprintln!("Unknown instruction: {}", cmd.raw);
         None
     }
 }
@@ -79,7 +99,7 @@ M=D
     )
 }
 
-fn generate_inst_arithmetic(inst: &str, cmd_index: usize) -> Option<String> {
+fn generate_inst_arithmetic(inst: &str, module_name: &str, cmd_index: usize) -> Option<String> {
     match inst {
         "add" => Some(format_asm!(
             "\
@@ -132,7 +152,7 @@ A=M-1
 M={TRUE}
 ({label_prefix}_CONT)
 ",
-            label_prefix = format!("EQ_LABEL_{}", cmd_index)
+            label_prefix = format!("{}.EQ_LABEL_{}", module_name, cmd_index)
         )),
         "gt" => Some(format_asm!(
             "\
@@ -156,7 +176,7 @@ A=M-1
 M={TRUE}
 ({label_prefix}_CONT)
 ",
-            label_prefix = format!("JGT_LABEL_{}", cmd_index)
+            label_prefix = format!("{}.JGT_LABEL_{}", module_name, cmd_index)
         )),
         "lt" => Some(format_asm!(
             "\
@@ -180,7 +200,7 @@ A=M-1
 M={TRUE}
 ({label_prefix}_CONT)
 ",
-            label_prefix = format!("JLT_LABEL_{}", cmd_index)
+            label_prefix = format!("{}.JLT_LABEL_{}", module_name, cmd_index)
         )),
         "not" => Some(format_asm!(
             "\
@@ -228,7 +248,7 @@ fn get_pointer_base(segment: &str) -> Option<(&'static str, bool)> {
 
 fn generate_inst_pushpop(inst: &PushPopInstruction, cmd: &Command) -> Option<String> {
     match (&inst.inst_type, inst.segment.as_str()) {
-        (Push { .. }, "static") => Some(format_asm!(
+        (Push, "static") => Some(format_asm!(
             "\
 @{label}.{addr}
 D=M
@@ -241,7 +261,7 @@ M=M+1\
             label = cmd.module_name,
             addr = inst.addr,
         )),
-        (Pop { .. }, "static") => Some(format_asm!(
+        (Pop, "static") => Some(format_asm!(
             "\
 @SP
 M=M-1
@@ -254,7 +274,7 @@ M=D\
             label = cmd.module_name,
             addr = inst.addr,
         )),
-        (Push { .. }, "pointer") => {
+        (Push, "pointer") => {
             let label = if inst.addr == 0 { "THIS" } else { "THAT" };
             Some(format_asm!(
                 "\
@@ -269,7 +289,7 @@ M=M+1\
                 label = label
             ))
         }
-        (Pop { .. }, "pointer") => {
+        (Pop, "pointer") => {
             let label = if inst.addr == 0 { "THIS" } else { "THAT" };
             Some(format_asm!(
                 "\
@@ -284,7 +304,7 @@ M=D\
                 label = label
             ))
         }
-        (Push { .. }, "constant") => Some(format_asm!(
+        (Push, "constant") => Some(format_asm!(
             "\
 @{addr}
 D=A
@@ -296,7 +316,7 @@ M=M+1\
 ",
             addr = inst.addr
         )),
-        (Push { .. }, segment) => {
+        (Push, segment) => {
             let (pointer_base, is_relative) = get_pointer_base(segment)?;
             let asm_set_segment = if is_relative { "A=D+M" } else { "A=D+A" };
             Some(format_asm!(
@@ -317,7 +337,7 @@ M=M+1\
                 asm_set_segment = asm_set_segment,
             ))
         }
-        (Pop { .. }, segment) => {
+        (Pop, segment) => {
             let (pointer_base, is_relative) = get_pointer_base(segment)?;
             let asm_set_segment = if is_relative { "D=D+M" } else { "D=D+A" };
             Some(format_asm!(
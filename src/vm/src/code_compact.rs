@@ -0,0 +1,297 @@
+//! Size optimized alternative to `code`: comparisons, calls and returns jump
+//! to shared routines emitted once by `generate_bootstrap`, and stack
+//! operations use `AM=M+1`/`AM=M-1` instead of reloading `@SP`.
+
+use crate::code;
+use crate::instruction::{PushPop::*, *};
+use crate::parser::{Command, ParseResult};
+
+const PUSH_D: &str = "@SP\nAM=M+1\nA=A-1\nM=D";
+const POP_D: &str = "@SP\nAM=M-1\nD=M";
+
+pub fn generate_code(parse_result: ParseResult) -> String {
+    generate_code_mapped(parse_result).0
+}
+
+/// Same as `generate_code`, also giving the VM line of every generated line.
+pub fn generate_code_mapped(parse_result: ParseResult) -> (String, Vec<Option<usize>>) {
+    code::generate_module(parse_result, generate)
+}
+
+fn generate(cmd: &Command, cmd_index: usize) -> Option<String> {
+    let asm = match &cmd.inst {
+        Instruction::Function(name, n_locals) => generate_function(name, *n_locals),
+        Instruction::Call(name, n_args) => generate_call(
+            name,
+            *n_args,
+            &format!("{}$ret.{}", cmd.module_name, cmd_index),
+        ),
+        Instruction::Return() => "@__RETURN\n0;JMP".to_string(),
+        Instruction::PushPop(inst) => generate_pushpop(inst, cmd.module_name)?,
+        Instruction::Arithmetic(op) => {
+            generate_arithmetic(op, &format!("{}$cmp.{}", cmd.module_name, cmd_index))?
+        }
+        Instruction::Label(label, func_name) => format!("({})", full_label(label, func_name)),
+        Instruction::Goto(label, func_name) => {
+            format!("@{}\n0;JMP", full_label(label, func_name))
+        }
+        Instruction::IfGoto(label, func_name) => {
+            format!("{}\n@{}\nD;JNE", POP_D, full_label(label, func_name))
+        }
+    };
+    Some(format!("// {}\n{}\n", cmd.raw, asm))
+}
+
+fn full_label(label: &str, func_name: &Option<String>) -> String {
+    match func_name {
+        Some(name) => format!("{}${}", name, label),
+        None => label.to_string(),
+    }
+}
+
+/// Bootstrap code followed by the shared routines.
+pub fn generate_bootstrap(program_name: &str) -> String {
+    format!(
+        "\
+///@program-bootstrap-start
+@256
+D=A
+@SP
+M=D
+{call_init}
+{routines}
+///@program-bootstrap-end\
+",
+        call_init = generate_call("Sys.init", 0, &format!("{}$ret.bootstrap", program_name)),
+        routines = generate_routines(),
+    )
+}
+
+/// Shared routines used by the generated code, behind a halt loop so
+/// execution can't fall into them.
+pub fn generate_routines() -> String {
+    format!(
+        "\
+(__HALT)
+@__HALT
+0;JMP
+{compare_eq}
+{compare_gt}
+{compare_lt}
+{call}
+{return_}",
+        compare_eq = generate_compare_routine("EQ"),
+        compare_gt = generate_compare_routine("GT"),
+        compare_lt = generate_compare_routine("LT"),
+        call = generate_call_routine(),
+        return_ = generate_return_routine(),
+    )
+}
+
+/// `__EQ`, `__GT` or `__LT`: compares the two topmost values, return address in D.
+fn generate_compare_routine(jump: &str) -> String {
+    format!(
+        "\
+(__{jump})
+@R13
+M=D
+{pop_d}
+A=A-1
+D=M-D
+M=-1
+@__{jump}_TRUE
+D;J{jump}
+@SP
+A=M-1
+M=0
+(__{jump}_TRUE)
+@R13
+A=M
+0;JMP",
+        jump = jump,
+        pop_d = POP_D,
+    )
+}
+
+/// `__CALL`: function address in R13, argument count in R14, return address in D.
+fn generate_call_routine() -> String {
+    let push_pointer = |pointer: &str| format!("@{}\nD=M\n{}", pointer, PUSH_D);
+    format!(
+        "\
+(__CALL)
+{push_d}
+{push_lcl}
+{push_arg}
+{push_this}
+{push_that}
+@R14
+D=M
+@5
+D=D+A
+@SP
+D=M-D
+@ARG
+M=D
+@SP
+D=M
+@LCL
+M=D
+@R13
+A=M
+0;JMP",
+        push_d = PUSH_D,
+        push_lcl = push_pointer("LCL"),
+        push_arg = push_pointer("ARG"),
+        push_this = push_pointer("THIS"),
+        push_that = push_pointer("THAT"),
+    )
+}
+
+/// `__RETURN`: same frame handling as `code::generate_inst_return`.
+fn generate_return_routine() -> String {
+    let restore = |pointer: &str| format!("@R14\nAM=M-1\nD=M\n@{}\nM=D", pointer);
+    format!(
+        "\
+(__RETURN)
+@LCL
+D=M
+@R14
+M=D
+@5
+A=D-A
+D=M
+@R15
+M=D
+{pop_d}
+@ARG
+A=M
+M=D
+@ARG
+D=M+1
+@SP
+M=D
+{restore_that}
+{restore_this}
+{restore_arg}
+{restore_lcl}
+@R15
+A=M
+0;JMP",
+        pop_d = POP_D,
+        restore_that = restore("THAT"),
+        restore_this = restore("THIS"),
+        restore_arg = restore("ARG"),
+        restore_lcl = restore("LCL"),
+    )
+}
+
+fn generate_call(name: &str, n_args: usize, label_return: &str) -> String {
+    format!(
+        "\
+@{n_args}
+D=A
+@R14
+M=D
+@{name}
+D=A
+@R13
+M=D
+@{label_return}
+D=A
+@__CALL
+0;JMP
+({label_return})",
+        n_args = n_args,
+        name = name,
+        label_return = label_return,
+    )
+}
+
+fn generate_function(name: &str, n_locals: usize) -> String {
+    if n_locals == 0 {
+        return format!("({})", name);
+    }
+    let zeroes = vec!["M=0"; n_locals].join("\nA=A+1\n");
+    format!("({})\n@SP\nA=M\n{}\nD=A+1\n@SP\nM=D", name, zeroes)
+}
+
+fn generate_arithmetic(op: &str, label_return: &str) -> Option<String> {
+    let binary = |expr: &str| format!("{}\nA=A-1\nM={}", POP_D, expr);
+    let compare = |jump: &str| {
+        format!(
+            "@{label}\nD=A\n@__{jump}\n0;JMP\n({label})",
+            label = label_return,
+            jump = jump
+        )
+    };
+    Some(match op {
+        "add" => binary("D+M"),
+        "sub" => binary("M-D"),
+        "and" => binary("D&M"),
+        "or" => binary("D|M"),
+        "neg" => "@SP\nA=M-1\nM=-M".to_string(),
+        "not" => "@SP\nA=M-1\nM=!M".to_string(),
+        "eq" => compare("EQ"),
+        "gt" => compare("GT"),
+        "lt" => compare("LT"),
+        _ => return None,
+    })
+}
+
+fn segment_base(segment: &str) -> Option<&'static str> {
+    match segment {
+        "argument" => Some("ARG"),
+        "local" => Some("LCL"),
+        "this" => Some("THIS"),
+        "that" => Some("THAT"),
+        _ => None,
+    }
+}
+
+/// Register holding a value with a fixed address, if the segment has one.
+fn fixed_address(segment: &str, addr: u16, module_name: &str) -> Option<String> {
+    match segment {
+        "static" => Some(format!("{}.{}", module_name, addr)),
+        "temp" => Some((5 + addr).to_string()),
+        "pointer" if addr == 0 => Some("THIS".into()),
+        "pointer" => Some("THAT".into()),
+        _ => None,
+    }
+}
+
+fn generate_pushpop(inst: &PushPopInstruction, module_name: &str) -> Option<String> {
+    let addr = inst.addr;
+    let segment = inst.segment.as_str();
+    Some(match &inst.inst_type {
+        Push if segment == "constant" => match addr {
+            0 | 1 => format!("@SP\nAM=M+1\nA=A-1\nM={}", addr),
+            _ => format!("@{}\nD=A\n{}", addr, PUSH_D),
+        },
+        Push => match fixed_address(segment, addr, module_name) {
+            Some(register) => format!("@{}\nD=M\n{}", register, PUSH_D),
+            None => {
+                let base = segment_base(segment)?;
+                let load = match addr {
+                    0 => format!("@{}\nA=M\nD=M", base),
+                    1 => format!("@{}\nA=M+1\nD=M", base),
+                    _ => format!("@{}\nD=A\n@{}\nA=D+M\nD=M", addr, base),
+                };
+                format!("{}\n{}", load, PUSH_D)
+            }
+        },
+        Pop => match fixed_address(segment, addr, module_name) {
+            Some(register) => format!("{}\n@{}\nM=D", POP_D, register),
+            None => {
+                let base = segment_base(segment)?;
+                match addr {
+                    0 => format!("{}\n@{}\nA=M\nM=D", POP_D, base),
+                    1 => format!("{}\n@{}\nA=M+1\nM=D", POP_D, base),
+                    _ => format!(
+                        "@{}\nD=A\n@{}\nD=D+M\n@R13\nM=D\n{}\n@R13\nA=M\nM=D",
+                        addr, base, POP_D
+                    ),
+                }
+            }
+        },
+    })
+}
@@ -1,14 +1,113 @@
+use std::error::Error;
+use std::path::{Path, PathBuf};
+
+/// Path standing for stdin as the input, or stdout as the output.
+pub const STDIO: &str = "-";
+
+pub const USAGE: &str = "\
+Usage: vm [options] <file.vm | directory | ->
+
+Translates VM code into <file>.asm, or <directory>/<directory>.asm for all the
+.vm files of a directory. Reading from stdin (`-`) writes to stdout.
+
+Options:
+  -o, --output <path>  Output file, `-` for stdout
+      --compact        Use shared compare/call/return routines for a smaller ROM
+      --no-bootstrap   Don't emit the code setting up the stack and calling Sys.init
+      --check          Only validate the input, don't write anything
+  -v, --verbose        Report what was written
+  -q, --quiet          Only report errors
+  -h, --help           Print this help
+";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default)]
+pub enum Verbosity {
+    Quiet,
+    #[default]
+    Normal,
+    Verbose,
+}
+
+/// Code generation options, shared by the CLI and programs using `translate`.
+#[derive(Debug, Default, Clone)]
+pub struct TranslateOptions {
+    /// Use shared compare/call/return routines to reduce the ROM size.
+    pub compact: bool,
+    /// Leave out the bootstrap code, e.g. for tests setting up the stack themselves.
+    pub no_bootstrap: bool,
+}
+
+#[derive(Debug, Default)]
 pub struct Config {
+    /// Source file or directory, `STDIO` for stdin.
     pub source_path: String,
+    /// Output file, `STDIO` for stdout.
+    pub output: Option<String>,
+    pub options: TranslateOptions,
+    pub check: bool,
+    pub verbosity: Verbosity,
+    pub help: bool,
 }
 
 impl Config {
-    pub fn new(args: &[String]) -> Result<Self, &'static str> {
-        if args.len() < 2 {
-            return Err("not enough arguments");
+    pub fn new(args: &[String]) -> Result<Self, Box<dyn Error>> {
+        let mut config = Config::default();
+        let mut source_path = None;
+        let mut args = args.iter().skip(1);
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "-h" | "--help" => config.help = true,
+                "-o" | "--output" => {
+                    config.output = Some(args.next().ok_or("missing output path")?.clone())
+                }
+                "--compact" => config.options.compact = true,
+                "--no-bootstrap" => config.options.no_bootstrap = true,
+                "--check" => config.check = true,
+                "-v" | "--verbose" => config.verbosity = Verbosity::Verbose,
+                "-q" | "--quiet" => config.verbosity = Verbosity::Quiet,
+                _ if arg.starts_with('-') && arg != STDIO => {
+                    return Err(format!("unknown option: {}", arg).into())
+                }
+                _ if source_path.is_none() => source_path = Some(arg.clone()),
+                _ => return Err(format!("unexpected argument: {}", arg).into()),
+            }
+        }
+        if !config.help {
+            config.source_path = source_path.ok_or("not enough arguments")?;
+        }
+        Ok(config)
+    }
+
+    /// Where to write the output, `STDIO` for stdout.
+    pub fn target(&self) -> String {
+        if let Some(output) = &self.output {
+            return output.clone();
+        }
+        if self.source_path == STDIO {
+            return STDIO.into();
         }
+        let source_path = Path::new(&self.source_path);
+        let mut target = if source_path.is_dir() {
+            source_path.join(program_name(source_path))
+        } else {
+            PathBuf::from(source_path)
+        };
+        target.set_extension("asm");
+        target.to_string_lossy().into_owned()
+    }
+}
 
-        let source_path = args[1].clone();
-        Ok(Config { source_path })
+/// Name of the program in a file or directory, `Main` for stdin.
+pub fn program_name(source_path: &Path) -> String {
+    if source_path == Path::new(STDIO) {
+        return "Main".into();
     }
+    // Canonicalize so that e.g. `.` is named after the current directory
+    let source_path = source_path
+        .canonicalize()
+        .unwrap_or_else(|_| source_path.to_path_buf());
+    source_path
+        .file_stem()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default()
 }
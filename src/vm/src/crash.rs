@@ -0,0 +1,187 @@
+//! Post-mortem crash reports. The call chain is read from RAM alone, walking
+//! backwards from `LCL` through the frames `code::generate_inst_call` lays
+//! out: return address, LCL, ARG, THIS and THAT, below each function's locals.
+//! Function names come from a symbol map, so the walk works on the ROM
+//! addresses of a translated program, with the assembler's symbols, as well as
+//! on the interpreter's commands, with `Program::symbol_map`.
+
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+
+use hasm::symbols::SymbolMap;
+
+use crate::debugger::{Debugger, Frame, StopReason};
+use crate::halt::{HaltDetector, HaltReason};
+use crate::interpreter::{Interpreter, ARG, LCL, THAT, THIS};
+
+type Res<T = ()> = Result<T, Box<dyn Error>>;
+
+/// Function containing an address: the closest function label before it.
+fn function_at<'a>(
+    symbols: &'a SymbolMap,
+    n_locals: &HashMap<String, usize>,
+    address: usize,
+) -> Option<&'a str> {
+    symbols
+        .labels
+        .iter()
+        .filter(|(name, start)| **start as usize <= address && n_locals.contains_key(*name))
+        .max_by_key(|(_, start)| **start)
+        .map(|(name, _)| name.as_str())
+}
+
+/// Frames from the function executing `pc` to the outermost one, whose return
+/// address has no `$ret.` label, like the bootstrap's. Labels missing from
+/// `n_locals`, which gives the number of locals of each function, aren't
+/// functions. The walk stops early at a frame which isn't below the previous
+/// one, as the stack is corrupt.
+pub fn call_chain(
+    ram: &[i16],
+    pc: usize,
+    symbols: &SymbolMap,
+    n_locals: &HashMap<String, usize>,
+) -> Vec<Frame> {
+    let word = |pointer: i16, offset: i16| {
+        let address = pointer.wrapping_add(offset) as u16 as usize;
+        ram.get(address).copied().unwrap_or_default()
+    };
+    let mut frames = vec![];
+    let mut pc = pc;
+    let (mut lcl, mut arg, mut this, mut that) = (
+        word(LCL as i16, 0),
+        word(ARG as i16, 0),
+        word(THIS as i16, 0),
+        word(THAT as i16, 0),
+    );
+    while let Some(function) = function_at(symbols, n_locals, pc) {
+        // Arguments lie between ARG and the saved return address
+        let Ok(n_args) = usize::try_from(lcl as i32 - 5 - arg as i32) else {
+            break;
+        };
+        let return_address = word(lcl, -5) as u16;
+        let return_label = symbols
+            .labels_at(return_address)
+            .find(|label| label.contains("$ret."))
+            .map(String::from);
+        let caller_lcl = word(lcl, -4);
+        let is_last = return_label.is_none() || caller_lcl >= lcl;
+        frames.push(Frame {
+            function: function.into(),
+            pc,
+            lcl,
+            arg,
+            this,
+            that,
+            n_args,
+            n_locals: n_locals[function],
+            return_label,
+        });
+        if is_last {
+            break;
+        }
+        pc = (return_address as usize).saturating_sub(1);
+        that = word(lcl, -1);
+        this = word(lcl, -2);
+        arg = word(lcl, -3);
+        lcl = caller_lcl;
+    }
+    frames
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CrashReason {
+    /// Code given to `Sys.error`.
+    SysError(i16),
+    /// Didn't halt within the step limit.
+    StepLimit(u64),
+    Breakpoint(String),
+}
+
+impl fmt::Display for CrashReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CrashReason::SysError(code) => write!(f, "Sys.error called with code {}", code),
+            CrashReason::StepLimit(steps) => write!(f, "Didn't halt within {} steps", steps),
+            CrashReason::Breakpoint(breakpoint) => write!(f, "Breakpoint {} hit", breakpoint),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct CrashReport {
+    pub reason: CrashReason,
+    /// Commands executed before the crash.
+    pub steps: u64,
+    /// From the function which crashed to the outermost one.
+    pub frames: Vec<Frame>,
+    /// The frames with their locations, segments and Jack variables.
+    pub backtrace: String,
+}
+
+impl fmt::Display for CrashReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{}, after {} steps", self.reason, self.steps)?;
+        writeln!(f, "{}", self.backtrace)
+    }
+}
+
+impl Debugger {
+    pub fn crash_report(&self, reason: CrashReason, pc: usize) -> CrashReport {
+        let frames = self.call_stack_at(pc);
+        CrashReport {
+            reason,
+            steps: self.interpreter.steps,
+            backtrace: self.format_frames(&frames),
+            frames,
+        }
+    }
+
+    /// Runs until the program halts or loops forever like `Sys.halt`,
+    /// returning `None`, or crashes: calls `Sys.error`, hits a breakpoint or
+    /// runs `max_steps` commands without halting. `before_step` can set up the
+    /// RAM, e.g. the keyboard, before each command.
+    pub fn run_to_crash(
+        &mut self,
+        max_steps: u64,
+        mut before_step: impl FnMut(&mut Interpreter),
+    ) -> Res<Option<CrashReport>> {
+        let sys_error = self.interpreter.program.functions.get("Sys.error").copied();
+        let mut detector = HaltDetector::default();
+        for _ in 0..max_steps {
+            if let Some(HaltReason::Loop { .. }) = detector.check(&self.interpreter) {
+                return Ok(None);
+            }
+            before_step(&mut self.interpreter);
+            let call_pc = self.interpreter.pc;
+            let reason = self.step_into()?;
+            // The built-in `Sys.error` halts at once, leaving the frame of its caller
+            if let Some(code) = self.interpreter.os.error {
+                return Ok(Some(
+                    self.crash_report(CrashReason::SysError(code), call_pc),
+                ));
+            }
+            let pc = self.interpreter.pc;
+            if sys_error == Some(pc) {
+                let code = self
+                    .interpreter
+                    .peek(self.interpreter.ram[ARG] as u16 as usize);
+                return Ok(Some(self.crash_report(CrashReason::SysError(code), pc)));
+            }
+            match reason {
+                StopReason::Breakpoint(i) => {
+                    let breakpoint = self.breakpoints[i].to_string();
+                    return Ok(Some(
+                        self.crash_report(CrashReason::Breakpoint(breakpoint), pc),
+                    ));
+                }
+                StopReason::Halted => return Ok(None),
+                _ => {}
+            }
+        }
+        Ok(Some(self.crash_report(
+            CrashReason::StepLimit(max_steps),
+            self.interpreter.pc,
+        )))
+    }
+}
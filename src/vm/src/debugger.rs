@@ -0,0 +1,486 @@
+//! VM level debugger on top of the `Interpreter`: breakpoints, stepping
+//! over calls, call stack and RAM watches. Jack lines and variable names are
+//! shown when the compiler's `DebugInfo` is given.
+
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fmt::{self, Write};
+
+use hasm::source_map::{Location, SourceMap};
+
+use crate::crash;
+use crate::interpreter::{Interpreter, Op, Program};
+
+type Res<T = ()> = Result<T, Box<dyn Error>>;
+
+/// Commands run by `continue` before giving up, `Sys.halt` never returns.
+pub const DEFAULT_STEP_LIMIT: u64 = 50_000_000;
+
+pub const HELP: &str = "\
+Commands:
+  b, break <target>    Break on a function (Main.main), VM line (Main.vm:12)
+                       or Jack line (Main.jack:5)
+  d, delete <n>        Delete breakpoint <n>
+  w, watch <address>   Stop when a RAM address changes
+  unwatch <address>    Remove a watch
+  c, continue          Run until a breakpoint, a watch or the end
+  s, step              Run one command, stepping into calls
+  n, next              Run one command, stepping over calls
+  f, finish            Run until the current function returns
+  bt, backtrace        Print the call stack and the segments of every frame
+  p, print <name|addr> Print a Jack variable of the current function or a RAM word
+  l, where             Print the current command
+  i, info              List the breakpoints and watches
+  h, help              Print this help
+  q, quit              Exit
+";
+
+/// Jack variable and where the compiled code keeps it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Variable {
+    pub name: String,
+    /// Jack type, e.g. `int` or a class name.
+    pub typ: String,
+    /// VM segment: `argument`, `local`, `this` or `static`.
+    pub segment: String,
+    pub index: u16,
+}
+
+/// What the compiler knows about the VM code it generated.
+#[derive(Debug, Default, Clone)]
+pub struct DebugInfo {
+    /// Jack line of the VM lines, by module name.
+    pub source_maps: BTreeMap<String, SourceMap>,
+    /// Jack variables in scope in each function, by function name.
+    pub variables: BTreeMap<String, Vec<Variable>>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Breakpoint {
+    Function(String),
+    /// Line of the `.vm` file of a module.
+    VmLine(String, usize),
+    /// Line of a `.jack` file, matched against the end of its path.
+    JackLine(String, usize),
+}
+
+impl Breakpoint {
+    /// `Main.main`, `Main.vm:12` or `Main.jack:5`.
+    pub fn parse(s: &str) -> Option<Self> {
+        let Some((file, line)) = s.rsplit_once(':') else {
+            return Some(Breakpoint::Function(s.into()));
+        };
+        let line = line.parse().ok()?;
+        if let Some(module) = file.strip_suffix(".vm") {
+            Some(Breakpoint::VmLine(module.into(), line))
+        } else if file.ends_with(".jack") {
+            Some(Breakpoint::JackLine(file.into(), line))
+        } else {
+            None
+        }
+    }
+}
+
+impl fmt::Display for Breakpoint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Breakpoint::Function(name) => f.write_str(name),
+            Breakpoint::VmLine(module, line) => write!(f, "{}.vm:{}", module, line),
+            Breakpoint::JackLine(file, line) => write!(f, "{}:{}", file, line),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StopReason {
+    /// Index of the breakpoint which was hit.
+    Breakpoint(usize),
+    Watch {
+        address: usize,
+        old: i16,
+        new: i16,
+    },
+    /// The stepping command is done.
+    Step,
+    Halted,
+    /// Ran the step limit without stopping.
+    StepLimit,
+}
+
+/// Function activation, read from the frame layout of `code::generate_inst_call`
+/// by `crash::call_chain`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Frame {
+    pub function: String,
+    /// Command being executed, the `call` in callers.
+    pub pc: usize,
+    pub lcl: i16,
+    pub arg: i16,
+    pub this: i16,
+    pub that: i16,
+    pub n_args: usize,
+    pub n_locals: usize,
+    /// Label of the return address, `Module$ret.N`, unless it's the outermost frame.
+    pub return_label: Option<String>,
+}
+
+#[derive(Debug)]
+pub struct Debugger {
+    pub interpreter: Interpreter,
+    pub info: DebugInfo,
+    pub breakpoints: Vec<Breakpoint>,
+    /// Watched RAM addresses, with their last value.
+    pub watches: Vec<(usize, i16)>,
+    pub step_limit: u64,
+    /// Calls which didn't return yet.
+    depth: usize,
+}
+
+impl Debugger {
+    pub fn new(program: Program, info: DebugInfo) -> Self {
+        Self {
+            interpreter: Interpreter::new(program),
+            info,
+            breakpoints: vec![],
+            watches: vec![],
+            step_limit: DEFAULT_STEP_LIMIT,
+            depth: 0,
+        }
+    }
+
+    /// Sets up the stack and stops on the first command of `Sys.init`.
+    pub fn bootstrap(&mut self) -> Res {
+        self.interpreter.bootstrap()?;
+        self.depth = 1;
+        Ok(())
+    }
+
+    fn program(&self) -> &Program {
+        &self.interpreter.program
+    }
+
+    fn ram(&self, address: i16) -> i16 {
+        self.interpreter.peek(address as u16 as usize)
+    }
+
+    /// Start of the function containing a command.
+    fn function_at(&self, pc: usize) -> Option<(&str, usize)> {
+        self.program()
+            .functions
+            .iter()
+            .filter(|(_, start)| **start <= pc)
+            .max_by_key(|(_, start)| **start)
+            .map(|(name, start)| (name.as_str(), *start))
+    }
+
+    pub fn vm_location(&self, pc: usize) -> Option<Location> {
+        let command = self.program().commands.get(pc)?;
+        Some(Location {
+            file: format!("{}.vm", command.module),
+            line: command.line,
+        })
+    }
+
+    pub fn jack_location(&self, pc: usize) -> Option<&Location> {
+        let command = self.program().commands.get(pc)?;
+        self.info
+            .source_maps
+            .get(&command.module)?
+            .get(command.line)
+    }
+
+    fn breakpoint_hit(&self, previous_pc: usize) -> Option<usize> {
+        let pc = self.interpreter.pc;
+        let command = self.program().commands.get(pc)?;
+        let jack = self.jack_location(pc);
+        // Line breakpoints stop when entering the line, not on each of its commands
+        let entered_vm_line = self.vm_location(previous_pc) != self.vm_location(pc);
+        let entered_jack_line = jack.is_some() && self.jack_location(previous_pc) != jack;
+        self.breakpoints
+            .iter()
+            .position(|breakpoint| match breakpoint {
+                Breakpoint::Function(name) => self.program().functions.get(name) == Some(&pc),
+                Breakpoint::VmLine(module, line) => {
+                    entered_vm_line && command.module == *module && command.line == *line
+                }
+                Breakpoint::JackLine(file, line) => {
+                    entered_jack_line
+                        && jack.is_some_and(|jack| jack.file.ends_with(file) && jack.line == *line)
+                }
+            })
+    }
+
+    /// Runs one command, returns why to stop if there's a reason to.
+    fn execute(&mut self) -> Res<Option<StopReason>> {
+        let previous_pc = self.interpreter.pc;
+        let op = self
+            .program()
+            .commands
+            .get(previous_pc)
+            .map(|command| command.op);
+        self.interpreter.step()?;
+        match op {
+            Some(Op::Call(..)) => self.depth += 1,
+            Some(Op::Return) => self.depth = self.depth.saturating_sub(1),
+            _ => {}
+        }
+        if self.interpreter.is_halted() {
+            return Ok(Some(StopReason::Halted));
+        }
+        for i in 0..self.watches.len() {
+            let (address, old) = self.watches[i];
+            let new = self.interpreter.peek(address);
+            if new != old {
+                self.watches[i].1 = new;
+                return Ok(Some(StopReason::Watch { address, old, new }));
+            }
+        }
+        Ok(self.breakpoint_hit(previous_pc).map(StopReason::Breakpoint))
+    }
+
+    /// Runs until `done` holds after a command, or another reason to stop.
+    fn run_until(&mut self, done: impl Fn(&Self) -> bool) -> Res<StopReason> {
+        if self.interpreter.is_halted() {
+            return Ok(StopReason::Halted);
+        }
+        for _ in 0..self.step_limit {
+            if let Some(reason) = self.execute()? {
+                return Ok(reason);
+            }
+            if done(self) {
+                return Ok(StopReason::Step);
+            }
+        }
+        Ok(StopReason::StepLimit)
+    }
+
+    pub fn continue_(&mut self) -> Res<StopReason> {
+        self.run_until(|_| false)
+    }
+
+    pub fn step_into(&mut self) -> Res<StopReason> {
+        self.run_until(|_| true)
+    }
+
+    pub fn step_over(&mut self) -> Res<StopReason> {
+        let depth = self.depth;
+        self.run_until(|debugger| debugger.depth <= depth)
+    }
+
+    pub fn step_out(&mut self) -> Res<StopReason> {
+        let depth = self.depth;
+        self.run_until(|debugger| debugger.depth < depth)
+    }
+
+    /// Frames from the current function to the outermost one.
+    pub fn call_stack(&self) -> Vec<Frame> {
+        self.call_stack_at(self.interpreter.pc)
+    }
+
+    /// Frames read from the RAM, the current function executing `pc`.
+    pub(crate) fn call_stack_at(&self, pc: usize) -> Vec<Frame> {
+        let program = self.program();
+        let n_locals = program
+            .functions
+            .iter()
+            .map(|(name, start)| match program.commands[*start].op {
+                Op::Function(n_locals) => (name.clone(), n_locals),
+                _ => (name.clone(), 0),
+            })
+            .collect();
+        crash::call_chain(&self.interpreter.ram, pc, &program.symbol_map(), &n_locals)
+    }
+
+    /// Value of a Jack variable in a frame.
+    pub fn variable_value(&self, frame: &Frame, variable: &Variable) -> Option<i16> {
+        let index = variable.index as i16;
+        Some(match variable.segment.as_str() {
+            "argument" => self.ram(frame.arg.wrapping_add(index)),
+            "local" => self.ram(frame.lcl.wrapping_add(index)),
+            "this" => self.ram(frame.this.wrapping_add(index)),
+            "static" => {
+                let (_, start) = self.function_at(frame.pc)?;
+                let module = &self.program().commands[start].module;
+                let address = self
+                    .program()
+                    .statics
+                    .get(&format!("{}.{}", module, index))?;
+                self.interpreter.peek(*address as usize)
+            }
+            _ => return None,
+        })
+    }
+
+    fn variables(&self, frame: &Frame) -> &[Variable] {
+        self.info
+            .variables
+            .get(&frame.function)
+            .map_or(&[], Vec::as_slice)
+    }
+
+    /// `Main.vm:4 push constant 2 (Main.jack:5)`
+    pub fn describe(&self, pc: usize) -> String {
+        let Some(command) = self.program().commands.get(pc) else {
+            return "halted".into();
+        };
+        let mut description = format!(
+            "{}.vm:{} {}",
+            command.module,
+            command.line,
+            command.raw.trim()
+        );
+        if let Some(jack) = self.jack_location(pc) {
+            description += &format!(" ({})", jack);
+        }
+        description
+    }
+
+    fn describe_stop(&self, reason: StopReason) -> String {
+        let reason = match reason {
+            StopReason::Breakpoint(i) => format!("Breakpoint {}: {}\n", i + 1, self.breakpoints[i]),
+            StopReason::Watch { address, old, new } => {
+                format!("RAM[{}] changed: {} -> {}\n", address, old, new)
+            }
+            StopReason::Step => String::new(),
+            StopReason::Halted => return format!("Halted after {} steps", self.interpreter.steps),
+            StopReason::StepLimit => format!("Stopped after {} steps\n", self.step_limit),
+        };
+        reason + &self.describe(self.interpreter.pc)
+    }
+
+    pub(crate) fn format_frames(&self, frames: &[Frame]) -> String {
+        let mut out = String::new();
+        for (i, frame) in frames.iter().enumerate() {
+            writeln!(
+                out,
+                "#{} {} at {}",
+                i,
+                frame.function,
+                self.describe(frame.pc)
+            )
+            .unwrap();
+            let words = |base: i16, len: usize| -> Vec<i16> {
+                (0..len as i16)
+                    .map(|offset| self.ram(base.wrapping_add(offset)))
+                    .collect()
+            };
+            writeln!(out, "   argument: {:?}", words(frame.arg, frame.n_args)).unwrap();
+            writeln!(out, "   local: {:?}", words(frame.lcl, frame.n_locals)).unwrap();
+            write!(out, "   this: {}, that: {}", frame.this, frame.that).unwrap();
+            if let Some(label) = &frame.return_label {
+                write!(out, ", returns to {}", label).unwrap();
+            }
+            writeln!(out).unwrap();
+            for variable in self.variables(frame) {
+                if let Some(value) = self.variable_value(frame, variable) {
+                    writeln!(
+                        out,
+                        "   {} {}: {} = {}",
+                        variable.segment, variable.name, variable.typ, value
+                    )
+                    .unwrap();
+                }
+            }
+        }
+        out.trim_end().to_string()
+    }
+
+    fn print(&self, name: &str) -> Res<String> {
+        if let Ok(address) = name.parse::<usize>() {
+            return Ok(format!(
+                "RAM[{}] = {}",
+                address,
+                self.interpreter.peek(address)
+            ));
+        }
+        let frames = self.call_stack();
+        let frame = frames.first().ok_or("No current function")?;
+        let variable = self
+            .variables(frame)
+            .iter()
+            .find(|variable| variable.name == name)
+            .ok_or_else(|| format!("Unknown variable in {}: {}", frame.function, name))?;
+        let value = self
+            .variable_value(frame, variable)
+            .ok_or_else(|| format!("Can't read variable: {}", name))?;
+        Ok(format!("{}: {} = {}", variable.name, variable.typ, value))
+    }
+
+    fn info_lists(&self) -> String {
+        let mut out = String::new();
+        for (i, breakpoint) in self.breakpoints.iter().enumerate() {
+            writeln!(out, "Breakpoint {}: {}", i + 1, breakpoint).unwrap();
+        }
+        for (address, value) in &self.watches {
+            writeln!(out, "Watch RAM[{}] = {}", address, value).unwrap();
+        }
+        out.trim_end().to_string()
+    }
+
+    /// Runs a command of the interactive debugger, returns what to print.
+    pub fn run_command(&mut self, input: &str) -> Res<String> {
+        let mut words = input.split_whitespace();
+        let command = words.next().unwrap_or_default();
+        let argument = words.next();
+        let address = || -> Res<usize> {
+            let address = argument.ok_or("Missing address")?;
+            Ok(address
+                .parse()
+                .map_err(|_| format!("Invalid address: {}", address))?)
+        };
+        Ok(match command {
+            "b" | "break" => {
+                let target = argument.ok_or("Missing breakpoint target")?;
+                let breakpoint = Breakpoint::parse(target)
+                    .ok_or_else(|| format!("Invalid breakpoint: {}", target))?;
+                if let Breakpoint::Function(name) = &breakpoint {
+                    if !self.program().functions.contains_key(name) {
+                        return Err(format!("Unknown function: {}", name).into());
+                    }
+                }
+                self.breakpoints.push(breakpoint);
+                format!("Breakpoint {}: {}", self.breakpoints.len(), target)
+            }
+            "d" | "delete" => {
+                let n: usize = argument.and_then(|n| n.parse().ok()).unwrap_or(0);
+                if n == 0 || n > self.breakpoints.len() {
+                    return Err("Invalid breakpoint number".into());
+                }
+                format!("Deleted breakpoint {}", self.breakpoints.remove(n - 1))
+            }
+            "w" | "watch" => {
+                let address = address()?;
+                self.watches.push((address, self.interpreter.peek(address)));
+                format!("Watching RAM[{}]", address)
+            }
+            "unwatch" => {
+                let address = address()?;
+                self.watches.retain(|(watched, _)| *watched != address);
+                format!("Removed watch on RAM[{}]", address)
+            }
+            "c" | "continue" => {
+                let reason = self.continue_()?;
+                self.describe_stop(reason)
+            }
+            "s" | "step" => {
+                let reason = self.step_into()?;
+                self.describe_stop(reason)
+            }
+            "n" | "next" => {
+                let reason = self.step_over()?;
+                self.describe_stop(reason)
+            }
+            "f" | "finish" => {
+                let reason = self.step_out()?;
+                self.describe_stop(reason)
+            }
+            "bt" | "backtrace" => self.format_frames(&self.call_stack()),
+            "p" | "print" => self.print(argument.ok_or("Missing variable or address")?)?,
+            "l" | "where" => self.describe(self.interpreter.pc),
+            "i" | "info" => self.info_lists(),
+            "h" | "help" => HELP.trim_end().into(),
+            "" => String::new(),
+            _ => return Err(format!("Unknown command: {}, try help", command).into()),
+        })
+    }
+}
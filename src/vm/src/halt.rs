@@ -0,0 +1,206 @@
+//! Detects programs which are done without halting, like the Jack OS
+//! `Sys.halt` spinning in `while (true) {}`, so headless runs can stop.
+
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::builtins::HEAP_BASE;
+use crate::interpreter::{ArithmeticOp, Interpreter, Op, Segment, VmCommand, SP};
+
+/// The stack spans `STACK_BASE..STACK_END`.
+const STACK_END: usize = HEAP_BASE;
+/// Steps between two states kept by `HaltDetector::check_repeat`.
+const REPEAT_INTERVAL: u64 = 100_000;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HaltReason {
+    /// Ran past the last command, by returning from `Sys.init` or calling a
+    /// built-in which halts.
+    Halted,
+    /// At a `goto` closing a loop which goes on forever without changing
+    /// anything, e.g. `while (true) {}`. `pc` is a command index, or a ROM
+    /// address for the CPU emulator.
+    Loop {
+        pc: usize,
+    },
+    /// The live RAM was the same this many steps apart.
+    Idle(u64),
+    StepLimit(u64),
+}
+
+impl fmt::Display for HaltReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HaltReason::Halted => write!(f, "halted"),
+            HaltReason::Loop { pc } => write!(f, "endless loop at {}", pc),
+            HaltReason::Idle(steps) => write!(f, "no change in {} steps", steps),
+            HaltReason::StepLimit(steps) => write!(f, "didn't halt within {} steps", steps),
+        }
+    }
+}
+
+/// Whether two RAM images hold the same values, leaving out the stack above
+/// `SP`, which only holds leftovers of earlier computations.
+pub fn same_live_ram(a: &[i16], b: &[i16]) -> bool {
+    if a.len() != b.len() || a[SP] != b[SP] {
+        return false;
+    }
+    let dead_start = (a[SP] as u16 as usize).min(a.len());
+    let dead_end = STACK_END.clamp(dead_start, a.len());
+    a[..dead_start] == b[..dead_start] && a[dead_end..] == b[dead_end..]
+}
+
+/// Whether a loop body, run from its first command straight to the `goto`
+/// back, always takes the same path without side effects: it only computes
+/// constants, none of its `if-goto`s jumps and the stack ends as it started.
+fn is_endless_loop(body: &[VmCommand]) -> bool {
+    let mut stack: Vec<i16> = vec![];
+    for command in body {
+        match command.op {
+            Op::Label => {}
+            Op::Push(Segment::Constant, value) => stack.push(value as i16),
+            Op::Arithmetic(op) => {
+                let Some(y) = stack.pop() else {
+                    return false;
+                };
+                let value = match op {
+                    ArithmeticOp::Neg => y.wrapping_neg(),
+                    ArithmeticOp::Not => !y,
+                    _ => {
+                        let Some(x) = stack.pop() else {
+                            return false;
+                        };
+                        match op {
+                            ArithmeticOp::Add => x.wrapping_add(y),
+                            ArithmeticOp::Sub => x.wrapping_sub(y),
+                            ArithmeticOp::And => x & y,
+                            ArithmeticOp::Or => x | y,
+                            ArithmeticOp::Eq => -((x == y) as i16),
+                            ArithmeticOp::Gt => -((x > y) as i16),
+                            ArithmeticOp::Lt => -((x < y) as i16),
+                            ArithmeticOp::Neg | ArithmeticOp::Not => unreachable!(),
+                        }
+                    }
+                };
+                stack.push(value);
+            }
+            Op::IfGoto(_) => match stack.pop() {
+                Some(0) => {}
+                _ => return false,
+            },
+            _ => return false,
+        }
+    }
+    stack.is_empty()
+}
+
+/// Checks a run for the reasons in `HaltReason`, before each step.
+#[derive(Debug, Clone, Default)]
+pub struct HaltDetector {
+    /// Stop when the live RAM is the same this many steps apart.
+    pub idle_steps: Option<u64>,
+    /// Step until which the RAM can change from outside, e.g. by the presses
+    /// of a key script, so the program doesn't count as idle before.
+    pub input_until: u64,
+    snapshot: Option<Vec<i16>>,
+    /// Whether the loop closed by a backward `goto` is endless, by its index.
+    loops: HashMap<usize, bool>,
+    /// State kept by `check_repeat`, and the step from which to keep the next one.
+    repeat: Option<State>,
+    next_repeat: u64,
+}
+
+/// Whole state of a machine at a jump target.
+#[derive(Debug, Clone)]
+struct State {
+    pc: usize,
+    steps: u64,
+    registers: Vec<i16>,
+    ram: Vec<i16>,
+}
+
+impl HaltDetector {
+    pub fn new(idle_steps: Option<u64>) -> Self {
+        Self {
+            idle_steps,
+            ..Default::default()
+        }
+    }
+
+    /// Why the interpreter won't do anything new anymore, if it won't.
+    pub fn check(&mut self, interpreter: &Interpreter) -> Option<HaltReason> {
+        if interpreter.is_halted() {
+            return Some(HaltReason::Halted);
+        }
+        let pc = interpreter.pc;
+        let commands = &interpreter.program.commands;
+        if let Op::Goto(target) = commands[pc].op {
+            let endless = target <= pc
+                && *self
+                    .loops
+                    .entry(pc)
+                    .or_insert_with(|| is_endless_loop(&commands[target..pc]));
+            if endless {
+                return Some(HaltReason::Loop { pc });
+            }
+        }
+        self.check_idle(interpreter.steps, &interpreter.ram)
+    }
+
+    /// The idle check alone, for engines without VM commands like the CPU
+    /// emulator, `steps` counting their cycles.
+    pub fn check_idle(&mut self, steps: u64, ram: &[i16]) -> Option<HaltReason> {
+        let idle_steps = self.idle_steps.filter(|steps| *steps > 0)?;
+        if steps < self.input_until || !steps.is_multiple_of(idle_steps) {
+            return None;
+        }
+        let idle = self
+            .snapshot
+            .as_deref()
+            .is_some_and(|snapshot| same_live_ram(snapshot, ram));
+        if idle {
+            return Some(HaltReason::Idle(idle_steps));
+        }
+        self.snapshot = Some(ram.to_vec());
+        None
+    }
+
+    /// The loop check for engines without VM commands, like the CPU emulator,
+    /// whatever code a loop compiles to: call it after each backward jump, with
+    /// `pc` its target. Every `REPEAT_INTERVAL` steps the state at a target is
+    /// kept, and a program back there with the same registers and RAM goes
+    /// around the same loop forever.
+    pub fn check_repeat(
+        &mut self,
+        steps: u64,
+        pc: usize,
+        registers: &[i16],
+        ram: &[i16],
+    ) -> Option<HaltReason> {
+        if steps < self.input_until {
+            return None;
+        }
+        if let Some(state) = &self.repeat {
+            if state.pc == pc {
+                let repeats = state.registers == registers && state.ram == ram;
+                self.repeat = None;
+                return repeats.then_some(HaltReason::Loop { pc });
+            }
+            // The loop was left, or goes through this target less often
+            if steps - state.steps > REPEAT_INTERVAL {
+                self.repeat = None;
+            }
+            return None;
+        }
+        if steps >= self.next_repeat {
+            self.repeat = Some(State {
+                pc,
+                steps,
+                registers: registers.to_vec(),
+                ram: ram.to_vec(),
+            });
+            self.next_repeat = steps + REPEAT_INTERVAL;
+        }
+        None
+    }
+}
@@ -0,0 +1,234 @@
+//! Checks the heap of a RAM snapshot, laid out like `src/os/Memory.jack` and
+//! the built-in `Memory` do it.
+//!
+//! The heap spans `HEAP_BASE..HEAP_END`. Free blocks are linked from
+//! `HEAP_BASE`, each holding the address of the next one (0 for the last) and
+//! its length, header included. Allocated blocks hold the free block they were
+//! taken from and their size, header excluded, in the two words before the
+//! address `Memory.alloc` returned. Blocks follow each other without gaps, so
+//! the allocated ones are found by walking the heap from the start.
+
+use std::collections::HashSet;
+use std::fmt;
+
+use crate::builtins::{HEAP_BASE, HEAP_LENGTH};
+
+pub const HEAP_END: usize = HEAP_BASE + HEAP_LENGTH as usize;
+
+/// Heap block, `length` words long from its header at `address`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Block {
+    pub address: usize,
+    pub length: usize,
+}
+
+impl Block {
+    fn end(&self) -> usize {
+        self.address + self.length
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HeapProblem {
+    /// Free list link from a block to an address outside of the heap.
+    InvalidLink {
+        from: usize,
+        to: i16,
+    },
+    /// Free list links back to a block already visited.
+    Cycle {
+        from: usize,
+        to: usize,
+    },
+    /// Header with a length which doesn't fit in the heap.
+    InvalidBlock {
+        address: usize,
+        length: i16,
+    },
+    Overlap {
+        first: usize,
+        second: usize,
+    },
+}
+
+impl fmt::Display for HeapProblem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HeapProblem::InvalidLink { from, to } => {
+                write!(
+                    f,
+                    "free block {} links to {}, outside of the heap",
+                    from, to
+                )
+            }
+            HeapProblem::Cycle { from, to } => {
+                write!(f, "free list cycles from {} back to {}", from, to)
+            }
+            HeapProblem::InvalidBlock { address, length } => {
+                write!(f, "block {} has an invalid length {}", address, length)
+            }
+            HeapProblem::Overlap { first, second } => {
+                write!(f, "blocks {} and {} overlap", first, second)
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct HeapReport {
+    /// In free list order.
+    pub free: Vec<Block>,
+    /// In address order, up to the first invalid block.
+    pub allocated: Vec<Block>,
+    pub problems: Vec<HeapProblem>,
+}
+
+/// Walks the free list and the allocated blocks of a RAM snapshot.
+pub fn inspect(ram: &[i16]) -> HeapReport {
+    let word = |address: usize| ram.get(address).copied().unwrap_or_default();
+    let mut report = HeapReport::default();
+
+    let mut visited = HashSet::new();
+    let mut node = HEAP_BASE;
+    loop {
+        visited.insert(node);
+        let length = word(node + 1);
+        if length < 2 || node + length as usize > HEAP_END {
+            report.problems.push(HeapProblem::InvalidBlock {
+                address: node,
+                length,
+            });
+            break;
+        }
+        report.free.push(Block {
+            address: node,
+            length: length as usize,
+        });
+        let next = word(node);
+        if next == 0 {
+            break;
+        }
+        let next_node = next as u16 as usize;
+        if !(HEAP_BASE..HEAP_END - 1).contains(&next_node) {
+            report.problems.push(HeapProblem::InvalidLink {
+                from: node,
+                to: next,
+            });
+            break;
+        }
+        if visited.contains(&next_node) {
+            report.problems.push(HeapProblem::Cycle {
+                from: node,
+                to: next_node,
+            });
+            break;
+        }
+        node = next_node;
+    }
+
+    if report.free.is_empty() {
+        // Not initialized by `Memory.init`
+        return report;
+    }
+    let mut address = HEAP_BASE;
+    while address < HEAP_END {
+        if let Some(block) = report.free.iter().find(|b| b.address == address) {
+            address = block.end();
+            continue;
+        }
+        let size = word(address + 1);
+        if size < 0 || address + size as usize + 2 > HEAP_END {
+            report.problems.push(HeapProblem::InvalidBlock {
+                address,
+                length: size,
+            });
+            break;
+        }
+        let block = Block {
+            address,
+            length: size as usize + 2,
+        };
+        report.allocated.push(block);
+        address = block.end();
+    }
+
+    let mut blocks: Vec<Block> = report
+        .free
+        .iter()
+        .chain(&report.allocated)
+        .copied()
+        .collect();
+    blocks.sort_by_key(|block| block.address);
+    for pair in blocks.windows(2) {
+        if pair[1].address < pair[0].end() {
+            report.problems.push(HeapProblem::Overlap {
+                first: pair[0].address,
+                second: pair[1].address,
+            });
+        }
+    }
+    report
+}
+
+impl HeapReport {
+    pub fn is_ok(&self) -> bool {
+        self.problems.is_empty()
+    }
+
+    pub fn free_words(&self) -> usize {
+        self.free.iter().map(|block| block.length).sum()
+    }
+
+    pub fn allocated_words(&self) -> usize {
+        self.allocated.iter().map(|block| block.length).sum()
+    }
+
+    pub fn largest_free(&self) -> usize {
+        self.free
+            .iter()
+            .map(|block| block.length)
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// Share of the free words outside of the largest free block, from 0 to 1.
+    pub fn fragmentation(&self) -> f64 {
+        match self.free_words() {
+            0 => 0.0,
+            free => 1.0 - self.largest_free() as f64 / free as f64,
+        }
+    }
+
+    /// Blocks allocated since an earlier snapshot, e.g. those a program
+    /// didn't free before halting, leaving out the ones the OS keeps.
+    pub fn allocated_since(&self, earlier: &HeapReport) -> Vec<Block> {
+        self.allocated
+            .iter()
+            .filter(|block| !earlier.allocated.contains(block))
+            .copied()
+            .collect()
+    }
+}
+
+impl fmt::Display for HeapReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "Free: {} words in {} block(s), largest {} (fragmentation {:.0}%)",
+            self.free_words(),
+            self.free.len(),
+            self.largest_free(),
+            self.fragmentation() * 100.0
+        )?;
+        writeln!(
+            f,
+            "Allocated: {} words in {} block(s)",
+            self.allocated_words(),
+            self.allocated.len()
+        )?;
+        for problem in &self.problems {
+            writeln!(f, "Problem: {}", problem)?;
+        }
+        Ok(())
+    }
+}
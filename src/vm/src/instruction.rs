@@ -1,17 +1,17 @@
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum PushPop {
     Push,
     Pop,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct PushPopInstruction {
     pub segment: String,
     pub addr: u16,
     pub inst_type: PushPop,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum Instruction {
     PushPop(PushPopInstruction),
     Arithmetic(String),
@@ -22,3 +22,24 @@ pub enum Instruction {
     Return(),
     Call(String, usize),
 }
+
+impl std::fmt::Display for Instruction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Instruction::PushPop(inst) => {
+                let command = match inst.inst_type {
+                    PushPop::Push => "push",
+                    PushPop::Pop => "pop",
+                };
+                write!(f, "{} {} {}", command, inst.segment, inst.addr)
+            }
+            Instruction::Arithmetic(op) => write!(f, "{}", op),
+            Instruction::Label(label, _) => write!(f, "label {}", label),
+            Instruction::Goto(label, _) => write!(f, "goto {}", label),
+            Instruction::IfGoto(label, _) => write!(f, "if-goto {}", label),
+            Instruction::Function(name, n_locals) => write!(f, "function {} {}", name, n_locals),
+            Instruction::Return() => write!(f, "return"),
+            Instruction::Call(name, n_args) => write!(f, "call {} {}", name, n_args),
+        }
+    }
+}
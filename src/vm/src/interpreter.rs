@@ -0,0 +1,516 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::fs;
+use std::path;
+
+use hasm::symbols::SymbolMap;
+
+use crate::builtins::{Builtin, OsState};
+use crate::instruction::{Instruction, PushPop, PushPopInstruction};
+use crate::parser;
+
+type Res<T = ()> = Result<T, Box<dyn Error>>;
+
+pub const RAM_SIZE: usize = 0x8000;
+
+pub const SP: usize = 0;
+pub const LCL: usize = 1;
+pub const ARG: usize = 2;
+pub const THIS: usize = 3;
+pub const THAT: usize = 4;
+pub const TEMP_BASE: usize = 5;
+pub const TEMP_SIZE: u16 = 8;
+/// Statics are allocated the same way the assembler allocates variables.
+pub const STATIC_BASE: u16 = 16;
+pub const STACK_BASE: i16 = 256;
+
+pub const TRUE: i16 = -1;
+pub const FALSE: i16 = 0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Segment {
+    Local,
+    Argument,
+    This,
+    That,
+    Pointer,
+    Temp,
+    Static,
+    Constant,
+}
+
+fn segment_from_str(s: &str) -> Option<Segment> {
+    Some(match s {
+        "local" => Segment::Local,
+        "argument" => Segment::Argument,
+        "this" => Segment::This,
+        "that" => Segment::That,
+        "pointer" => Segment::Pointer,
+        "temp" => Segment::Temp,
+        "static" => Segment::Static,
+        "constant" => Segment::Constant,
+        _ => return None,
+    })
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArithmeticOp {
+    Add,
+    Sub,
+    Neg,
+    Eq,
+    Gt,
+    Lt,
+    And,
+    Or,
+    Not,
+}
+
+fn arithmetic_from_str(s: &str) -> Option<ArithmeticOp> {
+    Some(match s {
+        "add" => ArithmeticOp::Add,
+        "sub" => ArithmeticOp::Sub,
+        "neg" => ArithmeticOp::Neg,
+        "eq" => ArithmeticOp::Eq,
+        "gt" => ArithmeticOp::Gt,
+        "lt" => ArithmeticOp::Lt,
+        "and" => ArithmeticOp::And,
+        "or" => ArithmeticOp::Or,
+        "not" => ArithmeticOp::Not,
+        _ => return None,
+    })
+}
+
+/// Instruction with labels, functions and statics resolved to indexes and addresses.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Op {
+    /// For `static` the index is an absolute RAM address.
+    Push(Segment, u16),
+    Pop(Segment, u16),
+    Arithmetic(ArithmeticOp),
+    Label,
+    Goto(usize),
+    IfGoto(usize),
+    Function(usize),
+    /// Target is `None` when no loaded module defines the function.
+    Call(Option<usize>, usize),
+    /// Call of an OS function no loaded module defines, see `Program::link_builtins`.
+    Builtin(Builtin, usize),
+    Return,
+}
+
+#[derive(Debug, Clone)]
+pub struct VmCommand {
+    pub op: Op,
+    pub inst: Instruction,
+    pub raw: String,
+    pub module: String,
+    /// Line of the module source, counted from 1.
+    pub line: usize,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Program {
+    pub commands: Vec<VmCommand>,
+    pub functions: HashMap<String, usize>,
+    pub statics: HashMap<String, u16>,
+    /// Calls of undefined OS functions use the built-in ones.
+    pub builtins: bool,
+}
+
+fn label_key(label: &str, func_name: &Option<String>) -> String {
+    match func_name {
+        Some(name) => format!("{}${}", name, label),
+        None => label.into(),
+    }
+}
+
+impl Program {
+    /// Loads modules given as `(module name, source)` pairs, in that order.
+    pub fn from_modules(modules: &[(&str, &str)]) -> Res<Self> {
+        let mut commands = Vec::<(Instruction, String, String, usize)>::new();
+        for (module, source) in modules {
+            let mut parser = parser::create(source, module);
+            let parse_result = parser.parse();
+            if let Some((line, text)) = parse_result.unparsed.first() {
+                return Err(format!(
+                    "{}.vm:{}: Unable to parse line: {}",
+                    module,
+                    line,
+                    text.trim()
+                )
+                .into());
+            }
+            commands.extend(
+                parse_result
+                    .commands
+                    .into_iter()
+                    .map(|cmd| (cmd.inst, cmd.raw, cmd.module_name.to_string(), cmd.line)),
+            );
+        }
+
+        let mut functions = HashMap::<String, usize>::new();
+        let mut labels = HashMap::<String, usize>::new();
+        let mut statics = HashMap::<String, u16>::new();
+        for (index, (inst, _, module, _)) in commands.iter().enumerate() {
+            match inst {
+                Instruction::Function(name, _) if functions.contains_key(name) => {
+                    return Err(format!("Duplicate function: {}", name).into());
+                }
+                Instruction::Function(name, _) => {
+                    functions.insert(name.clone(), index);
+                }
+                Instruction::Label(label, func_name) => {
+                    labels.insert(label_key(label, func_name), index);
+                }
+                Instruction::PushPop(PushPopInstruction { segment, addr, .. })
+                    if segment == "static" =>
+                {
+                    let next_addr = STATIC_BASE + statics.len() as u16;
+                    statics
+                        .entry(format!("{}.{}", module, addr))
+                        .or_insert(next_addr);
+                }
+                _ => {}
+            }
+        }
+
+        let resolve_label = |label: &str, func_name: &Option<String>| {
+            labels.get(&label_key(label, func_name)).copied()
+        };
+        let commands = commands
+            .into_iter()
+            .map(|(inst, raw, module, line)| -> Res<VmCommand> {
+                let error = |message: &str| -> Box<dyn Error> {
+                    format!("{}.vm:{}: {}: {}", module, line, message, raw.trim()).into()
+                };
+                let op = match &inst {
+                    Instruction::PushPop(PushPopInstruction {
+                        segment,
+                        addr,
+                        inst_type,
+                    }) => {
+                        let segment_type =
+                            segment_from_str(segment).ok_or_else(|| error("Unknown segment"))?;
+                        let index = match segment_type {
+                            Segment::Static => statics[&format!("{}.{}", module, addr)],
+                            Segment::Pointer if *addr > 1 => {
+                                return Err(error("Invalid pointer index"))
+                            }
+                            Segment::Temp if *addr >= TEMP_SIZE => {
+                                return Err(error("Invalid temp index"))
+                            }
+                            _ => *addr,
+                        };
+                        match inst_type {
+                            PushPop::Push => Op::Push(segment_type, index),
+                            PushPop::Pop if segment_type == Segment::Constant => {
+                                return Err(error("Can't pop to constant"))
+                            }
+                            PushPop::Pop => Op::Pop(segment_type, index),
+                        }
+                    }
+                    Instruction::Arithmetic(name) => Op::Arithmetic(
+                        arithmetic_from_str(name).ok_or_else(|| error("Unknown instruction"))?,
+                    ),
+                    Instruction::Label(..) => Op::Label,
+                    Instruction::Goto(label, func_name) => Op::Goto(
+                        resolve_label(label, func_name).ok_or_else(|| error("Unknown label"))?,
+                    ),
+                    Instruction::IfGoto(label, func_name) => Op::IfGoto(
+                        resolve_label(label, func_name).ok_or_else(|| error("Unknown label"))?,
+                    ),
+                    Instruction::Function(_, n_locals) => Op::Function(*n_locals),
+                    Instruction::Call(name, n_args) => {
+                        Op::Call(functions.get(name).copied(), *n_args)
+                    }
+                    Instruction::Return() => Op::Return,
+                };
+                Ok(VmCommand {
+                    op,
+                    inst,
+                    raw,
+                    module,
+                    line,
+                })
+            })
+            .collect::<Res<Vec<_>>>()?;
+
+        Ok(Program {
+            commands,
+            functions,
+            statics,
+            builtins: false,
+        })
+    }
+
+    /// Serves the calls of OS functions which no loaded module defines with
+    /// their built-in versions, so programs run without the compiled OS and
+    /// each OS class can be replaced by its own `.vm` file.
+    pub fn link_builtins(&mut self) {
+        for command in &mut self.commands {
+            if let (Op::Call(None, n_args), Instruction::Call(name, _)) =
+                (command.op, &command.inst)
+            {
+                if let Some(builtin) = Builtin::from_name(name) {
+                    command.op = Op::Builtin(builtin, n_args);
+                }
+            }
+        }
+        self.builtins = true;
+    }
+
+    /// Whether `Interpreter::bootstrap` can start the program: it has a
+    /// `Sys.init`, or the built-in one can call its `Main.main`.
+    pub fn has_entry_point(&self) -> bool {
+        self.functions.contains_key("Sys.init")
+            || (self.builtins && self.functions.contains_key("Main.main"))
+    }
+
+    /// Labels of the commands as the translator names them: functions at their
+    /// `function` command, and `Module$ret.N` after a call which is command `N`
+    /// of its module. Addresses are command indexes, so `crash::call_chain`
+    /// can read the return addresses of the interpreter's frames.
+    pub fn symbol_map(&self) -> SymbolMap {
+        let mut symbols = SymbolMap::new();
+        let mut module_commands = HashMap::<&str, usize>::new();
+        for (index, command) in self.commands.iter().enumerate() {
+            let module_index = module_commands.entry(&command.module).or_default();
+            let label = match (&command.inst, command.op) {
+                (Instruction::Function(name, _), _) => Some((name.clone(), index)),
+                (_, Op::Call(..) | Op::Builtin(..)) => Some((
+                    format!("{}$ret.{}", command.module, module_index),
+                    index + 1,
+                )),
+                _ => None,
+            };
+            if let Some((name, address)) = label {
+                // Programs too long for ROM addresses only get their first labels
+                if let Ok(address) = u16::try_from(address) {
+                    symbols.labels.insert(name, address);
+                }
+            }
+            *module_index += 1;
+        }
+        symbols
+    }
+
+    /// Loads a single `.vm` file, or every `.vm` file of a directory sorted by name.
+    pub fn from_path(source_path: &path::Path) -> Res<Self> {
+        let mut files = if source_path.is_dir() {
+            fs::read_dir(source_path)?
+                .filter_map(Result::ok)
+                .map(|f| f.path())
+                .filter(|f| f.extension().is_some_and(|ext| ext == "vm"))
+                .collect::<Vec<_>>()
+        } else {
+            vec![source_path.to_owned()]
+        };
+        files.sort();
+        let sources = files
+            .iter()
+            .map(|f| -> Res<(String, String)> {
+                let module = f
+                    .file_stem()
+                    .and_then(|s| s.to_str())
+                    .ok_or("Invalid filename")?;
+                Ok((module.to_string(), fs::read_to_string(f)?))
+            })
+            .collect::<Res<Vec<_>>>()?;
+        let modules = sources
+            .iter()
+            .map(|(module, source)| (module.as_str(), source.as_str()))
+            .collect::<Vec<_>>();
+        Self::from_modules(&modules)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Interpreter {
+    pub program: Program,
+    pub ram: Vec<i16>,
+    pub pc: usize,
+    pub steps: u64,
+    /// State of the built-in OS functions.
+    pub os: OsState,
+}
+
+pub(crate) fn address(value: i16) -> usize {
+    value as u16 as usize % RAM_SIZE
+}
+
+impl Interpreter {
+    pub fn new(program: Program) -> Self {
+        Self {
+            program,
+            ram: vec![0; RAM_SIZE],
+            pc: 0,
+            steps: 0,
+            os: OsState::default(),
+        }
+    }
+
+    /// Does what `code::generate_bootstrap` does: sets up the stack and calls `Sys.init`.
+    /// Returning from `Sys.init` halts the program.
+    pub fn bootstrap(&mut self) -> Res {
+        if !self.program.has_entry_point() {
+            return Err("Can't bootstrap, no Sys.init function".into());
+        }
+        self.ram[SP] = STACK_BASE;
+        let halt_address = self.program.commands.len();
+        match self.program.functions.get("Sys.init") {
+            Some(&sys_init) => self.call(sys_init, 0, halt_address),
+            None => self.sys_init()?,
+        }
+        Ok(())
+    }
+
+    pub fn is_halted(&self) -> bool {
+        self.pc >= self.program.commands.len()
+    }
+
+    pub fn peek(&self, addr: usize) -> i16 {
+        self.ram[addr % RAM_SIZE]
+    }
+
+    pub fn poke(&mut self, addr: usize, value: i16) {
+        self.ram[addr % RAM_SIZE] = value;
+    }
+
+    pub(crate) fn push(&mut self, value: i16) {
+        let sp = self.ram[SP];
+        self.poke(address(sp), value);
+        self.ram[SP] = sp.wrapping_add(1);
+    }
+
+    pub(crate) fn pop(&mut self) -> i16 {
+        let sp = self.ram[SP].wrapping_sub(1);
+        self.ram[SP] = sp;
+        self.peek(address(sp))
+    }
+
+    fn segment_address(&self, segment: Segment, index: u16) -> usize {
+        let base = |pointer: usize| address(self.ram[pointer].wrapping_add(index as i16));
+        match segment {
+            Segment::Local => base(LCL),
+            Segment::Argument => base(ARG),
+            Segment::This => base(THIS),
+            Segment::That => base(THAT),
+            Segment::Pointer => THIS + index as usize,
+            Segment::Temp => TEMP_BASE + index as usize,
+            Segment::Static => index as usize,
+            Segment::Constant => unreachable!("Constant segment has no address"),
+        }
+    }
+
+    /// Lays out the frame the same way as `code::generate_inst_call`.
+    pub(crate) fn call(&mut self, target: usize, n_args: usize, return_address: usize) {
+        self.push(return_address as i16);
+        for pointer in [LCL, ARG, THIS, THAT] {
+            self.push(self.ram[pointer]);
+        }
+        self.ram[ARG] = self.ram[SP].wrapping_sub(5 + n_args as i16);
+        self.ram[LCL] = self.ram[SP];
+        self.pc = target;
+    }
+
+    /// Unwinds the frame the same way as `code::generate_inst_return`.
+    fn return_(&mut self) {
+        let frame = self.ram[LCL];
+        let return_address = self.peek(address(frame.wrapping_sub(5)));
+        let value = self.pop();
+        let arg = self.ram[ARG];
+        self.poke(address(arg), value);
+        self.ram[SP] = arg.wrapping_add(1);
+        for (offset, pointer) in [THAT, THIS, ARG, LCL].into_iter().enumerate() {
+            self.ram[pointer] = self.peek(address(frame.wrapping_sub(offset as i16 + 1)));
+        }
+        self.pc = return_address as u16 as usize;
+    }
+
+    fn arithmetic(&mut self, op: ArithmeticOp) {
+        let bool_value = |b: bool| if b { TRUE } else { FALSE };
+        let value = match op {
+            ArithmeticOp::Neg => {
+                let x = self.pop();
+                x.wrapping_neg()
+            }
+            ArithmeticOp::Not => !self.pop(),
+            _ => {
+                let y = self.pop();
+                let x = self.pop();
+                match op {
+                    ArithmeticOp::Add => x.wrapping_add(y),
+                    ArithmeticOp::Sub => x.wrapping_sub(y),
+                    ArithmeticOp::Eq => bool_value(x == y),
+                    ArithmeticOp::Gt => bool_value(x > y),
+                    ArithmeticOp::Lt => bool_value(x < y),
+                    ArithmeticOp::And => x & y,
+                    ArithmeticOp::Or => x | y,
+                    ArithmeticOp::Neg | ArithmeticOp::Not => unreachable!(),
+                }
+            }
+        };
+        self.push(value);
+    }
+
+    /// Executes a single VM command.
+    pub fn step(&mut self) -> Res {
+        let command = self
+            .program
+            .commands
+            .get(self.pc)
+            .ok_or("Program has halted")?;
+        let op = command.op;
+        let next_pc = self.pc + 1;
+        self.pc = next_pc;
+        self.steps += 1;
+        match op {
+            Op::Push(Segment::Constant, value) => self.push(value as i16),
+            Op::Push(segment, index) => {
+                let value = self.peek(self.segment_address(segment, index));
+                self.push(value);
+            }
+            Op::Pop(segment, index) => {
+                let value = self.pop();
+                self.poke(self.segment_address(segment, index), value);
+            }
+            Op::Arithmetic(op) => self.arithmetic(op),
+            Op::Label => {}
+            Op::Goto(target) => self.pc = target,
+            Op::IfGoto(target) => {
+                if self.pop() != 0 {
+                    self.pc = target;
+                }
+            }
+            Op::Function(n_locals) => {
+                for _ in 0..n_locals {
+                    self.push(0);
+                }
+            }
+            Op::Call(Some(target), n_args) => self.call(target, n_args, next_pc),
+            Op::Call(None, _) => {
+                self.pc = next_pc - 1;
+                return Err(match &self.program.commands[self.pc].inst {
+                    Instruction::Call(name, _) => format!("Unknown function: {}", name),
+                    inst => format!("Invalid call: {:?}", inst),
+                }
+                .into());
+            }
+            Op::Builtin(builtin, n_args) => self.builtin(builtin, n_args)?,
+            Op::Return => self.return_(),
+        };
+        Ok(())
+    }
+
+    /// Runs until the program halts or `max_steps` commands were executed,
+    /// returns the number of executed commands.
+    pub fn run(&mut self, max_steps: u64) -> Res<u64> {
+        let mut steps = 0;
+        while steps < max_steps && !self.is_halted() {
+            self.step()?;
+            steps += 1;
+        }
+        Ok(steps)
+    }
+}
@@ -1,65 +1,200 @@
-mod code;
+pub mod builtins;
+pub mod code;
+pub mod code_compact;
 pub mod config;
-mod instruction;
-mod parser;
+pub mod crash;
+pub mod debugger;
+pub mod halt;
+pub mod heap;
+pub mod instruction;
+pub mod interpreter;
+pub mod parser;
 
 use std::error::Error;
 use std::fs;
-use std::path;
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
 
-fn process_files(config: &config::Config) -> Result<(path::PathBuf, String), Box<dyn Error>> {
-    let source_path = path::PathBuf::from(&config.source_path);
-    let file_metadata = source_path.metadata()?;
-    let result = match file_metadata {
-        m if m.is_file() => {
-            let contents = fs::read_to_string(&config.source_path)?;
-            let filename = source_path.clone();
-            let filename_stem = filename
-                .file_stem()
-                .and_then(|s| s.to_str())
-                .ok_or("Invalid filename")?;
-            let generated = code::generate_code(parser::create(&contents, filename_stem).parse());
-            let mut target = source_path;
-            target.set_extension("asm");
-            let bootstrap = code::generate_bootstrap(filename_stem);
-            Ok((target, format!("{}\n{}", bootstrap, generated)))
-        }
-        m if m.is_dir() => {
-            let targets = fs::read_dir(&source_path)?;
-            let generated = targets
-                .filter_map(Result::ok)
-                .filter(|f| f.file_name().into_string().unwrap().ends_with(".vm"))
-                .map(|f| -> Result<_, Box<dyn Error>> {
-                    let module_source = fs::read_to_string(f.path())?;
-                    let filename = f.path();
-                    let filename_stem = filename
-                        .file_stem()
-                        .and_then(|s| s.to_str())
-                        .ok_or("Invalid filename")?;
-                    let generated =
-                        code::generate_code(parser::create(&module_source, filename_stem).parse());
-                    Ok(generated)
+use config::{TranslateOptions, Verbosity, STDIO};
+use hasm::source_map::{Location, SourceMap};
+
+/// Hack ROM size limit, in instructions.
+pub const ROM_SIZE: usize = 32768;
+
+/// Number of instructions in Hack assembly, i.e. lines which aren't blank,
+/// comments or labels.
+pub fn rom_size(asm: &str) -> usize {
+    asm.lines()
+        .map(|line| line.split("//").next().unwrap_or("").trim())
+        .filter(|line| !line.is_empty() && !line.starts_with('('))
+        .count()
+}
+
+fn generate_module(
+    source: &str,
+    module: &str,
+    options: &TranslateOptions,
+) -> (String, Vec<Option<usize>>) {
+    let mut parser = parser::create(source, module);
+    let parse_result = parser.parse();
+    if options.compact {
+        code_compact::generate_code_mapped(parse_result)
+    } else {
+        code::generate_code_mapped(parse_result)
+    }
+}
+
+/// Translates VM modules, given as `(module name, source)`, to a single
+/// assembly program starting with the bootstrap code.
+pub fn translate(
+    modules: &[(String, String)],
+    program_name: &str,
+    options: &TranslateOptions,
+) -> String {
+    translate_with_map(modules, program_name, options).0
+}
+
+/// Same as `translate`, also mapping the assembly lines to the VM lines of
+/// `<module>.vm` they were translated from.
+pub fn translate_with_map(
+    modules: &[(String, String)],
+    program_name: &str,
+    options: &TranslateOptions,
+) -> (String, SourceMap) {
+    let mut parts: Vec<(String, Vec<Option<Location>>)> = modules
+        .iter()
+        .map(|(module, source)| {
+            let (code, lines) = generate_module(source, module, options);
+            let file = format!("{}.vm", module);
+            let locations = lines
+                .into_iter()
+                .map(|line| {
+                    line.map(|line| Location {
+                        file: file.clone(),
+                        line,
+                    })
                 })
-                .filter_map(Result::ok)
-                .collect::<Vec<_>>()
-                .join("\n");
-            let mut target = source_path.clone();
-            let filename = source_path
+                .collect();
+            (code, locations)
+        })
+        .collect();
+    let unmapped = |code: String| {
+        let lines = code.lines().count();
+        (code, vec![None; lines])
+    };
+    match (options.compact, options.no_bootstrap) {
+        (false, false) => parts.insert(0, unmapped(code::generate_bootstrap(program_name))),
+        (true, false) => parts.insert(0, unmapped(code_compact::generate_bootstrap(program_name))),
+        (true, true) => parts.push(unmapped(code_compact::generate_routines())),
+        (false, true) => {}
+    }
+
+    let mut map = SourceMap::new();
+    let mut code = vec![];
+    let mut offset = 0;
+    for (part, locations) in parts {
+        for (i, location) in locations.into_iter().enumerate() {
+            if let Some(location) = location {
+                map.insert(offset + i + 1, location);
+            }
+        }
+        offset += part.lines().count();
+        code.push(part);
+    }
+    (code.join("\n"), map)
+}
+
+/// Reads the `.vm` files of a directory sorted by name, a single file or
+/// stdin, as `(module name, source)`.
+pub fn read_modules(source_path: &str) -> Result<Vec<(String, String)>, Box<dyn Error>> {
+    if source_path == STDIO {
+        let mut source = String::new();
+        io::stdin().read_to_string(&mut source)?;
+        return Ok(vec![(config::program_name(Path::new(STDIO)), source)]);
+    }
+    let source_path = PathBuf::from(source_path);
+    let mut files = match source_path.metadata()? {
+        m if m.is_file() => vec![source_path],
+        m if m.is_dir() => fs::read_dir(&source_path)?
+            .filter_map(Result::ok)
+            .map(|f| f.path())
+            .filter(|f| f.extension().is_some_and(|ext| ext == "vm"))
+            .collect(),
+        _ => return Err(format!("Invalid source: {}", source_path.to_string_lossy()).into()),
+    };
+    files.sort();
+    files
+        .iter()
+        .map(|file| {
+            let module = file
                 .file_stem()
-                .and_then(|f| f.to_str())
+                .and_then(|s| s.to_str())
                 .ok_or("Invalid filename")?;
-            target.push(path::Path::new(filename));
-            target.set_extension("asm");
-            let bootstrap = code::generate_bootstrap(filename);
-            Ok((target, format!("{}\n{}", bootstrap, generated)))
+            Ok((module.to_string(), fs::read_to_string(file)?))
+        })
+        .collect()
+}
+
+/// Checks that every line of the modules is a valid VM command, reporting
+/// all the lines which don't parse at once.
+pub fn check_modules(modules: &[(String, String)]) -> Result<(), Box<dyn Error>> {
+    let mut errors = vec![];
+    for (module, source) in modules {
+        let mut parser = parser::create(source, module);
+        for (line, text) in parser.parse().unparsed {
+            errors.push(format!(
+                "{}.vm:{}: Unable to parse line: {}",
+                module,
+                line,
+                text.trim()
+            ));
         }
-        _ => Err(format!("Invalid source: {}", source_path.to_string_lossy())),
-    }?;
-    Ok(result)
+    }
+    if !errors.is_empty() {
+        return Err(errors.join("\n").into());
+    }
+    let modules: Vec<(&str, &str)> = modules
+        .iter()
+        .map(|(module, source)| (module.as_str(), source.as_str()))
+        .collect();
+    interpreter::Program::from_modules(&modules)?;
+    Ok(())
 }
 
 pub fn run(config: &config::Config) -> Result<(), Box<dyn Error>> {
-    let (target_path, generated) = process_files(config)?;
-    fs::write(target_path, generated)?;
+    let modules = read_modules(&config.source_path)?;
+    check_modules(&modules)?;
+    let program_name = config::program_name(Path::new(&config.source_path));
+    let generated = translate(&modules, &program_name, &config.options);
+    let size = rom_size(&generated);
+    if config.verbosity >= Verbosity::Normal {
+        if config.options.compact {
+            let default_options = TranslateOptions {
+                compact: false,
+                ..config.options.clone()
+            };
+            let default = translate(&modules, &program_name, &default_options);
+            eprintln!("ROM size: {} -> {} instructions", rom_size(&default), size);
+        }
+        if size > ROM_SIZE {
+            eprintln!(
+                "Warning: {} instructions don't fit in the {} word ROM",
+                size, ROM_SIZE
+            );
+        }
+    }
+    if config.check {
+        return Ok(());
+    }
+
+    let target = config.target();
+    if target == STDIO {
+        io::stdout().write_all(generated.as_bytes())?;
+    } else {
+        fs::write(&target, &generated)?;
+        if config.verbosity >= Verbosity::Verbose {
+            eprintln!("Wrote {} instructions to: {}", size, target);
+        }
+    }
     Ok(())
 }
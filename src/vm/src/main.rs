@@ -7,9 +7,13 @@ use vm::{config, run};
 fn main() {
     let args: Vec<String> = env::args().collect();
     let config = config::Config::new(&args).unwrap_or_else(|err| {
-        println!("Problem parsing arguments: {}", err);
+        println!("Problem parsing arguments: {}\n\n{}", err, config::USAGE);
         std::process::exit(1);
     });
+    if config.help {
+        print!("{}", config::USAGE);
+        return;
+    }
 
     if let Err(e) = run(&config) {
         println!("Application error: {}", e);
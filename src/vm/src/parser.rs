@@ -7,12 +7,16 @@ pub struct Command<'a> {
     pub inst: Instruction,
     pub raw: String,
     pub module_name: &'a str,
+    /// Line of the module source, counted from 1.
+    pub line: usize,
 }
 
 #[derive(Debug)]
 pub struct ParseResult<'a> {
     pub commands: Vec<Command<'a>>,
     pub module: String,
+    /// Lines which aren't VM commands, as `(line, text)`.
+    pub unparsed: Vec<(usize, String)>,
 }
 
 #[derive(Debug, Default, Clone)]
@@ -36,27 +40,32 @@ impl<'a> Parser<'a> {
         }
     }
 
-    pub fn parse(&mut self) -> ParseResult {
+    pub fn parse(&mut self) -> ParseResult<'_> {
         let mut commands = Vec::<Command>::new();
-        for line in self.input.lines() {
-            if let Some(inst) = self.parse_line(line) {
-                commands.push(Command {
+        let mut unparsed = vec![];
+        for (i, line) in self.input.lines().enumerate() {
+            match self.parse_line(line) {
+                Some(inst) => commands.push(Command {
                     inst,
                     raw: line.into(),
                     module_name: self.filename,
-                })
+                    line: i + 1,
+                }),
+                None if !is_blank(line) => unparsed.push((i + 1, line.to_string())),
+                None => {}
             }
         }
         ParseResult {
             commands,
             module: self.filename.into(),
+            unparsed,
         }
     }
 
     fn parse_line(&mut self, line: &str) -> Option<Instruction> {
         let cleaned = line.split("//").next().unwrap_or_default().trim();
         let cmds: Vec<&str> = cleaned.split_whitespace().collect();
-        let result = match cmds[..] {
+        match cmds[..] {
             ["call", label, n_args] => Some(Instruction::Call(
                 label.into(),
                 str::parse::<usize>(n_args).ok()?,
@@ -98,14 +107,19 @@ impl<'a> Parser<'a> {
                 _ => None,
             },
             _ => None,
-        };
-        if result.is_none() && !cleaned.is_empty() {
-            eprintln!("Unable to parse line: {}", line);
         }
-        result
     }
 }
 
+/// Whether a line holds no command, only whitespace or a comment.
+fn is_blank(line: &str) -> bool {
+    line.split("//")
+        .next()
+        .unwrap_or_default()
+        .trim()
+        .is_empty()
+}
+
 pub fn create<'a>(content: &'a str, filename: &'a str) -> Parser<'a> {
     Parser::new(content, filename)
 }
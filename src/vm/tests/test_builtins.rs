@@ -0,0 +1,294 @@
+use vm::builtins::{Waiting, HEAP_BASE, HEAP_DAMAGED, HEAP_LENGTH, KBD, SCREEN, WAIT_STEPS_PER_MS};
+use vm::interpreter::{Interpreter, Program};
+
+/// Boots a program using the built-in OS.
+fn boot(modules: &[(&str, &str)]) -> Interpreter {
+    let mut program = Program::from_modules(modules).unwrap();
+    program.link_builtins();
+    let mut vm = Interpreter::new(program);
+    vm.bootstrap().unwrap();
+    vm
+}
+
+fn main(body: &str) -> Interpreter {
+    let source = format!("function Main.main 1\n{}\npush constant 0\nreturn\n", body);
+    boot(&[("Main", &source)])
+}
+
+/// VM code storing the value on the stack at a RAM address.
+fn poke(address: u16) -> String {
+    format!(
+        "pop temp 0\npush constant {}\npush temp 0\ncall Memory.poke 2\npop temp 0\n",
+        address
+    )
+}
+
+#[test]
+fn test_math() {
+    let mut vm = main(&format!(
+        "
+        push constant 123
+        push constant 45
+        neg
+        call Math.multiply 2
+        {}
+        push constant 5535
+        push constant 3
+        neg
+        call Math.divide 2
+        {}
+        push constant 32767
+        call Math.sqrt 1
+        {}
+        push constant 7
+        neg
+        call Math.abs 1
+        push constant 3
+        call Math.max 2
+        {}
+        ",
+        poke(8000),
+        poke(8001),
+        poke(8002),
+        poke(8003),
+    ));
+    vm.run(1000).unwrap();
+    assert!(vm.is_halted());
+    assert_eq!(&vm.ram[8000..8004], &[-5535, -1845, 181, 7]);
+}
+
+#[test]
+fn test_memory() {
+    let mut vm = main(&format!(
+        "
+        push constant 3
+        call Array.new 1
+        pop local 0
+        push local 0
+        {}
+        push constant 10
+        call Memory.alloc 1
+        {}
+        push local 0
+        call Array.dispose 1
+        pop temp 0
+        ",
+        poke(8000),
+        poke(8001),
+    ));
+    vm.run(1000).unwrap();
+    let heap_end = HEAP_BASE as i16 + HEAP_LENGTH;
+    // Blocks are taken from the end of the free block, after a two word header
+    assert_eq!(vm.ram[8000], heap_end - 3);
+    assert_eq!(vm.ram[8001], heap_end - 5 - 12 + 2);
+    assert_eq!(vm.ram[HEAP_BASE + 1], HEAP_LENGTH - 5 - 12);
+    assert_eq!(
+        vm.ram[heap_end as usize - 17..heap_end as usize - 15],
+        [2048, 10]
+    );
+    // The array block follows the allocated one, so it's appended to the free list
+    assert_eq!(vm.ram[HEAP_BASE], heap_end - 5);
+    assert_eq!(vm.ram[heap_end as usize - 5..heap_end as usize - 3], [0, 5]);
+}
+
+#[test]
+fn test_bad_heap_pointers() {
+    // An object outside the heap
+    let mut vm = main("push constant 1\ncall Memory.deAlloc 1\npop temp 0");
+    vm.run(1000).unwrap();
+    assert!(vm.is_halted());
+    assert_eq!(vm.os.error, Some(HEAP_DAMAGED));
+
+    // A free list linking back to itself
+    let mut vm = main(
+        "
+        push constant 2048
+        push constant 2048
+        call Memory.poke 2
+        pop temp 0
+        push constant 5
+        call Array.new 1
+        pop temp 0
+        ",
+    );
+    vm.run(1000).unwrap();
+    assert!(vm.is_halted());
+    assert_eq!(vm.os.error, Some(HEAP_DAMAGED));
+
+    // A free block longer than the heap
+    let mut vm = main(&format!(
+        "push constant 20000\n{}push constant 5\ncall Array.new 1\npop temp 0",
+        poke(HEAP_BASE as u16 + 1)
+    ));
+    vm.run(1000).unwrap();
+    assert_eq!(vm.os.error, Some(HEAP_DAMAGED));
+}
+
+#[test]
+fn test_strings_and_output() {
+    let mut vm = main(
+        "
+        push constant 2
+        call String.new 1
+        push constant 65
+        call String.appendChar 2
+        push constant 98
+        call String.appendChar 2
+        pop local 0
+        push local 0
+        call Output.printString 1
+        pop temp 0
+        push constant 12
+        neg
+        call Output.printInt 1
+        pop temp 0
+        push local 0
+        call String.intValue 1
+        pop temp 1
+        push local 0
+        push constant 1
+        call String.charAt 2
+        pop temp 2
+        ",
+    );
+    vm.run(1000).unwrap();
+    assert!(vm.is_halted());
+    assert_eq!(vm.ram[6], 0);
+    assert_eq!(vm.ram[7], 98);
+    assert_eq!((vm.os.cursor_row, vm.os.cursor_column), (0, 5));
+    // "A" in the low byte of the first word, "b" in the high one
+    let rows: Vec<i16> = (0..11).map(|i| vm.ram[SCREEN + i * 32]).collect();
+    let a = [12, 30, 51, 51, 63, 51, 51, 51, 51, 0, 0];
+    let b = [3, 3, 3, 15, 27, 51, 51, 51, 30, 0, 0];
+    let expected: Vec<i16> = a.iter().zip(b).map(|(a, b)| a | (b << 8)).collect();
+    assert_eq!(rows, expected);
+    // "-" of "-12"
+    assert_eq!(vm.ram[SCREEN + 5 * 32 + 1] & 0xff, 63);
+}
+
+#[test]
+fn test_screen() {
+    let mut vm = main(
+        "
+        push constant 0
+        push constant 0
+        push constant 15
+        push constant 3
+        call Screen.drawRectangle 4
+        pop temp 0
+        push constant 0
+        call Screen.setColor 1
+        pop temp 0
+        push constant 1
+        push constant 1
+        call Screen.drawPixel 2
+        pop temp 0
+        ",
+    );
+    vm.run(1000).unwrap();
+    assert_eq!(&vm.ram[SCREEN..SCREEN + 2], &[-1, 0]);
+    assert_eq!(vm.ram[SCREEN + 32], -3);
+    assert_eq!(vm.ram[SCREEN + 3 * 32], -1);
+    assert_eq!(vm.ram[SCREEN + 4 * 32], 0);
+}
+
+#[test]
+fn test_errors_halt() {
+    let mut vm = main(
+        "
+        push constant 1
+        push constant 0
+        call Math.divide 2
+        pop temp 0
+        push constant 1
+        pop static 0
+        ",
+    );
+    vm.run(1000).unwrap();
+    assert!(vm.is_halted());
+    assert_eq!(vm.os.error, Some(3));
+    assert_eq!(vm.ram[16], 0);
+    // "ERR3" is printed
+    assert_eq!((vm.os.cursor_row, vm.os.cursor_column), (0, 4));
+}
+
+#[test]
+fn test_read_int() {
+    let mut vm = main(&format!(
+        "
+        push constant 0
+        call String.new 1
+        call Keyboard.readInt 1
+        {}
+        ",
+        poke(8000),
+    ));
+    for key in [b'4', b'7', 129, b'2', 128] {
+        vm.run(100).unwrap();
+        assert!(matches!(vm.os.waiting, Some(Waiting::Key(_))));
+        vm.ram[KBD] = key as i16;
+        vm.run(100).unwrap();
+        vm.ram[KBD] = 0;
+    }
+    vm.run(100).unwrap();
+    assert!(vm.is_halted());
+    assert_eq!(vm.ram[8000], 42);
+}
+
+#[test]
+fn test_wait() {
+    let mut vm =
+        main("push constant 3\ncall Sys.wait 1\npop temp 0\npush constant 1\npop static 0");
+    vm.run(3 * WAIT_STEPS_PER_MS).unwrap();
+    assert_eq!(vm.ram[16], 0);
+    vm.run(100).unwrap();
+    assert_eq!(vm.ram[16], 1);
+}
+
+#[test]
+fn test_replaced_function() {
+    // Built-in `String.new` allocates with the loaded `Memory.alloc`
+    let mut vm = boot(&[
+        (
+            "Main",
+            "
+            function Main.main 0
+            push constant 4
+            call String.new 1
+            pop static 0
+            push constant 0
+            return
+            ",
+        ),
+        (
+            "Memory",
+            "
+            function Memory.alloc 0
+            push static 0
+            push constant 6000
+            add
+            push static 0
+            push argument 0
+            add
+            pop static 0
+            return
+            ",
+        ),
+    ]);
+    vm.run(1000).unwrap();
+    assert!(vm.is_halted());
+    assert_eq!(vm.ram[16], 6000);
+    assert_eq!(&vm.ram[6000..6003], &[0, 4, 6003]);
+}
+
+#[test]
+fn test_builtin_call_errors() {
+    let mut vm = main("push constant 1\ncall Math.abs 2");
+    let err = vm.run(100).unwrap_err();
+    assert_eq!(err.to_string(), "Math.abs expects 1 argument(s), got 2");
+
+    let mut program = Program::from_modules(&[("Foo", "function Foo.bar 0")]).unwrap();
+    program.link_builtins();
+    let err = Interpreter::new(program).bootstrap().unwrap_err();
+    assert_eq!(err.to_string(), "Can't bootstrap, no Sys.init function");
+}
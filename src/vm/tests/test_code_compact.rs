@@ -0,0 +1,159 @@
+use emulator::{cpu::Cpu, rom};
+use vm::{code, code_compact, config::TranslateOptions, parser, rom_size};
+
+const MAIN: &str = "
+function Main.fib 0
+    push argument 0
+    push constant 2
+    lt
+    if-goto BASE
+    push argument 0
+    push constant 1
+    sub
+    call Main.fib 1
+    push argument 0
+    push constant 2
+    sub
+    call Main.fib 1
+    add
+    return
+label BASE
+    push argument 0
+    return
+
+function Main.fill 2
+    push argument 0
+    pop pointer 1
+    push constant 0
+    pop local 0
+label LOOP
+    push local 0
+    push argument 1
+    eq
+    if-goto DONE
+    push local 0
+    push local 0
+    add
+    pop that 0
+    push pointer 1
+    push constant 1
+    add
+    pop pointer 1
+    push local 0
+    push constant 1
+    add
+    pop local 0
+    goto LOOP
+label DONE
+    push local 1
+    return
+";
+
+const SYS: &str = "
+function Sys.init 1
+    push constant 12
+    call Main.fib 1
+    pop static 0
+    push constant 7
+    push constant 9
+    gt
+    pop temp 0
+    push constant 9
+    push constant 7
+    gt
+    pop temp 1
+    push constant 5
+    neg
+    push constant 5
+    eq
+    pop temp 2
+    push constant 3000
+    push constant 4
+    call Main.fill 2
+    pop local 0
+    push constant 1
+    neg
+    push constant 3
+    lt
+    pop temp 3
+label END
+    goto END
+";
+
+fn translate(compact: bool) -> String {
+    let modules: Vec<String> = [("Main", MAIN), ("Sys", SYS)]
+        .iter()
+        .map(|(name, source)| {
+            let mut parser = parser::create(source, name);
+            let parse_result = parser.parse();
+            if compact {
+                code_compact::generate_code(parse_result)
+            } else {
+                code::generate_code(parse_result)
+            }
+        })
+        .collect();
+    let bootstrap = if compact {
+        code_compact::generate_bootstrap("Test")
+    } else {
+        code::generate_bootstrap("Test")
+    };
+    format!("{}\n{}", bootstrap, modules.join("\n"))
+}
+
+fn execute(asm: &str) -> Cpu {
+    let hack = hasm::code::generate_code(hasm::parser::parse(asm.to_string()).unwrap());
+    let mut cpu = Cpu::new(&rom::parse_hack(&hack).unwrap());
+    cpu.run(200_000);
+    cpu
+}
+
+#[test]
+fn test_compact_matches_default() {
+    let default = execute(&translate(false));
+    let compact = execute(&translate(true));
+    let peek_all = |cpu: &Cpu| -> Vec<i16> {
+        [0, 16, 5, 6, 7, 8, 3000, 3001, 3002, 3003, 3004]
+            .iter()
+            .map(|&addr| cpu.peek(addr))
+            .collect()
+    };
+    assert_eq!(
+        peek_all(&compact),
+        vec![262, 144, 0, -1, 0, -1, 0, 2, 4, 6, 0]
+    );
+    assert_eq!(peek_all(&default), peek_all(&compact));
+}
+
+#[test]
+fn test_compact_is_smaller() {
+    let default = rom_size(&translate(false));
+    let compact = rom_size(&translate(true));
+    assert!(
+        compact < default,
+        "compact: {}, default: {}",
+        compact,
+        default
+    );
+}
+
+#[test]
+fn test_rom_size() {
+    let asm = "// comment\n(LOOP)\n@LOOP // jump\n0;JMP\n\n";
+    assert_eq!(rom_size(asm), 2);
+}
+
+#[test]
+fn test_translate_without_bootstrap() {
+    let modules = vec![("Main".to_string(), MAIN.to_string())];
+    let options = TranslateOptions {
+        compact: true,
+        no_bootstrap: true,
+    };
+    let asm = vm::translate(&modules, "Main", &options);
+    assert!(asm.starts_with("///@module-start 'Main'"));
+    assert!(asm.contains("(__CALL)"));
+
+    let asm = vm::translate(&modules, "Main", &TranslateOptions::default());
+    assert!(asm.starts_with("///@program-bootstrap-start"));
+}
@@ -0,0 +1,216 @@
+use std::collections::HashMap;
+
+use emulator::{cpu::Cpu, rom};
+use hasm::{code, parser};
+use vm::config::TranslateOptions;
+use vm::crash::{self, CrashReason};
+use vm::debugger::{Breakpoint, DebugInfo, Debugger};
+use vm::interpreter::{Interpreter, Program};
+
+const SYS: &str = "\
+function Sys.init 0
+    push constant 5
+    call Main.outer 1
+label END
+    goto END
+";
+
+const MAIN: &str = "\
+function Main.outer 1
+    push argument 0
+    pop local 0
+    push constant 7
+    push local 0
+    call Main.inner 2
+    return
+function Main.inner 0
+label LOOP
+    goto LOOP
+";
+
+fn n_locals() -> HashMap<String, usize> {
+    [("Sys.init", 0), ("Main.outer", 1), ("Main.inner", 0)]
+        .into_iter()
+        .map(|(name, n)| (name.to_string(), n))
+        .collect()
+}
+
+/// Functions, arguments and return labels of the frames.
+fn summary(ram: &[i16], frames: &[vm::debugger::Frame]) -> Vec<(String, Vec<i16>, Option<String>)> {
+    frames
+        .iter()
+        .map(|frame| {
+            let args = (0..frame.n_args)
+                .map(|i| ram[frame.arg as usize + i])
+                .collect();
+            (frame.function.clone(), args, frame.return_label.clone())
+        })
+        .collect()
+}
+
+#[test]
+fn test_call_chain() {
+    let program = Program::from_modules(&[("Sys", SYS), ("Main", MAIN)]).unwrap();
+    let symbols = program.symbol_map();
+    assert_eq!(symbols.labels["Main.inner"], 12);
+    assert_eq!(symbols.labels["Main$ret.5"], 11);
+    let mut interpreter = Interpreter::new(program);
+    interpreter.bootstrap().unwrap();
+    interpreter.run(100).unwrap();
+    let frames = crash::call_chain(&interpreter.ram, interpreter.pc, &symbols, &n_locals());
+    assert_eq!(
+        summary(&interpreter.ram, &frames),
+        [
+            ("Main.inner".into(), vec![7, 5], Some("Main$ret.5".into())),
+            ("Main.outer".into(), vec![5], Some("Sys$ret.2".into())),
+            ("Sys.init".into(), vec![], None),
+        ]
+    );
+    assert_eq!(interpreter.ram[frames[1].lcl as usize], 5);
+}
+
+/// The same walk reads the frames of the translated program, with the
+/// assembler's symbols.
+#[test]
+fn test_call_chain_on_cpu() {
+    let modules = [
+        ("Sys".to_string(), SYS.to_string()),
+        ("Main".to_string(), MAIN.to_string()),
+    ];
+    for compact in [false, true] {
+        let options = TranslateOptions {
+            compact,
+            ..Default::default()
+        };
+        let asm = vm::translate(&modules, "Prog", &options);
+        let parse_result = parser::parse_with_macros(&asm, None).unwrap();
+        let symbols = parse_result.symbols.clone();
+        let hack = code::generate_code(parse_result);
+        let mut cpu = Cpu::new(&rom::parse_hack(&hack).unwrap());
+        cpu.run(10_000);
+        let frames = crash::call_chain(cpu.ram(), cpu.pc as usize, &symbols, &n_locals());
+        let mut frames = summary(cpu.ram(), &frames);
+        // Returns to the bootstrap code, which isn't part of a function
+        let (function, _, bootstrap) = frames.pop().unwrap();
+        assert_eq!(function, "Sys.init");
+        assert!(bootstrap.unwrap().starts_with("Prog$ret."));
+        assert_eq!(
+            frames,
+            [
+                ("Main.inner".into(), vec![7, 5], Some("Main$ret.5".into())),
+                ("Main.outer".into(), vec![5], Some("Sys$ret.2".into())),
+            ]
+        );
+    }
+}
+
+fn boot(main: &str) -> Debugger {
+    let mut program = Program::from_modules(&[("Main", main)]).unwrap();
+    program.link_builtins();
+    let mut debugger = Debugger::new(program, DebugInfo::default());
+    debugger.bootstrap().unwrap();
+    debugger
+}
+
+const DIVIDE: &str = "\
+function Main.main 0
+    push constant 4
+    call Main.divide 1
+    pop temp 0
+    push constant 0
+    return
+function Main.divide 0
+    push argument 0
+    push constant 0
+    call Math.divide 2
+    return
+";
+
+#[test]
+fn test_builtin_sys_error() {
+    let mut debugger = boot(DIVIDE);
+    let report = debugger.run_to_crash(1000, |_| {}).unwrap().unwrap();
+    assert_eq!(report.reason, CrashReason::SysError(3));
+    let functions: Vec<&str> = report
+        .frames
+        .iter()
+        .map(|frame| frame.function.as_str())
+        .collect();
+    assert_eq!(functions, ["Main.divide", "Main.main"]);
+    assert_eq!(
+        debugger.describe(report.frames[0].pc),
+        "Main.vm:10 call Math.divide 2"
+    );
+    let text = report.to_string();
+    assert!(text.starts_with("Sys.error called with code 3, after "));
+    assert!(text.contains(
+        "#0 Main.divide at Main.vm:10 call Math.divide 2\n   argument: [4]\n   local: []\n   this: 0, that: 0, returns to Main$ret.2\n"
+    ));
+}
+
+#[test]
+fn test_sys_error_function() {
+    let main = "\
+function Main.main 0
+    push constant 9
+    call Sys.error 1
+    return
+function Sys.error 0
+label HALT
+    goto HALT
+";
+    let mut debugger = boot(main);
+    let report = debugger.run_to_crash(1000, |_| {}).unwrap().unwrap();
+    assert_eq!(report.reason, CrashReason::SysError(9));
+    assert_eq!(report.frames[0].function, "Sys.error");
+    assert_eq!(report.frames[0].return_label.as_deref(), Some("Main$ret.2"));
+    assert_eq!(report.frames[1].function, "Main.main");
+}
+
+#[test]
+fn test_step_limit_and_breakpoint() {
+    let main = "\
+function Main.main 0
+    call Main.loop 0
+    return
+function Main.loop 0
+label LOOP
+    push constant 1
+    pop static 0
+    goto LOOP
+";
+    let mut debugger = boot(main);
+    let report = debugger.run_to_crash(1000, |_| {}).unwrap().unwrap();
+    assert_eq!(report.reason, CrashReason::StepLimit(1000));
+    assert_eq!(report.frames[0].function, "Main.loop");
+
+    let mut debugger = boot(DIVIDE);
+    debugger.breakpoints = vec![Breakpoint::parse("Main.divide").unwrap()];
+    let report = debugger.run_to_crash(1000, |_| {}).unwrap().unwrap();
+    assert_eq!(report.reason, CrashReason::Breakpoint("Main.divide".into()));
+    assert_eq!(report.frames.len(), 2);
+}
+
+#[test]
+fn test_no_crash() {
+    let main = "\
+function Main.main 0
+    push constant 0
+    return
+";
+    let mut debugger = boot(main);
+    let mut steps = 0;
+    let report = debugger.run_to_crash(1000, |_| steps += 1).unwrap();
+    assert!(report.is_none());
+    assert!(steps > 0);
+
+    // Looping in a `Sys.halt` like the Jack OS one isn't a crash
+    let main = "\
+function Main.main 0
+    call Sys.halt 0
+function Sys.halt 0
+label LOOP
+    goto LOOP
+";
+    assert!(boot(main).run_to_crash(1000, |_| {}).unwrap().is_none());
+}
@@ -0,0 +1,161 @@
+use vm::debugger::{Breakpoint, DebugInfo, Debugger, StopReason, Variable};
+use vm::interpreter::Program;
+
+const SYS: &str = "\
+function Sys.init 0
+    push constant 3
+    call Main.double 1
+    pop static 0
+    push constant 7
+    call Main.double 1
+    pop static 1
+    return
+";
+
+const MAIN: &str = "\
+function Main.double 1
+    push argument 0
+    push argument 0
+    add
+    pop local 0
+    push local 0
+    return
+";
+
+fn debugger(info: DebugInfo) -> Debugger {
+    let program = Program::from_modules(&[("Sys", SYS), ("Main", MAIN)]).unwrap();
+    let mut debugger = Debugger::new(program, info);
+    debugger.bootstrap().unwrap();
+    debugger
+}
+
+#[test]
+fn test_breakpoints() {
+    let mut debugger = debugger(DebugInfo::default());
+    debugger.breakpoints = vec![
+        Breakpoint::parse("Main.double").unwrap(),
+        Breakpoint::parse("Main.vm:5").unwrap(),
+    ];
+    assert_eq!(debugger.continue_().unwrap(), StopReason::Breakpoint(0));
+    assert_eq!(
+        debugger.describe(debugger.interpreter.pc),
+        "Main.vm:1 function Main.double 1"
+    );
+    assert_eq!(debugger.continue_().unwrap(), StopReason::Breakpoint(1));
+    assert_eq!(debugger.continue_().unwrap(), StopReason::Breakpoint(0));
+    assert_eq!(debugger.continue_().unwrap(), StopReason::Breakpoint(1));
+    assert_eq!(debugger.continue_().unwrap(), StopReason::Halted);
+    assert_eq!(debugger.interpreter.peek(16), 6);
+    assert_eq!(debugger.interpreter.peek(17), 14);
+
+    assert_eq!(
+        Breakpoint::parse("Main.jack:4"),
+        Some(Breakpoint::JackLine("Main.jack".into(), 4))
+    );
+    assert_eq!(Breakpoint::parse("Main.txt:4"), None);
+}
+
+#[test]
+fn test_stepping() {
+    let mut debugger = debugger(DebugInfo::default());
+    debugger.step_into().unwrap();
+    // Over the call, to the `pop static 0`
+    assert_eq!(debugger.step_over().unwrap(), StopReason::Step);
+    assert_eq!(debugger.step_over().unwrap(), StopReason::Step);
+    assert_eq!(
+        debugger.describe(debugger.interpreter.pc),
+        "Sys.vm:4 pop static 0"
+    );
+    debugger.step_over().unwrap();
+    debugger.step_over().unwrap();
+    // Into the call, then back out
+    debugger.step_into().unwrap();
+    assert_eq!(
+        debugger.describe(debugger.interpreter.pc),
+        "Main.vm:1 function Main.double 1"
+    );
+    debugger.step_into().unwrap();
+    assert_eq!(debugger.step_out().unwrap(), StopReason::Step);
+    assert_eq!(
+        debugger.describe(debugger.interpreter.pc),
+        "Sys.vm:7 pop static 1"
+    );
+}
+
+#[test]
+fn test_call_stack() {
+    let mut debugger = debugger(DebugInfo::default());
+    debugger.breakpoints = vec![Breakpoint::parse("Main.vm:6").unwrap()];
+    debugger.continue_().unwrap();
+    let frames = debugger.call_stack();
+    let functions: Vec<(&str, usize)> = frames
+        .iter()
+        .map(|frame| (frame.function.as_str(), frame.n_args))
+        .collect();
+    assert_eq!(functions, vec![("Main.double", 1), ("Sys.init", 0)]);
+    assert_eq!(debugger.interpreter.peek(frames[0].arg as usize), 3);
+    assert_eq!(debugger.interpreter.peek(frames[0].lcl as usize), 6);
+    assert_eq!(
+        debugger.describe(frames[1].pc),
+        "Sys.vm:3 call Main.double 1"
+    );
+}
+
+#[test]
+fn test_watch() {
+    let mut debugger = debugger(DebugInfo::default());
+    debugger.run_command("watch 17").unwrap();
+    assert_eq!(
+        debugger.continue_().unwrap(),
+        StopReason::Watch {
+            address: 17,
+            old: 0,
+            new: 14
+        }
+    );
+    assert_eq!(debugger.continue_().unwrap(), StopReason::Halted);
+}
+
+#[test]
+fn test_commands() {
+    let mut info = DebugInfo::default();
+    let variable = |name: &str, segment: &str| Variable {
+        name: name.into(),
+        typ: "int".into(),
+        segment: segment.into(),
+        index: 0,
+    };
+    info.variables.insert(
+        "Main.double".into(),
+        vec![variable("x", "argument"), variable("doubled", "local")],
+    );
+    let mut debugger = debugger(info);
+    assert_eq!(
+        debugger.run_command("break Main.double").unwrap(),
+        "Breakpoint 1: Main.double"
+    );
+    assert!(debugger.run_command("break Main.triple").is_err());
+    assert_eq!(
+        debugger.run_command("c").unwrap(),
+        "Breakpoint 1: Main.double\nMain.vm:1 function Main.double 1"
+    );
+    debugger.run_command("finish").unwrap();
+    debugger.run_command("n").unwrap();
+    debugger.run_command("n").unwrap();
+    debugger.run_command("s").unwrap();
+    for _ in 0..5 {
+        debugger.run_command("n").unwrap();
+    }
+    assert_eq!(debugger.run_command("p x").unwrap(), "x: int = 7");
+    assert_eq!(
+        debugger.run_command("p doubled").unwrap(),
+        "doubled: int = 14"
+    );
+    assert_eq!(debugger.run_command("p 16").unwrap(), "RAM[16] = 6");
+    assert!(debugger.run_command("p y").is_err());
+    let backtrace = debugger.run_command("bt").unwrap();
+    assert!(backtrace
+        .starts_with("#0 Main.double at Main.vm:6 push local 0\n   argument: [7]\n   local: [14]"));
+    assert!(backtrace.contains("#1 Sys.init at Sys.vm:6 call Main.double 1"));
+    assert!(debugger.run_command("jump").is_err());
+}
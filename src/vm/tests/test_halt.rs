@@ -0,0 +1,115 @@
+use vm::halt::{same_live_ram, HaltDetector, HaltReason};
+use vm::interpreter::{Interpreter, Program};
+
+fn boot(main: &str) -> Interpreter {
+    let mut program = Program::from_modules(&[("Main", main)]).unwrap();
+    program.link_builtins();
+    let mut interpreter = Interpreter::new(program);
+    interpreter.bootstrap().unwrap();
+    interpreter
+}
+
+/// Runs with the detector checking each step, up to `max_steps`.
+fn run(interpreter: &mut Interpreter, detector: &mut HaltDetector, max_steps: u64) -> HaltReason {
+    for _ in 0..max_steps {
+        if let Some(reason) = detector.check(interpreter) {
+            return reason;
+        }
+        interpreter.step().unwrap();
+    }
+    HaltReason::StepLimit(max_steps)
+}
+
+#[test]
+fn test_self_jump() {
+    // `while (true) {}` as compiled with and without optimizations
+    for body in [
+        "label L\ngoto L",
+        "label L\npush constant 0\nnot\nnot\nif-goto END\ngoto L\nlabel END",
+    ] {
+        let main = format!(
+            "function Main.main 0\npush constant 7\npop static 0\n{}\n",
+            body
+        );
+        let mut interpreter = boot(&main);
+        let reason = run(&mut interpreter, &mut HaltDetector::default(), 1000);
+        let HaltReason::Loop { pc } = reason else {
+            panic!("{:?} for {}", reason, body);
+        };
+        assert_eq!(interpreter.program.commands[pc].raw.trim(), "goto L");
+        assert_eq!(interpreter.peek(16), 7);
+    }
+}
+
+#[test]
+fn test_loops_which_end() {
+    for body in [
+        // Exits on the second pass, entering the loop from its middle
+        "goto B\nlabel A\npush constant 1\nif-goto END\nlabel B\ngoto A\nlabel END",
+        // Counts down
+        "push constant 3\npop static 0\nlabel L\npush static 0\npush constant 1\nsub\npop static 0\npush static 0\nif-goto L",
+        // Leaves a value on the stack at each pass
+        "label L\npush constant 0\ngoto L",
+    ] {
+        let main = format!("function Main.main 0\n{}\npush constant 0\nreturn\n", body);
+        let mut interpreter = boot(&main);
+        let reason = run(&mut interpreter, &mut HaltDetector::default(), 1000);
+        match reason {
+            HaltReason::Halted => {}
+            HaltReason::StepLimit(_) if body.ends_with("goto L") => {}
+            _ => panic!("{:?} for {}", reason, body),
+        }
+    }
+}
+
+#[test]
+fn test_idle() {
+    // Waits for a static which nothing sets
+    let main = "
+        function Main.main 0
+        label WAIT
+        push static 0
+        if-goto END
+        goto WAIT
+        label END
+        push constant 0
+        return
+    ";
+    let mut interpreter = boot(main);
+    assert_eq!(
+        run(&mut interpreter, &mut HaltDetector::default(), 1000),
+        HaltReason::StepLimit(1000)
+    );
+    let mut detector = HaltDetector::new(Some(100));
+    detector.input_until = 5000;
+    assert_eq!(
+        run(&mut interpreter, &mut detector, 10_000),
+        HaltReason::Idle(100)
+    );
+    assert_eq!(interpreter.steps, 5100);
+
+    // Set from outside, the program goes on and halts
+    interpreter.poke(16, -1);
+    assert_eq!(
+        run(&mut interpreter, &mut detector, 1000),
+        HaltReason::Halted
+    );
+}
+
+#[test]
+fn test_same_live_ram() {
+    let mut a = vec![0; 0x8000];
+    a[0] = 260;
+    let mut b = a.clone();
+    b[260] = 5;
+    b[2047] = 5;
+    assert!(same_live_ram(&a, &b));
+    b[259] = 5;
+    assert!(!same_live_ram(&a, &b));
+    b[259] = 0;
+    b[2048] = 5;
+    assert!(!same_live_ram(&a, &b));
+    b[2048] = 0;
+    b[0] = 261;
+    assert!(!same_live_ram(&a, &b));
+}
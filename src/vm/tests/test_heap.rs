@@ -0,0 +1,208 @@
+use vm::builtins::{HEAP_BASE, HEAP_LENGTH};
+use vm::heap::{self, Block, HeapProblem, HEAP_END};
+use vm::interpreter::{Interpreter, Program};
+
+/// RAM with a heap freshly initialized by `Memory.init`.
+fn empty_heap() -> Vec<i16> {
+    let mut ram = vec![0; 32768];
+    ram[HEAP_BASE + 1] = HEAP_LENGTH;
+    ram
+}
+
+/// Takes a block of `size` words from the end of the free block at `from`,
+/// like `Memory.alloc`, returning its header address.
+fn take(ram: &mut [i16], from: usize, size: i16) -> usize {
+    ram[from + 1] -= size + 2;
+    let address = from + ram[from + 1] as usize;
+    ram[address] = from as i16;
+    ram[address + 1] = size;
+    address
+}
+
+#[test]
+fn test_empty_heap() {
+    let report = heap::inspect(&empty_heap());
+    assert!(report.is_ok());
+    assert_eq!(
+        report.free,
+        [Block {
+            address: HEAP_BASE,
+            length: HEAP_LENGTH as usize
+        }]
+    );
+    assert!(report.allocated.is_empty());
+    assert_eq!(report.free_words(), HEAP_LENGTH as usize);
+    assert_eq!(report.fragmentation(), 0.0);
+}
+
+#[test]
+fn test_allocated_blocks() {
+    let mut ram = empty_heap();
+    let a = take(&mut ram, HEAP_BASE, 3);
+    let b = take(&mut ram, HEAP_BASE, 10);
+    let report = heap::inspect(&ram);
+    assert!(report.is_ok(), "{}", report);
+    assert_eq!(
+        report.allocated,
+        [
+            Block {
+                address: b,
+                length: 12
+            },
+            Block {
+                address: a,
+                length: 5
+            },
+        ]
+    );
+    assert_eq!(b + 12, a);
+    assert_eq!(a + 5, HEAP_END);
+    assert_eq!(report.allocated_words(), 17);
+    assert_eq!(report.free_words(), HEAP_LENGTH as usize - 17);
+}
+
+#[test]
+fn test_fragmentation() {
+    let mut ram = empty_heap();
+    let a = take(&mut ram, HEAP_BASE, 98);
+    take(&mut ram, HEAP_BASE, 10);
+    // Free `a`, appending it to the free list
+    ram[HEAP_BASE] = a as i16;
+    ram[a] = 0;
+    ram[a + 1] = 100;
+    let report = heap::inspect(&ram);
+    assert!(report.is_ok(), "{}", report);
+    assert_eq!(report.free.len(), 2);
+    assert_eq!(report.allocated.len(), 1);
+    assert_eq!(report.largest_free(), HEAP_LENGTH as usize - 112);
+    let expected = 100.0 / (HEAP_LENGTH as f64 - 12.0);
+    assert!((report.fragmentation() - expected).abs() < 1e-9);
+}
+
+#[test]
+fn test_free_list_cycle() {
+    let mut ram = empty_heap();
+    let a = take(&mut ram, HEAP_BASE, 8);
+    ram[HEAP_BASE] = a as i16;
+    ram[a] = HEAP_BASE as i16;
+    ram[a + 1] = 10;
+    let report = heap::inspect(&ram);
+    assert_eq!(
+        report.problems,
+        [HeapProblem::Cycle {
+            from: a,
+            to: HEAP_BASE
+        }]
+    );
+}
+
+#[test]
+fn test_invalid_link() {
+    let mut ram = empty_heap();
+    ram[HEAP_BASE] = 100;
+    let report = heap::inspect(&ram);
+    assert_eq!(
+        report.problems,
+        [HeapProblem::InvalidLink {
+            from: HEAP_BASE,
+            to: 100
+        }]
+    );
+}
+
+#[test]
+fn test_invalid_block() {
+    let mut ram = empty_heap();
+    let a = take(&mut ram, HEAP_BASE, 8);
+    ram[a + 1] = 1000;
+    let report = heap::inspect(&ram);
+    assert_eq!(
+        report.problems,
+        [HeapProblem::InvalidBlock {
+            address: a,
+            length: 1000
+        }]
+    );
+
+    let report = heap::inspect(&vec![0; 32768]);
+    assert!(!report.is_ok());
+    assert!(report.free.is_empty());
+}
+
+#[test]
+fn test_overlap() {
+    let mut ram = empty_heap();
+    let a = take(&mut ram, HEAP_BASE, 8);
+    // A free block inside the allocated one
+    ram[HEAP_BASE] = (a + 4) as i16;
+    ram[a + 4] = 0;
+    ram[a + 5] = 2;
+    let report = heap::inspect(&ram);
+    assert_eq!(
+        report.problems,
+        [HeapProblem::Overlap {
+            first: a,
+            second: a + 4
+        }]
+    );
+    assert!(report.to_string().contains("Problem: blocks"));
+}
+
+#[test]
+fn test_allocated_since() {
+    let mut ram = empty_heap();
+    take(&mut ram, HEAP_BASE, 3);
+    let before = heap::inspect(&ram);
+    let b = take(&mut ram, HEAP_BASE, 4);
+    let after = heap::inspect(&ram);
+    assert_eq!(
+        after.allocated_since(&before),
+        [Block {
+            address: b,
+            length: 6
+        }]
+    );
+    assert!(before.allocated_since(&before).is_empty());
+}
+
+/// The built-in `Memory` keeps the heap consistent, leaking nothing once the
+/// blocks are freed.
+#[test]
+fn test_builtin_memory() {
+    let source = "
+        function Main.main 2
+        push constant 3
+        call Memory.alloc 1
+        pop local 0
+        push constant 20
+        call Memory.alloc 1
+        pop local 1
+        push local 0
+        call Memory.deAlloc 1
+        pop temp 0
+        push local 1
+        call Memory.deAlloc 1
+        pop temp 0
+        push constant 5
+        call Memory.alloc 1
+        pop static 0
+        call Sys.halt 0
+        ";
+    let mut program = Program::from_modules(&[("Main", source)]).unwrap();
+    program.link_builtins();
+    let mut vm = Interpreter::new(program);
+    vm.bootstrap().unwrap();
+    let before = heap::inspect(&vm.ram);
+    vm.run(10_000).unwrap();
+    assert!(vm.is_halted());
+    let report = heap::inspect(&vm.ram);
+    assert!(report.is_ok(), "{}", report);
+    assert_eq!(report.free.len(), 2);
+    let leaked = report.allocated_since(&before);
+    assert_eq!(leaked.len(), 1);
+    assert_eq!(leaked[0].address + 2, vm.ram[16] as usize);
+    assert_eq!(
+        report.free_words() + report.allocated_words(),
+        HEAP_LENGTH as usize
+    );
+}
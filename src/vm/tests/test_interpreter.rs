@@ -0,0 +1,173 @@
+use vm::interpreter::{Interpreter, Program, ARG, LCL, SP, THAT, THIS};
+
+fn load(modules: &[(&str, &str)]) -> Interpreter {
+    Interpreter::new(Program::from_modules(modules).unwrap())
+}
+
+#[test]
+fn test_stack_arithmetic() {
+    let mut vm = load(&[(
+        "StackTest",
+        "
+        push constant 17
+        push constant 17
+        eq
+        push constant 892
+        push constant 891
+        lt
+        push constant 32767
+        push constant 32766
+        gt
+        push constant 57
+        push constant 31
+        push constant 53
+        add
+        push constant 112
+        sub
+        neg
+        and
+        push constant 82
+        or
+        not
+        ",
+    )]);
+    vm.ram[SP] = 256;
+    vm.run(1000).unwrap();
+    assert!(vm.is_halted());
+    assert_eq!(vm.ram[SP], 260);
+    assert_eq!(&vm.ram[256..260], &[-1, 0, -1, -91]);
+}
+
+#[test]
+fn test_segments() {
+    let mut vm = load(&[(
+        "BasicTest",
+        "
+        push constant 10
+        pop local 0
+        push constant 21
+        push constant 22
+        pop argument 2
+        pop argument 1
+        push constant 36
+        pop this 6
+        push constant 3030
+        pop pointer 0
+        push constant 3040
+        pop pointer 1
+        push constant 32
+        pop this 2
+        push constant 46
+        pop that 6
+        push constant 510
+        pop temp 6
+        push constant 333
+        pop static 3
+        push local 0
+        push that 6
+        add
+        push this 2
+        push temp 6
+        add
+        push static 3
+        ",
+    )]);
+    vm.ram[SP] = 256;
+    vm.ram[LCL] = 300;
+    vm.ram[ARG] = 400;
+    vm.run(1000).unwrap();
+    assert_eq!(vm.ram[300], 10);
+    assert_eq!(&vm.ram[401..403], &[21, 22]);
+    assert_eq!(vm.ram[THIS], 3030);
+    assert_eq!(vm.ram[THAT], 3040);
+    assert_eq!(vm.ram[3032], 32);
+    assert_eq!(vm.ram[3046], 46);
+    assert_eq!(vm.ram[11], 510);
+    assert_eq!(vm.ram[16], 333);
+    assert_eq!(&vm.ram[256..259], &[56, 542, 333]);
+}
+
+#[test]
+fn test_call_return_with_bootstrap() {
+    let mut vm = load(&[
+        (
+            "Main",
+            "
+            // Computes the n'th Fibonacci number recursively
+            function Main.fibonacci 0
+            push argument 0
+            push constant 2
+            lt
+            if-goto IF_TRUE
+            goto IF_FALSE
+            label IF_TRUE
+            push argument 0
+            return
+            label IF_FALSE
+            push argument 0
+            push constant 2
+            sub
+            call Main.fibonacci 1
+            push argument 0
+            push constant 1
+            sub
+            call Main.fibonacci 1
+            add
+            return
+            ",
+        ),
+        (
+            "Sys",
+            "
+            function Sys.init 0
+            push constant 4000
+            pop pointer 0
+            push constant 5000
+            pop pointer 1
+            push constant 12
+            call Main.fibonacci 1
+            pop static 0
+            label WHILE
+            goto WHILE
+            ",
+        ),
+    ]);
+    vm.bootstrap().unwrap();
+    vm.run(100_000).unwrap();
+    assert!(!vm.is_halted());
+    assert_eq!(vm.ram[16], 144);
+    // Caller frame is restored after returns
+    assert_eq!(vm.ram[SP], 261);
+    assert_eq!(vm.ram[LCL], 261);
+    assert_eq!(vm.ram[THIS], 4000);
+    assert_eq!(vm.ram[THAT], 5000);
+}
+
+#[test]
+fn test_unknown_function() {
+    let mut vm = load(&[("Main", "function Main.main 0\ncall Foo.bar 0\nreturn")]);
+    vm.ram[SP] = 256;
+    let err = vm.run(10).unwrap_err();
+    assert_eq!(err.to_string(), "Unknown function: Foo.bar");
+    assert_eq!(vm.pc, 1);
+}
+
+#[test]
+fn test_check_modules() {
+    let modules = |source: &str| [("Bad".to_string(), source.to_string())];
+    let err = vm::check_modules(&modules("foo bar\npush constant 1\npop local\n")).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "Bad.vm:1: Unable to parse line: foo bar\nBad.vm:3: Unable to parse line: pop local"
+    );
+    let err = vm::check_modules(&modules("// Comment\npush banana 3")).unwrap_err();
+    assert_eq!(err.to_string(), "Bad.vm:2: Unknown segment: push banana 3");
+    let err = vm::check_modules(&modules("pop constant 3")).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "Bad.vm:1: Can't pop to constant: pop constant 3"
+    );
+    let err = vm::check_modules(&modules("push constant 1\nfoo")).unwrap_err();
+    assert_eq!(err.to_string(), "Bad.vm:2: Unknown instruction: foo");
+    assert!(vm::check_modules(&modules("\n// Comment\npush constant 1 // One\n\nneg")).is_ok());
+}
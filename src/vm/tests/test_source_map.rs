@@ -0,0 +1,62 @@
+use vm::{config::TranslateOptions, translate_with_map};
+
+const MAIN: &str = "\
+// Adds 2 and 3
+function Main.main 0
+    push constant 2
+    push constant 3
+
+    add
+    return
+";
+
+fn vm_lines(options: &TranslateOptions) -> Vec<(String, Option<usize>)> {
+    let modules = [("Main".to_string(), MAIN.to_string())];
+    let (asm, map) = translate_with_map(&modules, "Main", options);
+    asm.lines()
+        .enumerate()
+        .map(|(i, line)| {
+            let location = map.get(i + 1);
+            if let Some(location) = location {
+                assert_eq!(location.file, "Main.vm");
+            }
+            (line.to_string(), location.map(|location| location.line))
+        })
+        .collect()
+}
+
+#[test]
+fn test_translate_map() {
+    for compact in [false, true] {
+        let options = TranslateOptions {
+            compact,
+            ..Default::default()
+        };
+        let lines = vm_lines(&options);
+        // Bootstrap and routines aren't mapped
+        assert_eq!(lines[0].1, None);
+        assert!(lines
+            .iter()
+            .any(|(line, vm_line)| line.contains("Sys.init") && vm_line.is_none()));
+        // Each command, with its comment, maps to its line
+        let commands: Vec<(&str, usize)> = lines
+            .iter()
+            .filter(|(line, _)| line.starts_with("// "))
+            .map(|(line, vm_line)| (line.as_str(), vm_line.unwrap()))
+            .collect();
+        assert_eq!(
+            commands,
+            vec![
+                ("// function Main.main 0", 2),
+                ("//     push constant 2", 3),
+                ("//     push constant 3", 4),
+                ("//     add", 6),
+                ("//     return", 7),
+            ]
+        );
+        let return_lines = lines.iter().skip_while(|(line, _)| line != "//     return");
+        assert!(return_lines
+            .take_while(|(line, _)| !line.starts_with("///@module-end"))
+            .all(|(_, vm_line)| *vm_line == Some(7)));
+    }
+}